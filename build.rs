@@ -0,0 +1,34 @@
+//! Embed all templates of `templates/` into the binary
+//!
+//! Generates a list of `(name, source)` pairs, see `src/templates.rs`
+use std::path::PathBuf;
+
+fn main() {
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").expect("Set by cargo"));
+    let template_dir = manifest_dir.join("templates");
+    println!("cargo:rerun-if-changed={}", template_dir.display());
+
+    let mut templates = std::fs::read_dir(&template_dir)
+        .expect("The templates directory exists")
+        .map(|entry| entry.expect("Can read the templates directory").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "html"))
+        .collect::<Vec<_>>();
+    templates.sort();
+
+    let mut code = String::from("pub(crate) static EMBEDDED_TEMPLATES: &[(&str, &str)] = &[\n");
+    for path in templates {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .expect("Template names are valid UTF-8");
+        code.push_str(&format!(
+            "    ({name:?}, include_str!({:?})),\n",
+            path.display().to_string()
+        ));
+    }
+    code.push_str("];\n");
+
+    let out_file = PathBuf::from(std::env::var_os("OUT_DIR").expect("Set by cargo"))
+        .join("embedded_templates.rs");
+    std::fs::write(out_file, code).expect("Can write the embedded templates");
+}
@@ -9,6 +9,8 @@ last_name = Nachname
 club = Verein/Ort
 category = Altersklasse
 birth_year = Geburtsjahr
+start_list = Startliste
+bib = Startnummer
 start_time = Startzeit
 
 no_registered_participants_yet = Noch keine angemeldeten Teilnehmer
@@ -69,3 +71,137 @@ short_registration = Anmeldung
 edit_participant = Teilnehmer bearbeiten
 short_edit_participant = Teilnehmer bearbeiten
 short_add_participant = Teilnehmer hinzufügen
+
+results = Ergebnisse
+finish_time = Zielzeit
+certificates = Urkunden
+certificate_template = Urkundenvorlage
+title = Titel
+content = Inhalt
+available_placeholders = Verfügbare Platzhalter
+certificate_default_title = Urkunde
+certificate_default_body =
+    %competition%, %date%
+    %first_name% %last_name%
+    %club%
+    absolvierte den Lauf über %race% in %time%
+    und belegte in der Altersklasse %category% den %rank%. Platz
+
+series = Serien
+new_series = Serie erstellen
+edit_series = Serie bearbeiten
+standings = Gesamtwertung
+points_per_rank = Punkte pro Platz
+best_n = Anzahl der gewerteten Ergebnisse
+rank = Platz
+total = Gesamt
+no_results_yet = Noch keine Ergebnisse
+
+persons = Personen
+participations = Teilnahmen
+match_participants = Nicht zugeordnete Teilnehmer zuordnen
+link_participant = Teilnehmer zuordnen
+unlink = Zuordnung aufheben
+
+payments = Zahlungen
+entry_fees = Startgebühren
+entry_fee = Startgebühr
+late_entry_fee = Nachmeldegebühr
+late_entry_from = Nachmeldung ab
+surcharge = Aufpreis
+unpaid_participants = Offene Zahlungen
+paid = Bezahlt
+reference_code = Zahlungsreferenz
+amount_due = Offener Betrag
+total_due = Summe offen
+total_received = Summe erhalten
+mark_paid = Als bezahlt markieren
+waive_fee = Startgeld erlassen
+bank_statement = Kontoauszug
+import_payments = Zahlungen importieren
+marked_as_paid = Als bezahlt markiert
+unmatched_lines = Zeilen ohne Zahlungsreferenz
+registration_confirmed = Vielen Dank für die Anmeldung
+payment_instructions = Bitte das Startgeld mit dem Referenzcode als Verwendungszweck überweisen
+
+audit_log = Änderungsprotokoll
+entity = Objekt
+action = Aktion
+user = Benutzer
+filter = Filtern
+time = Zeit
+before = Vorher
+after = Nachher
+
+trash = Papierkorb
+trash_retention_days = Tage bis gelöschte Einträge endgültig entfernt werden können
+purge_expired = Abgelaufene Einträge endgültig löschen
+deleted_at = Gelöscht am
+purgeable = Endgültig löschbar
+restore = Wiederherstellen
+
+competition = Wettkampf
+race = Strecke
+start = Start
+special_category = Sonderwertung
+confirm_delete = Soll der folgende Eintrag wirklich gelöscht werden?
+cancel = Abbrechen
+
+login_throttles = Fehlgeschlagene Anmeldungen
+throttle_kind = Art
+throttle_key = Benutzername / IP-Adresse
+failed_attempts = Fehlversuche
+last_failed_at = Letzter Fehlversuch
+blocked_until = Gesperrt bis
+locked = Gesperrt
+clear = Zurücksetzen
+
+logout = Abmelden
+my_sessions = Meine Sitzungen
+logged_in_at = Angemeldet am
+expires_at = Läuft ab am
+current_session = Aktuelle Sitzung
+revoke = Beenden
+revoke_other_sessions = Alle anderen Sitzungen beenden
+
+change_password = Passwort ändern
+current_password = Aktuelles Passwort
+new_password = Neues Passwort
+new_password_confirmation = Neues Passwort wiederholen
+password_changed = Das Passwort wurde geändert
+wrong_current_password = Das aktuelle Passwort ist falsch
+password_confirmation_mismatch = Die neuen Passwörter stimmen nicht überein
+password_unchanged = Das neue Passwort muss sich vom aktuellen unterscheiden
+password_too_short = Das neue Passwort muss mindestens 12 Zeichen lang sein
+password_too_long = Das neue Passwort darf höchstens 128 Zeichen lang sein
+password_contains_username = Das neue Passwort darf den Benutzernamen nicht enthalten
+two_factor_authentication = Zwei-Faktor-Authentifizierung
+two_factor_enabled = Die Zwei-Faktor-Authentifizierung ist aktiviert
+two_factor_disabled = Die Zwei-Faktor-Authentifizierung ist deaktiviert
+enable_two_factor = Zwei-Faktor-Authentifizierung aktivieren
+disable_two_factor = Zwei-Faktor-Authentifizierung deaktivieren
+authentication_code = Authentifizierungscode
+enter_authentication_code = Code aus der Authenticator-App oder einen Wiederherstellungscode eingeben
+invalid_authentication_code = Der Authentifizierungscode ist ungültig
+scan_qr_code = Diesen QR-Code mit der Authenticator-App scannen und den angezeigten Code eingeben
+secret_for_manual_entry = Schlüssel für die manuelle Eingabe
+remaining_recovery_codes = Unbenutzte Wiederherstellungscodes
+recovery_codes_hint = Diese Wiederherstellungscodes sicher aufbewahren. Jeder davon kann einmal anstelle eines Codes aus der Authenticator-App verwendet werden. Sie werden nur jetzt angezeigt.
+api_tokens = API-Tokens
+create_api_token = API-Token erstellen
+new_api_token_hint = Dieses Token jetzt kopieren, es wird nicht noch einmal angezeigt. Es wird als `Authorization: Bearer` Header gesendet.
+api_token_scope = Berechtigung
+api_token_scope_read = Nur lesen
+api_token_scope_write = Lesen und schreiben
+last_used_at = Zuletzt verwendet am
+expired = abgelaufen
+created_at = Erstellt am
+download_backup = Sicherung jetzt herunterladen
+branding = Erscheinungsbild
+accent_color = Akzentfarbe
+accent_background_color = Akzent-Hintergrundfarbe
+default_color_hint = Leere Farben werden durch die Standardfarbe ersetzt
+footer = Fußzeile
+logo = Logo
+logo_hint = PNG-, JPEG-, GIF- oder WebP-Bild, höchstens
+remove_logo = Logo entfernen
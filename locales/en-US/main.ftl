@@ -9,6 +9,8 @@ last_name = Last Name
 club = Club/City
 category = Category
 birth_year = Birth year
+start_list = Start List
+bib = Bib
 start_time = Start time
 
 no_registered_participants_yet = No registered participants yet
@@ -69,3 +71,137 @@ short_registration = Registration
 edit_participant = Edit Participant
 short_edit_participant = Edit Participant
 short_add_participant = Add Participant
+
+results = Results
+finish_time = Finish time
+certificates = Certificates
+certificate_template = Certificate Template
+title = Title
+content = Content
+available_placeholders = Available placeholders
+certificate_default_title = Certificate
+certificate_default_body =
+    %competition%, %date%
+    %first_name% %last_name%
+    %club%
+    finished the %race% in %time%
+    and reached rank %rank% in the category %category%
+
+series = Series
+new_series = Create Series
+edit_series = Edit Series
+standings = Standings
+points_per_rank = Points per rank
+best_n = Number of counted results
+rank = Rank
+total = Total
+no_results_yet = No results yet
+
+persons = Persons
+participations = Participations
+match_participants = Match unlinked participants
+link_participant = Link participant
+unlink = Unlink
+
+payments = Payments
+entry_fees = Entry fees
+entry_fee = Entry fee
+late_entry_fee = Late entry fee
+late_entry_from = Late entry from
+surcharge = Surcharge
+unpaid_participants = Unpaid participants
+paid = Paid
+reference_code = Reference code
+amount_due = Amount due
+total_due = Total outstanding
+total_received = Total received
+mark_paid = Mark as paid
+waive_fee = Waive entry fee
+bank_statement = Bank statement
+import_payments = Import payments
+marked_as_paid = Marked as paid
+unmatched_lines = Lines without reference code
+registration_confirmed = Thank you for your registration
+payment_instructions = Please transfer the entry fee and use the reference code as payment reference
+
+audit_log = Audit log
+entity = Entity
+action = Action
+user = User
+filter = Filter
+time = Time
+before = Before
+after = After
+
+trash = Trash
+trash_retention_days = Days until deleted entries can be purged
+purge_expired = Purge expired entries
+deleted_at = Deleted at
+purgeable = Purgeable
+restore = Restore
+
+competition = Competition
+race = Race
+start = Start
+special_category = Special Category
+confirm_delete = Do you really want to delete the following entry?
+cancel = Cancel
+
+login_throttles = Failed logins
+throttle_kind = Type
+throttle_key = User name / IP address
+failed_attempts = Failed attempts
+last_failed_at = Last failed attempt
+blocked_until = Blocked until
+locked = Locked
+clear = Clear
+
+logout = Logout
+my_sessions = My sessions
+logged_in_at = Logged in at
+expires_at = Expires at
+current_session = Current session
+revoke = Revoke
+revoke_other_sessions = Revoke all other sessions
+
+change_password = Change password
+current_password = Current password
+new_password = New password
+new_password_confirmation = Repeat new password
+password_changed = Your password was changed
+wrong_current_password = The current password is wrong
+password_confirmation_mismatch = The new passwords do not match
+password_unchanged = The new password must differ from the current one
+password_too_short = The new password needs at least 12 characters
+password_too_long = The new password must not have more than 128 characters
+password_contains_username = The new password must not contain the user name
+two_factor_authentication = Two-factor authentication
+two_factor_enabled = Two-factor authentication is enabled
+two_factor_disabled = Two-factor authentication is disabled
+enable_two_factor = Enable two-factor authentication
+disable_two_factor = Disable two-factor authentication
+authentication_code = Authentication code
+enter_authentication_code = Enter the code from your authenticator app or one of your recovery codes
+invalid_authentication_code = The authentication code is invalid
+scan_qr_code = Scan this QR code with your authenticator app and enter the code it shows
+secret_for_manual_entry = Secret for manual entry
+remaining_recovery_codes = Unused recovery codes
+recovery_codes_hint = Store these recovery codes in a safe place. Each of them can be used once instead of a code from your authenticator app. They are only shown now.
+api_tokens = API tokens
+create_api_token = Create API token
+new_api_token_hint = Copy this token now, it is not shown again. Send it as `Authorization: Bearer` header.
+api_token_scope = Scope
+api_token_scope_read = Read only
+api_token_scope_write = Read and write
+last_used_at = Last used at
+expired = expired
+created_at = Created at
+download_backup = Download backup now
+branding = Branding
+accent_color = Accent color
+accent_background_color = Accent background color
+default_color_hint = Leave a color empty to use the default one
+footer = Footer
+logo = Logo
+logo_hint = PNG, JPEG, GIF or WebP image, at most
+remove_logo = Remove logo
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS `certificate_templates`;
+DROP TABLE IF EXISTS `results`;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE `results`(
+	`participant_id` INTEGER NOT NULL PRIMARY KEY REFERENCES participants(id) ON DELETE CASCADE,
+	-- finish time in seconds
+	`finish_time` INTEGER NOT NULL
+);
+
+CREATE TABLE `certificate_templates`(
+	`competition_id` INTEGER NOT NULL PRIMARY KEY REFERENCES competitions(id) ON DELETE CASCADE,
+	`title` TEXT NOT NULL,
+	`body` TEXT NOT NULL
+);
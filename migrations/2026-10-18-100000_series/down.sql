@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS `competitions_in_series`;
+DROP TABLE IF EXISTS `series`;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE `series`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`name` TEXT NOT NULL,
+	`description` TEXT NOT NULL,
+	-- comma separated list of points awarded for rank 1, 2, 3, …
+	`points_per_rank` TEXT NOT NULL,
+	-- only the best N results of a participant count for the standings
+	`best_n` INTEGER NOT NULL
+);
+
+CREATE TABLE `competitions_in_series`(
+	`series_id` INTEGER NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+	`competition_id` INTEGER NOT NULL REFERENCES competitions(id) ON DELETE CASCADE,
+	PRIMARY KEY(`series_id`, `competition_id`)
+);
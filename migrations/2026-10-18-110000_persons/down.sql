@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `participants` DROP COLUMN `person_id`;
+DROP TABLE IF EXISTS `persons`;
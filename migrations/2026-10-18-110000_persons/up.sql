@@ -0,0 +1,25 @@
+-- Your SQL goes here
+CREATE TABLE `persons`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`first_name` TEXT NOT NULL,
+	`last_name` TEXT NOT NULL,
+	`birth_year` INTEGER NOT NULL,
+	`club` TEXT
+);
+
+CREATE INDEX `persons_identity` ON `persons`(`last_name`, `first_name`, `birth_year`);
+
+ALTER TABLE `participants` ADD COLUMN `person_id` INTEGER REFERENCES persons(id) ON DELETE SET NULL;
+
+-- create a person for each distinct participant identity
+INSERT INTO `persons`(`first_name`, `last_name`, `birth_year`, `club`)
+SELECT MAX(`first_name`), MAX(`last_name`), `birth_year`, MAX(`club`)
+FROM `participants`
+GROUP BY lower(trim(`first_name`)), lower(trim(`last_name`)), `birth_year`;
+
+UPDATE `participants` SET `person_id` = (
+	SELECT `persons`.`id` FROM `persons`
+	WHERE lower(trim(`persons`.`first_name`)) = lower(trim(`participants`.`first_name`))
+	AND lower(trim(`persons`.`last_name`)) = lower(trim(`participants`.`last_name`))
+	AND `persons`.`birth_year` = `participants`.`birth_year`
+);
@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE `participants` DROP COLUMN `paid_at`;
+ALTER TABLE `participants` DROP COLUMN `paid_amount`;
+ALTER TABLE `participants` DROP COLUMN `payment_status`;
+ALTER TABLE `participants` DROP COLUMN `registered_at`;
+
+ALTER TABLE `special_categories` DROP COLUMN `surcharge`;
+
+ALTER TABLE `races` DROP COLUMN `late_entry_from`;
+ALTER TABLE `races` DROP COLUMN `late_entry_fee`;
+ALTER TABLE `races` DROP COLUMN `entry_fee`;
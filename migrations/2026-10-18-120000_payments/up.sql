@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- all amounts are stored in cents
+ALTER TABLE `races` ADD COLUMN `entry_fee` INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE `races` ADD COLUMN `late_entry_fee` INTEGER;
+ALTER TABLE `races` ADD COLUMN `late_entry_from` DATE;
+
+ALTER TABLE `special_categories` ADD COLUMN `surcharge` INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE `participants` ADD COLUMN `registered_at` TIMESTAMP;
+ALTER TABLE `participants` ADD COLUMN `payment_status` TEXT NOT NULL DEFAULT 'open' CHECK(`payment_status` IN ('open', 'paid', 'waived'));
+ALTER TABLE `participants` ADD COLUMN `paid_amount` INTEGER;
+ALTER TABLE `participants` ADD COLUMN `paid_at` TIMESTAMP;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE `audit_log`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`user_id` INTEGER REFERENCES users(id) ON DELETE SET NULL,
+	`action` TEXT NOT NULL CHECK(`action` IN ('create', 'update', 'delete')),
+	`entity` TEXT NOT NULL,
+	`entity_id` INTEGER NOT NULL,
+	-- JSON objects containing only the changed fields
+	`before` TEXT,
+	`after` TEXT
+);
+
+CREATE INDEX `audit_log_entity` ON `audit_log`(`entity`, `entity_id`);
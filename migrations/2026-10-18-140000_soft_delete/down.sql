@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM `participants` WHERE `deleted_at` IS NOT NULL;
+DELETE FROM `competitions` WHERE `deleted_at` IS NOT NULL;
+ALTER TABLE `participants` DROP COLUMN `deleted_at`;
+ALTER TABLE `competitions` DROP COLUMN `deleted_at`;
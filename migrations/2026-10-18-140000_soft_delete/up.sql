@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- soft deleted entries are kept in the trash until they are purged
+ALTER TABLE `competitions` ADD COLUMN `deleted_at` TIMESTAMP;
+ALTER TABLE `participants` ADD COLUMN `deleted_at` TIMESTAMP;
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE `login_throttles`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`kind` TEXT NOT NULL CHECK(`kind` IN ('username', 'ip')),
+	-- the user name or the IP address
+	`key` TEXT NOT NULL,
+	`failed_attempts` INTEGER NOT NULL,
+	`last_failed_at` TIMESTAMP NOT NULL,
+	`locked_until` TIMESTAMP,
+	UNIQUE(`kind`, `key`)
+);
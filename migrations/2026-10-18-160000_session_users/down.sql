@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE `session_records`;
+
+CREATE TABLE `session_records`(
+	`id` BINARY NOT NULL PRIMARY KEY,
+	`data` TEXT NOT NULL,
+	`expiry_date` TEXT NOT NULL
+);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- existing sessions cannot be assigned to a user, so they are dropped
+DROP TABLE `session_records`;
+
+CREATE TABLE `session_records`(
+	`id` BINARY NOT NULL PRIMARY KEY,
+	`data` TEXT NOT NULL,
+	`expiry_date` TEXT NOT NULL,
+	-- identifies the session on the session list without revealing the session id
+	`public_id` TEXT UNIQUE NOT NULL,
+	`user_id` INTEGER REFERENCES `users`(`id`) ON DELETE CASCADE,
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX `session_records_user_id` ON `session_records`(`user_id`);
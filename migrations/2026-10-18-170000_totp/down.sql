@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE `recovery_codes`;
+ALTER TABLE `users` DROP COLUMN `totp_last_used_step`;
+ALTER TABLE `users` DROP COLUMN `totp_secret`;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- base32 encoded shared secret, set once two-factor authentication is enabled
+ALTER TABLE `users` ADD COLUMN `totp_secret` TEXT;
+-- time step of the last accepted code, used to reject replayed codes
+ALTER TABLE `users` ADD COLUMN `totp_last_used_step` BIGINT;
+
+CREATE TABLE `recovery_codes`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`user_id` INTEGER NOT NULL REFERENCES `users`(`id`) ON DELETE CASCADE,
+	-- hex encoded SHA-256 hash of the code
+	`code_hash` TEXT NOT NULL,
+	`used_at` TIMESTAMP
+);
+
+CREATE INDEX `recovery_codes_user_id` ON `recovery_codes`(`user_id`);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE `api_tokens`(
+	`id` INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	`user_id` INTEGER NOT NULL REFERENCES `users`(`id`) ON DELETE CASCADE,
+	-- describes what the token is used for
+	`name` TEXT NOT NULL,
+	-- hex encoded SHA-256 hash of the token
+	`token_hash` TEXT UNIQUE NOT NULL,
+	`scope` TEXT NOT NULL CHECK(`scope` IN ('read', 'write')),
+	`created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`expires_at` TIMESTAMP,
+	`last_used_at` TIMESTAMP
+);
+
+CREATE INDEX `api_tokens_user_id` ON `api_tokens`(`user_id`);
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE `competition_branding`(
+	`competition_id` INTEGER NOT NULL PRIMARY KEY REFERENCES competitions(id) ON DELETE CASCADE,
+	-- CSS colors like `#0f9e18`, the default colors are used if unset
+	`accent_color` TEXT,
+	`accent_background_color` TEXT,
+	-- plain text shown at the bottom of the public pages
+	`footer` TEXT NOT NULL DEFAULT '',
+	`logo` BLOB,
+	`logo_content_type` TEXT,
+	-- part of the asset urls, so browsers do not keep outdated versions
+	`updated_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	CHECK((`logo` IS NULL) = (`logo_content_type` IS NULL))
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX `persons_identity`;
+CREATE INDEX `persons_identity` ON `persons`(`last_name`, `first_name`, `birth_year`);
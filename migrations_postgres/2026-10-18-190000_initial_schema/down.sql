@@ -0,0 +1,19 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE api_tokens;
+DROP TABLE recovery_codes;
+DROP TABLE session_records;
+DROP TABLE login_throttles;
+DROP TABLE audit_log;
+DROP TABLE competitions_in_series;
+DROP TABLE series;
+DROP TABLE certificate_templates;
+DROP TABLE results;
+DROP TABLE participants_in_special_category;
+DROP TABLE participants;
+DROP TABLE persons;
+DROP TABLE special_categories;
+DROP TABLE categories;
+DROP TABLE starts;
+DROP TABLE races;
+DROP TABLE competitions;
+DROP TABLE users;
@@ -0,0 +1,181 @@
+-- Your SQL goes here
+-- PostgreSQL schema equivalent to all SQLite migrations up to
+-- 2026-10-18-180000_api_tokens, later migrations are added to both directories
+-- all timestamps without time zone are stored in UTC
+
+CREATE TABLE users(
+	id SERIAL PRIMARY KEY,
+	name TEXT UNIQUE NOT NULL,
+	password TEXT NOT NULL,
+	-- base32 encoded shared secret, set once two-factor authentication is enabled
+	totp_secret TEXT,
+	-- time step of the last accepted code, used to reject replayed codes
+	totp_last_used_step BIGINT
+);
+
+CREATE TABLE competitions(
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL,
+	description TEXT NOT NULL,
+	date DATE NOT NULL,
+	location TEXT NOT NULL,
+	announcement TEXT NOT NULL,
+	-- soft deleted entries are kept in the trash until they are purged
+	deleted_at TIMESTAMP
+);
+
+-- all amounts are stored in cents
+CREATE TABLE races(
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL,
+	competition_id INTEGER NOT NULL REFERENCES competitions(id) ON DELETE CASCADE,
+	entry_fee INTEGER NOT NULL DEFAULT 0,
+	late_entry_fee INTEGER,
+	late_entry_from DATE
+);
+
+CREATE TABLE starts(
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL,
+	time TIMESTAMP NOT NULL,
+	race_id INTEGER NOT NULL REFERENCES races(id) ON DELETE CASCADE
+);
+
+CREATE TABLE categories(
+	id SERIAL PRIMARY KEY,
+	label TEXT NOT NULL,
+	from_age INTEGER NOT NULL,
+	to_age INTEGER NOT NULL,
+	male BOOLEAN NOT NULL,
+	start_id INTEGER NOT NULL REFERENCES starts(id) ON DELETE CASCADE
+);
+
+CREATE TABLE special_categories(
+	id SERIAL PRIMARY KEY,
+	short_name TEXT NOT NULL,
+	name TEXT NOT NULL,
+	race_id INTEGER NOT NULL REFERENCES races(id) ON DELETE CASCADE,
+	surcharge INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE persons(
+	id SERIAL PRIMARY KEY,
+	first_name TEXT NOT NULL,
+	last_name TEXT NOT NULL,
+	birth_year INTEGER NOT NULL,
+	club TEXT
+);
+
+CREATE INDEX persons_identity ON persons(last_name, first_name, birth_year);
+
+CREATE TABLE participants(
+	id SERIAL PRIMARY KEY,
+	last_name TEXT NOT NULL,
+	first_name TEXT NOT NULL,
+	club TEXT,
+	category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+	consent_agb BOOLEAN NOT NULL,
+	birth_year INTEGER NOT NULL,
+	person_id INTEGER REFERENCES persons(id) ON DELETE SET NULL,
+	registered_at TIMESTAMP,
+	payment_status TEXT NOT NULL DEFAULT 'open' CHECK(payment_status IN ('open', 'paid', 'waived')),
+	paid_amount INTEGER,
+	paid_at TIMESTAMP,
+	deleted_at TIMESTAMP
+);
+
+CREATE TABLE participants_in_special_category(
+	participant_id INTEGER NOT NULL REFERENCES participants(id) ON DELETE CASCADE,
+	special_category_id INTEGER NOT NULL REFERENCES special_categories(id) ON DELETE CASCADE,
+	PRIMARY KEY(participant_id, special_category_id)
+);
+
+CREATE TABLE results(
+	participant_id INTEGER PRIMARY KEY REFERENCES participants(id) ON DELETE CASCADE,
+	-- finish time in seconds
+	finish_time INTEGER NOT NULL
+);
+
+CREATE TABLE certificate_templates(
+	competition_id INTEGER PRIMARY KEY REFERENCES competitions(id) ON DELETE CASCADE,
+	title TEXT NOT NULL,
+	body TEXT NOT NULL
+);
+
+CREATE TABLE series(
+	id SERIAL PRIMARY KEY,
+	name TEXT NOT NULL,
+	description TEXT NOT NULL,
+	-- comma separated list of points awarded for rank 1, 2, 3, …
+	points_per_rank TEXT NOT NULL,
+	-- only the best N results of a participant count for the standings
+	best_n INTEGER NOT NULL
+);
+
+CREATE TABLE competitions_in_series(
+	series_id INTEGER NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+	competition_id INTEGER NOT NULL REFERENCES competitions(id) ON DELETE CASCADE,
+	PRIMARY KEY(series_id, competition_id)
+);
+
+CREATE TABLE audit_log(
+	id SERIAL PRIMARY KEY,
+	created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+	user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+	action TEXT NOT NULL CHECK(action IN ('create', 'update', 'delete')),
+	entity TEXT NOT NULL,
+	entity_id INTEGER NOT NULL,
+	-- JSON objects containing only the changed fields
+	before TEXT,
+	after TEXT
+);
+
+CREATE INDEX audit_log_entity ON audit_log(entity, entity_id);
+
+CREATE TABLE login_throttles(
+	id SERIAL PRIMARY KEY,
+	kind TEXT NOT NULL CHECK(kind IN ('username', 'ip')),
+	-- the user name or the IP address
+	key TEXT NOT NULL,
+	failed_attempts INTEGER NOT NULL,
+	last_failed_at TIMESTAMP NOT NULL,
+	locked_until TIMESTAMP,
+	UNIQUE(kind, key)
+);
+
+CREATE TABLE session_records(
+	id BYTEA PRIMARY KEY,
+	data TEXT NOT NULL,
+	expiry_date TIMESTAMPTZ NOT NULL,
+	-- identifies the session on the session list without revealing the session id
+	public_id TEXT UNIQUE NOT NULL,
+	user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+	created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX session_records_user_id ON session_records(user_id);
+
+CREATE TABLE recovery_codes(
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	-- hex encoded SHA-256 hash of the code
+	code_hash TEXT NOT NULL,
+	used_at TIMESTAMP
+);
+
+CREATE INDEX recovery_codes_user_id ON recovery_codes(user_id);
+
+CREATE TABLE api_tokens(
+	id SERIAL PRIMARY KEY,
+	user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+	-- describes what the token is used for
+	name TEXT NOT NULL,
+	-- hex encoded SHA-256 hash of the token
+	token_hash TEXT UNIQUE NOT NULL,
+	scope TEXT NOT NULL CHECK(scope IN ('read', 'write')),
+	created_at TIMESTAMP NOT NULL DEFAULT (CURRENT_TIMESTAMP AT TIME ZONE 'UTC'),
+	expires_at TIMESTAMP,
+	last_used_at TIMESTAMP
+);
+
+CREATE INDEX api_tokens_user_id ON api_tokens(user_id);
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- persons are looked up by their normalized name, see `find_or_create_person`
+DROP INDEX persons_identity;
+CREATE INDEX persons_identity ON persons(lower(trim(last_name)), lower(trim(first_name)), birth_year);
@@ -0,0 +1,168 @@
+//! Admin page setup for API tokens
+//!
+//! Users create tokens for scripts that cannot use the login form, e.g. the
+//! timing bridge. Each token can be limited to read-only requests and can
+//! expire at a given date. See `user::api_token` for how tokens are checked.
+use super::current_user_id;
+use super::user::api_token::{self, ApiTokenScope};
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::api_tokens;
+use crate::database::{DbBackend, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::{Form, Router};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use time::macros::format_description;
+use time::{Date, PrimitiveDateTime};
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route("/api_tokens.html", axum::routing::get(list_api_tokens))
+        .route("/api_tokens", axum::routing::post(create_api_token))
+        .route(
+            "/api_tokens/:token_id/revoke",
+            axum::routing::post(revoke_api_token),
+        )
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(check_for_backend(DbBackend))]
+struct ApiToken {
+    id: Id,
+    name: String,
+    scope: ApiTokenScope,
+    created_at: PrimitiveDateTime,
+    expires_at: Option<PrimitiveDateTime>,
+    last_used_at: Option<PrimitiveDateTime>,
+}
+
+#[derive(Serialize)]
+struct ApiTokenEntry {
+    #[serde(flatten)]
+    token: ApiToken,
+    expired: bool,
+}
+
+/// Data used to render the API token page
+///
+/// See `templates/admin_api_tokens.html` for the relevant template
+#[derive(Serialize)]
+struct ApiTokensData {
+    tokens: Vec<ApiTokenEntry>,
+    /// a newly created token, which is only shown this one time
+    new_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CreateApiTokenForm {
+    name: String,
+    scope: ApiTokenScope,
+    /// date from which on the token is not accepted anymore, empty for no expiry
+    expires_on: String,
+}
+
+async fn render_api_tokens(
+    state: &AppState,
+    user_id: Id,
+    new_token: Option<String>,
+) -> Result<Html<String>> {
+    let now = crate::database::now();
+    let tokens = state
+        .with_connection(move |conn| {
+            api_tokens::table
+                .filter(api_tokens::user_id.eq(user_id))
+                .order_by(api_tokens::created_at.desc())
+                .select(ApiToken::as_select())
+                .load(conn)
+        })
+        .await?;
+    let tokens = tokens
+        .into_iter()
+        .map(|token| ApiTokenEntry {
+            expired: token.expires_at.is_some_and(|e| e <= now),
+            token,
+        })
+        .collect();
+    state.render_template("admin_api_tokens.html", ApiTokensData { tokens, new_token })
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_api_tokens(state: AppState, auth_session: AuthSession) -> Result<Html<String>> {
+    let user_id = current_user_id(&auth_session)?;
+    render_api_tokens(&state, user_id, None).await
+}
+
+/// Create a new token for the current user and show it once
+#[axum::debug_handler(state = app_state::State)]
+async fn create_api_token(
+    state: AppState,
+    auth_session: AuthSession,
+    Form(form): Form<CreateApiTokenForm>,
+) -> Result<Html<String>> {
+    let user_id = current_user_id(&auth_session)?;
+    let name = form.name.trim().to_owned();
+    if name.is_empty() {
+        return Err(Error::InvalidInput(String::from(
+            "An API token requires a name",
+        )));
+    }
+    let expires_at = Some(form.expires_on.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            Date::parse(v, format_description!("[year]-[month]-[day]"))
+                .map_err(|e| Error::InvalidInput(format!("Invalid date `{v}`: {e}")))
+        })
+        .transpose()?
+        .map(Date::midnight);
+    let token = api_token::generate_token();
+    let token_hash = api_token::hash_token(&token);
+    let scope = form.scope;
+    let token_id = state
+        .with_connection(move |conn| {
+            diesel::insert_into(api_tokens::table)
+                .values((
+                    api_tokens::user_id.eq(user_id),
+                    api_tokens::name.eq(name),
+                    api_tokens::token_hash.eq(token_hash),
+                    api_tokens::scope.eq(scope),
+                    api_tokens::expires_at.eq(expires_at),
+                ))
+                .returning(api_tokens::id)
+                .get_result::<Id>(conn)
+        })
+        .await?;
+    tracing::info!(user_id, token_id, ?scope, "Created API token");
+    render_api_tokens(&state, user_id, Some(token)).await
+}
+
+/// Remove a token of the current user, it is rejected from now on
+#[axum::debug_handler(state = app_state::State)]
+async fn revoke_api_token(
+    state: AppState,
+    auth_session: AuthSession,
+    token_id: Path<Id>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = current_user_id(&auth_session)?;
+    let token_id = token_id.0;
+    let count = state
+        .with_connection(move |conn| {
+            diesel::delete(
+                api_tokens::table
+                    .find(token_id)
+                    .filter(api_tokens::user_id.eq(user_id)),
+            )
+            .execute(conn)
+        })
+        .await?;
+    if count != 1 {
+        return Err(Error::NotFound(format!(
+            "API token with id {token_id} not found"
+        )));
+    }
+    tracing::info!(user_id, token_id, "Revoked API token");
+    Ok(Redirect::to(&format!("{base_url}/admin/api_tokens.html")))
+}
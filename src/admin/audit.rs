@@ -0,0 +1,599 @@
+//! Audit log for all changes done via the admin pages
+//!
+//! Each change records the user that performed it, the action, the affected
+//! entity and a before/after snapshot that only contains the changed fields.
+//! Snapshots are taken inside the same transaction as the change itself.
+//! Deleting an entity also records the deletion of all entities that are
+//! removed together with it by cascading deletes, see `audited_delete`.
+use crate::app_state::{self, AppState};
+use crate::database::schema::{
+    audit_log, categories, competition_branding, competitions, competitions_in_series,
+    participants, races, results, series, special_categories, starts, users,
+};
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::errors::Result;
+use axum::extract::Query;
+use axum::response::Html;
+use axum::Router;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use time::{Date, PrimitiveDateTime};
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new().route("/audit.html", axum::routing::get(list_audit_entries))
+}
+
+/// Maximal number of entries shown at once in the audit log view
+const MAX_ENTRIES: i64 = 500;
+
+/// Entities that are tracked in the audit log
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Entity {
+    Competition,
+    Race,
+    Start,
+    Category,
+    SpecialCategory,
+    Participant,
+    /// the result of a participant, identified by the participant id
+    Result,
+    Series,
+    /// the branding of a competition, identified by the competition id
+    Branding,
+}
+
+impl Entity {
+    const ALL: [Self; 9] = [
+        Self::Competition,
+        Self::Race,
+        Self::Start,
+        Self::Category,
+        Self::SpecialCategory,
+        Self::Participant,
+        Self::Result,
+        Self::Series,
+        Self::Branding,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Competition => "competition",
+            Self::Race => "race",
+            Self::Start => "start",
+            Self::Category => "category",
+            Self::SpecialCategory => "special_category",
+            Self::Participant => "participant",
+            Self::Result => "result",
+            Self::Series => "series",
+            Self::Branding => "branding",
+        }
+    }
+}
+
+/// Kind of change recorded in the audit log
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    Create,
+    Update,
+    Delete,
+}
+
+impl Action {
+    const ALL: [&'static str; 3] = ["create", "update", "delete"];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+fn display<T: Display, S: serde::Serializer>(
+    value: &T,
+    ser: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    value.to_string().serialize(ser)
+}
+
+fn display_optional<T: Display, S: serde::Serializer>(
+    value: &Option<T>,
+    ser: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    value.as_ref().map(ToString::to_string).serialize(ser)
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = competitions)]
+#[diesel(check_for_backend(DbBackend))]
+struct CompetitionSnapshot {
+    id: Id,
+    name: String,
+    description: String,
+    #[serde(serialize_with = "display")]
+    date: Date,
+    location: String,
+    announcement: String,
+    #[serde(serialize_with = "display_optional")]
+    deleted_at: Option<PrimitiveDateTime>,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = races)]
+#[diesel(check_for_backend(DbBackend))]
+struct RaceSnapshot {
+    id: Id,
+    name: String,
+    competition_id: Id,
+    entry_fee: i32,
+    late_entry_fee: Option<i32>,
+    #[serde(serialize_with = "display_optional")]
+    late_entry_from: Option<Date>,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = starts)]
+#[diesel(check_for_backend(DbBackend))]
+struct StartSnapshot {
+    id: Id,
+    name: String,
+    #[serde(serialize_with = "display")]
+    time: PrimitiveDateTime,
+    race_id: Id,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = categories)]
+#[diesel(check_for_backend(DbBackend))]
+struct CategorySnapshot {
+    id: Id,
+    label: String,
+    from_age: i32,
+    to_age: i32,
+    male: bool,
+    start_id: Id,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = special_categories)]
+#[diesel(check_for_backend(DbBackend))]
+struct SpecialCategorySnapshot {
+    id: Id,
+    short_name: String,
+    name: String,
+    race_id: Id,
+    surcharge: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+struct ParticipantSnapshot {
+    id: Id,
+    last_name: String,
+    first_name: String,
+    club: Option<String>,
+    category_id: Id,
+    consent_agb: bool,
+    birth_year: i32,
+    person_id: Option<Id>,
+    #[serde(serialize_with = "display_optional")]
+    registered_at: Option<PrimitiveDateTime>,
+    payment_status: String,
+    paid_amount: Option<i32>,
+    #[serde(serialize_with = "display_optional")]
+    paid_at: Option<PrimitiveDateTime>,
+    #[serde(serialize_with = "display_optional")]
+    deleted_at: Option<PrimitiveDateTime>,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = results)]
+#[diesel(check_for_backend(DbBackend))]
+struct ResultSnapshot {
+    participant_id: Id,
+    finish_time: i32,
+}
+
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = series)]
+#[diesel(check_for_backend(DbBackend))]
+struct SeriesSnapshot {
+    id: Id,
+    name: String,
+    description: String,
+    points_per_rank: String,
+    best_n: i32,
+}
+
+/// A series together with the competitions belonging to it
+#[derive(Serialize)]
+struct SeriesWithCompetitions {
+    #[serde(flatten)]
+    series: SeriesSnapshot,
+    competition_ids: Vec<Id>,
+}
+
+/// The logo itself is only tracked as whether there is one
+#[derive(Queryable, Selectable, Serialize)]
+#[diesel(table_name = competition_branding)]
+#[diesel(check_for_backend(DbBackend))]
+struct BrandingSnapshot {
+    competition_id: Id,
+    accent_color: Option<String>,
+    accent_background_color: Option<String>,
+    footer: String,
+    #[diesel(select_expression = competition_branding::logo.is_not_null())]
+    has_logo: bool,
+}
+
+fn to_json<T: Serialize>(row: Option<T>) -> QueryResult<Option<Value>> {
+    row.map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))
+}
+
+/// Load the current state of an entity as JSON object
+///
+/// Returns `None` if the entity does not exist
+pub(crate) fn snapshot(
+    conn: &mut DbConnection,
+    entity: Entity,
+    id: Id,
+) -> QueryResult<Option<Value>> {
+    match entity {
+        Entity::Competition => to_json(
+            competitions::table
+                .find(id)
+                .select(CompetitionSnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Race => to_json(
+            races::table
+                .find(id)
+                .select(RaceSnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Start => to_json(
+            starts::table
+                .find(id)
+                .select(StartSnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Category => to_json(
+            categories::table
+                .find(id)
+                .select(CategorySnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::SpecialCategory => to_json(
+            special_categories::table
+                .find(id)
+                .select(SpecialCategorySnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Participant => to_json(
+            participants::table
+                .find(id)
+                .select(ParticipantSnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Result => to_json(
+            results::table
+                .find(id)
+                .select(ResultSnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Branding => to_json(
+            competition_branding::table
+                .find(id)
+                .select(BrandingSnapshot::as_select())
+                .first(conn)
+                .optional()?,
+        ),
+        Entity::Series => {
+            let Some(series) = series::table
+                .find(id)
+                .select(SeriesSnapshot::as_select())
+                .first(conn)
+                .optional()?
+            else {
+                return Ok(None);
+            };
+            let competition_ids = competitions_in_series::table
+                .filter(competitions_in_series::series_id.eq(id))
+                .order_by(competitions_in_series::competition_id)
+                .select(competitions_in_series::competition_id)
+                .load(conn)?;
+            to_json(Some(SeriesWithCompetitions {
+                series,
+                competition_ids,
+            }))
+        }
+    }
+}
+
+/// Reduce two snapshots of the same entity to the fields that differ
+fn diff(before: Option<Value>, after: Option<Value>) -> (Option<Value>, Option<Value>) {
+    match (before, after) {
+        (Some(Value::Object(before)), Some(Value::Object(after))) => {
+            let changed = |a: &Map<String, Value>, b: &Map<String, Value>| {
+                a.iter()
+                    .filter(|(k, v)| b.get(*k) != Some(*v))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect::<Map<_, _>>()
+            };
+            (
+                Some(Value::Object(changed(&before, &after))),
+                Some(Value::Object(changed(&after, &before))),
+            )
+        }
+        other => other,
+    }
+}
+
+/// Whether the snapshot belongs to an entity that was moved to the trash
+fn is_soft_deleted(snapshot: &Value) -> bool {
+    snapshot
+        .get("deleted_at")
+        .is_some_and(|deleted_at| !deleted_at.is_null())
+}
+
+/// Record a change of an entity based on the snapshots before and after the change
+///
+/// The action is derived from the snapshots. Moving an entity to the trash
+/// is recorded as deletion. Nothing is recorded if the entity did not
+/// change at all
+pub(crate) fn record(
+    conn: &mut DbConnection,
+    user_id: Option<Id>,
+    entity: Entity,
+    entity_id: Id,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> QueryResult<()> {
+    let action = match (&before, &after) {
+        (None, None) => return Ok(()),
+        (None, Some(_)) => Action::Create,
+        (Some(_), None) => Action::Delete,
+        (Some(before), Some(after)) if before == after => return Ok(()),
+        (Some(before), Some(after)) if is_soft_deleted(after) && !is_soft_deleted(before) => {
+            Action::Delete
+        }
+        (Some(_), Some(_)) => Action::Update,
+    };
+    let (before, after) = diff(before, after);
+    diesel::insert_into(audit_log::table)
+        .values((
+            audit_log::user_id.eq(user_id),
+            audit_log::action.eq(action.as_str()),
+            audit_log::entity.eq(entity.as_str()),
+            audit_log::entity_id.eq(entity_id),
+            audit_log::before.eq(before.map(|v| v.to_string())),
+            audit_log::after.eq(after.map(|v| v.to_string())),
+        ))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Record the creation of a new entity
+pub(crate) fn record_created(
+    conn: &mut DbConnection,
+    user_id: Option<Id>,
+    entity: Entity,
+    entity_id: Id,
+) -> QueryResult<()> {
+    let after = snapshot(conn, entity, entity_id)?;
+    record(conn, user_id, entity, entity_id, None, after)
+}
+
+/// Run a mutation of an existing entity and record the change in the audit log
+///
+/// This covers updates and moving an entity to the trash, deletions have to
+/// use `audited_delete`
+pub(crate) fn audited<T>(
+    conn: &mut DbConnection,
+    user_id: Option<Id>,
+    entity: Entity,
+    entity_id: Id,
+    mutation: impl FnOnce(&mut DbConnection) -> QueryResult<T>,
+) -> QueryResult<T> {
+    conn.transaction(|conn| {
+        let before = snapshot(conn, entity, entity_id)?;
+        let out = mutation(conn)?;
+        let after = snapshot(conn, entity, entity_id)?;
+        record(conn, user_id, entity, entity_id, before, after)?;
+        Ok(out)
+    })
+}
+
+/// Filter options for the audit log view
+///
+/// Empty values are ignored
+#[derive(Deserialize, Serialize, Default)]
+struct AuditFilter {
+    #[serde(default)]
+    entity: String,
+    #[serde(default)]
+    entity_id: String,
+    #[serde(default)]
+    user_id: String,
+    #[serde(default)]
+    action: String,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = audit_log)]
+#[diesel(check_for_backend(DbBackend))]
+struct AuditEntry {
+    id: Id,
+    created_at: PrimitiveDateTime,
+    #[diesel(select_expression = users::name.nullable())]
+    user: Option<String>,
+    action: String,
+    entity: String,
+    entity_id: Id,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+#[derive(Queryable, Serialize)]
+struct UserOption {
+    id: Id,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct AuditLogData {
+    entries: Vec<AuditEntry>,
+    users: Vec<UserOption>,
+    selected_user: Option<Id>,
+    entities: Vec<&'static str>,
+    actions: [&'static str; 3],
+    filter: AuditFilter,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_audit_entries(state: AppState, filter: Query<AuditFilter>) -> Result<Html<String>> {
+    let filter = filter.0;
+    let entity = Some(filter.entity.clone()).filter(|e| !e.is_empty());
+    let action = Some(filter.action.clone()).filter(|a| !a.is_empty());
+    let entity_id = filter.entity_id.trim().parse::<Id>().ok();
+    let user_id = filter.user_id.trim().parse::<Id>().ok();
+    let (entries, users) = state
+        .with_connection(move |conn| {
+            let mut query = audit_log::table
+                .left_join(users::table)
+                .order_by(audit_log::id.desc())
+                .limit(MAX_ENTRIES)
+                .select(AuditEntry::as_select())
+                .into_boxed();
+            if let Some(entity) = entity {
+                query = query.filter(audit_log::entity.eq(entity));
+            }
+            if let Some(entity_id) = entity_id {
+                query = query.filter(audit_log::entity_id.eq(entity_id));
+            }
+            if let Some(user_id) = user_id {
+                query = query.filter(audit_log::user_id.eq(user_id));
+            }
+            if let Some(action) = action {
+                query = query.filter(audit_log::action.eq(action));
+            }
+            let entries = query.load(conn)?;
+            let users = users::table
+                .order_by(users::name)
+                .select((users::id, users::name))
+                .load::<UserOption>(conn)?;
+            QueryResult::Ok((entries, users))
+        })
+        .await?;
+
+    state.render_template(
+        "admin_audit_log.html",
+        AuditLogData {
+            entries,
+            users,
+            selected_user: user_id,
+            entities: Entity::ALL.iter().map(|e| e.as_str()).collect(),
+            actions: Action::ALL,
+            filter,
+        },
+    )
+}
+
+/// Entities directly removed together with an entity by `ON DELETE CASCADE`
+fn children(conn: &mut DbConnection, entity: Entity, id: Id) -> QueryResult<Vec<(Entity, Id)>> {
+    let with_entity =
+        |entity: Entity| move |ids: Vec<Id>| ids.into_iter().map(move |id| (entity, id));
+    Ok(match entity {
+        Entity::Competition => {
+            let races = races::table
+                .filter(races::competition_id.eq(id))
+                .select(races::id)
+                .load(conn)
+                .map(with_entity(Entity::Race))?;
+            let branding = competition_branding::table
+                .find(id)
+                .select(competition_branding::competition_id)
+                .load(conn)
+                .map(with_entity(Entity::Branding))?;
+            races.chain(branding).collect()
+        }
+        Entity::Race => {
+            let starts = starts::table
+                .filter(starts::race_id.eq(id))
+                .select(starts::id)
+                .load(conn)
+                .map(with_entity(Entity::Start))?;
+            let special_categories = special_categories::table
+                .filter(special_categories::race_id.eq(id))
+                .select(special_categories::id)
+                .load(conn)
+                .map(with_entity(Entity::SpecialCategory))?;
+            starts.chain(special_categories).collect()
+        }
+        Entity::Start => categories::table
+            .filter(categories::start_id.eq(id))
+            .select(categories::id)
+            .load(conn)
+            .map(with_entity(Entity::Category))?
+            .collect(),
+        Entity::Category => participants::table
+            .filter(participants::category_id.eq(id))
+            .select(participants::id)
+            .load(conn)
+            .map(with_entity(Entity::Participant))?
+            .collect(),
+        Entity::Participant => results::table
+            .find(id)
+            .select(results::participant_id)
+            .load(conn)
+            .map(with_entity(Entity::Result))?
+            .collect(),
+        // the competitions of a series are part of its snapshot
+        Entity::SpecialCategory | Entity::Result | Entity::Series | Entity::Branding => Vec::new(),
+    })
+}
+
+/// Run the deletion of an entity and record it in the audit log
+///
+/// Contrary to `audited` this records the deletion of all entities removed
+/// by cascading deletes as well, e.g. the starts, categories and participants
+/// of a deleted race
+pub(crate) fn audited_delete<T>(
+    conn: &mut DbConnection,
+    user_id: Option<Id>,
+    entity: Entity,
+    entity_id: Id,
+    mutation: impl FnOnce(&mut DbConnection) -> QueryResult<T>,
+) -> QueryResult<T> {
+    conn.transaction(|conn| {
+        let mut affected = vec![(entity, entity_id)];
+        let mut next = 0;
+        while let Some(&(entity, id)) = affected.get(next) {
+            affected.extend(children(conn, entity, id)?);
+            next += 1;
+        }
+        let before = affected
+            .iter()
+            .map(|&(entity, id)| snapshot(conn, entity, id))
+            .collect::<QueryResult<Vec<_>>>()?;
+        let out = mutation(conn)?;
+        for ((entity, id), before) in affected.into_iter().zip(before) {
+            let after = snapshot(conn, entity, id)?;
+            record(conn, user_id, entity, id, before, after)?;
+        }
+        Ok(out)
+    })
+}
@@ -0,0 +1,51 @@
+//! Admin route to download a backup of the database
+//!
+//! The backup is taken the same way as the scheduled backups, see
+//! `crate::database::backup`, so it is safe to use while the event runs.
+//!
+//! A backup contains secrets like session ids, password hashes and the
+//! second factor of all users. It is therefore only available with a login
+//! session, API tokens of any scope are rejected.
+use crate::admin::user::api_token::ApiUser;
+use crate::app_state::{self, AppState};
+use crate::database::backup;
+use crate::errors::{Error, Result};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use time::OffsetDateTime;
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new().route("/backup/download", axum::routing::get(download_backup))
+}
+
+/// Take a backup now and send it as file download
+#[axum::debug_handler(state = app_state::State)]
+async fn download_backup(
+    state: AppState,
+    api_user: Option<axum::Extension<ApiUser>>,
+) -> Result<Response> {
+    if let Some(axum::Extension(api_user)) = api_user {
+        tracing::warn!(
+            user_id = api_user.user.id,
+            "Rejected backup download with an API token"
+        );
+        return Err(Error::InsufficientScope);
+    }
+    let data = state
+        .with_connection(|conn| Ok(backup::snapshot(conn)))
+        .await?;
+    let file_name = backup::file_name(OffsetDateTime::now_utc());
+    tracing::info!(size = data.len(), "Downloaded database backup");
+    Ok((
+        [
+            (CONTENT_TYPE, String::from("application/vnd.sqlite3")),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{file_name}\""),
+            ),
+        ],
+        data,
+    )
+        .into_response())
+}
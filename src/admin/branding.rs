@@ -0,0 +1,250 @@
+//! Admin page setup for the branding of a competition
+//!
+//! The logo, accent colors and footer are shown on the public pages of the
+//! competition, see `crate::branding`. The edit page itself uses the stored
+//! branding as well, so it doubles as preview.
+use super::audit;
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::branding::{self, Branding};
+use crate::database::schema::{competition_branding, competitions};
+use crate::database::{self, Id};
+use crate::errors::{Error, Result};
+use axum::extract::{Multipart, Path};
+use axum::response::{Html, Redirect};
+use axum::Router;
+use diesel::prelude::*;
+use serde::Serialize;
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/competitions/:competition_id/branding.html",
+            axum::routing::get(render_edit_branding),
+        )
+        .route(
+            "/competitions/:competition_id/branding",
+            axum::routing::post(update_branding),
+        )
+}
+
+/// Maximal accepted size of an uploaded logo
+const MAX_LOGO_SIZE: usize = 512 * 1024;
+
+/// Image formats accepted as logo with their magic bytes
+///
+/// SVG is not accepted, as it can contain scripts
+const LOGO_FORMATS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+];
+
+/// Detect the content type of an uploaded logo from its content
+fn logo_content_type(logo: &[u8]) -> Option<&'static str> {
+    LOGO_FORMATS
+        .iter()
+        .find(|(magic, _)| logo.starts_with(magic))
+        .map(|(_, content_type)| *content_type)
+        .or_else(|| {
+            (logo.len() >= 12 && logo.starts_with(b"RIFF") && &logo[8..12] == b"WEBP")
+                .then_some("image/webp")
+        })
+}
+
+/// Accept an empty value as unset and colors like `#0f9e18` as they are
+fn parse_color(field: &str, value: String) -> Result<Option<String>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let is_color = value.len() == 7
+        && value.starts_with('#')
+        && value[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !is_color {
+        return Err(Error::InvalidInput(format!(
+            "`{value}` is not a valid color for `{field}`, expected a color like `#0f9e18`"
+        )));
+    }
+    Ok(Some(value.to_ascii_lowercase()))
+}
+
+#[derive(Serialize)]
+struct EditBrandingData {
+    competition_id: Id,
+    competition_name: String,
+    /// the stored branding, not to be confused with the `branding` of the page
+    current: Option<Branding>,
+    max_logo_size_kib: usize,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_edit_branding(state: AppState, competition_id: Path<Id>) -> Result<Html<String>> {
+    let competition_id = competition_id.0;
+    let (competition_name, current) = state
+        .with_connection(move |conn| {
+            let competition_name = competitions::table
+                .find(competition_id)
+                .select(competitions::name)
+                .first::<String>(conn)
+                .optional()?;
+            let current = branding::load(conn, competition_id)?;
+            QueryResult::Ok((competition_name, current))
+        })
+        .await?;
+    let competition_name = competition_name.ok_or_else(|| {
+        Error::NotFound(format!("Competition with id {competition_id} not found"))
+    })?;
+    state
+        .render_competition_template(
+            "edit_branding.html",
+            competition_id,
+            EditBrandingData {
+                competition_id,
+                competition_name,
+                current,
+                max_logo_size_kib: MAX_LOGO_SIZE / 1024,
+            },
+        )
+        .await
+}
+
+/// Submitted values of the branding form
+#[derive(Default)]
+struct BrandingForm {
+    accent_color: Option<String>,
+    accent_background_color: Option<String>,
+    footer: String,
+    /// newly uploaded logo with its content type
+    logo: Option<(Vec<u8>, &'static str)>,
+    remove_logo: bool,
+}
+
+impl BrandingForm {
+    async fn from_multipart(mut multipart: Multipart) -> Result<Self> {
+        let invalid =
+            |e: axum::extract::multipart::MultipartError| Error::InvalidInput(e.body_text());
+        let mut form = Self::default();
+        while let Some(field) = multipart.next_field().await.map_err(invalid)? {
+            match field.name().unwrap_or_default() {
+                "accent_color" => {
+                    form.accent_color =
+                        parse_color("accent_color", field.text().await.map_err(invalid)?)?;
+                }
+                "accent_background_color" => {
+                    form.accent_background_color = parse_color(
+                        "accent_background_color",
+                        field.text().await.map_err(invalid)?,
+                    )?;
+                }
+                "footer" => {
+                    // normalize line endings as send by browsers
+                    form.footer = field
+                        .text()
+                        .await
+                        .map_err(invalid)?
+                        .trim()
+                        .replace("\r\n", "\n");
+                }
+                "remove_logo" => form.remove_logo = true,
+                "logo" => {
+                    let logo = field.bytes().await.map_err(invalid)?;
+                    // browsers send an empty file if none was selected
+                    if logo.is_empty() {
+                        continue;
+                    }
+                    if logo.len() > MAX_LOGO_SIZE {
+                        return Err(Error::InvalidInput(format!(
+                            "The logo must not be larger than {} KiB",
+                            MAX_LOGO_SIZE / 1024
+                        )));
+                    }
+                    let content_type = logo_content_type(&logo).ok_or_else(|| {
+                        Error::InvalidInput(String::from(
+                            "The logo must be a PNG, JPEG, GIF or WebP image",
+                        ))
+                    })?;
+                    form.logo = Some((logo.to_vec(), content_type));
+                }
+                // the CSRF token is checked by `super::csrf`
+                _ => {}
+            }
+        }
+        Ok(form)
+    }
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn update_branding(
+    state: AppState,
+    auth_session: AuthSession,
+    competition_id: Path<Id>,
+    multipart: Multipart,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let competition_id = competition_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let form = BrandingForm::from_multipart(multipart).await?;
+    let found = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Branding,
+                competition_id,
+                |conn| {
+                    if !diesel::dsl::select(diesel::dsl::exists(
+                        competitions::table.find(competition_id),
+                    ))
+                    .get_result::<bool>(conn)?
+                    {
+                        return Ok(false);
+                    }
+                    let updated_at = database::now();
+                    diesel::insert_into(competition_branding::table)
+                        .values((
+                            competition_branding::competition_id.eq(competition_id),
+                            competition_branding::accent_color.eq(&form.accent_color),
+                            competition_branding::accent_background_color
+                                .eq(&form.accent_background_color),
+                            competition_branding::footer.eq(&form.footer),
+                            competition_branding::updated_at.eq(updated_at),
+                        ))
+                        .on_conflict(competition_branding::competition_id)
+                        .do_update()
+                        .set((
+                            competition_branding::accent_color.eq(&form.accent_color),
+                            competition_branding::accent_background_color
+                                .eq(&form.accent_background_color),
+                            competition_branding::footer.eq(&form.footer),
+                            competition_branding::updated_at.eq(updated_at),
+                        ))
+                        .execute(conn)?;
+                    let logo = match form.logo {
+                        Some((logo, content_type)) => Some((Some(logo), Some(content_type))),
+                        None if form.remove_logo => Some((None, None)),
+                        None => None,
+                    };
+                    if let Some((logo, content_type)) = logo {
+                        diesel::update(competition_branding::table.find(competition_id))
+                            .set((
+                                competition_branding::logo.eq(logo),
+                                competition_branding::logo_content_type.eq(content_type),
+                            ))
+                            .execute(conn)?;
+                    }
+                    Ok(true)
+                },
+            )
+        })
+        .await?;
+    if !found {
+        return Err(Error::NotFound(format!(
+            "Competition with id {competition_id} not found"
+        )));
+    }
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/competitions/{competition_id}/branding.html"
+    )))
+}
@@ -1,18 +1,22 @@
 //! Admin page setup for categories
+use super::audit;
+use super::user::auth_session::AuthSession;
 use crate::app_state::{self, AppState};
 use crate::database::Id;
 use crate::errors::Result;
-use axum::extract::Path;
+use axum::extract::{Path, Query};
 use axum::response::{Html, Redirect};
 use axum::{Form, Router};
+use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
 pub(crate) fn routes() -> Router<app_state::State> {
     let categories_router = Router::new()
         .route(
             "/:category_id/delete.html",
-            axum::routing::get(delete_category),
+            axum::routing::get(confirm_delete_category),
         )
+        .route("/:category_id/delete", axum::routing::post(delete_category))
         .route(
             "/:category_id/edit.html",
             axum::routing::get(render_edit_category),
@@ -65,9 +69,43 @@ async fn list_categories_per_start(state: AppState, start_id: Path<Id>) -> Resul
 }
 
 #[axum::debug_handler(state = app_state::State)]
-async fn delete_category(state: AppState, category_id: Path<Id>) -> Result<Redirect> {
+async fn confirm_delete_category(
+    state: AppState,
+    category_id: Path<Id>,
+    query: Query<super::RedirectInfo>,
+) -> Result<Html<String>> {
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "category",
+            id: category_id.0,
+            target_url: format!("categories/{}/delete", category_id.0),
+            cancel_url: query.0.redirect_to,
+            redirect_to: None,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn delete_category(
+    state: AppState,
+    auth_session: AuthSession,
+    category_id: Path<Id>,
+) -> Result<Redirect> {
     let base_url = state.base_url();
-    let start_id: Id = todo!();
+    let category_id = category_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let start_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited_delete(
+                conn,
+                user_id,
+                audit::Entity::Category,
+                category_id,
+                |conn| todo!("Delete the category and get the corresponding start id"),
+            )
+        })
+        .await?;
 
     Ok(Redirect::to(&format!(
         "{base_url}/admin/starts/{}/categories.html",
@@ -118,11 +156,20 @@ async fn render_create_category(state: AppState, start_id: Path<Id>) -> Result<H
 #[axum::debug_handler(state = app_state::State)]
 async fn create_category(
     state: AppState,
+    auth_session: AuthSession,
     start_id: Path<Id>,
     data: Form<CategoryFormInputData>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    todo!("Insert data here");
+    let user_id = auth_session.user.map(|u| u.id);
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let category_id: Id = todo!("Insert data here");
+                audit::record_created(conn, user_id, audit::Entity::Category, category_id)
+            })
+        })
+        .await?;
 
     Ok(Redirect::to(&format!(
         "{base_url}/admin/starts/{}/categories.html",
@@ -148,11 +195,24 @@ async fn render_edit_category(state: AppState, category_id: Path<Id>) -> Result<
 #[axum::debug_handler(state = app_state::State)]
 async fn update_category(
     state: AppState,
+    auth_session: AuthSession,
     category_id: Path<Id>,
     data: Form<CategoryFormInputData>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let start_id: Id = todo!("Verify that start exists here");
+    let category_id = category_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let start_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Category,
+                category_id,
+                |conn| todo!("Verify that start exists and update the category here"),
+            )
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/starts/{}/categories.html",
         start_id
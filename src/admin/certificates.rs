@@ -0,0 +1,279 @@
+//! Admin page setup for participant certificates
+//!
+//! Each competition has a certificate template consisting of a title and a body.
+//! Both can contain placeholders like `%first_name%` that are replaced by the
+//! actual participant data while generating the PDF file
+use super::results::{format_time, load_results_for_category};
+use crate::app_state::{self, AppState};
+use crate::database::schema::{categories, certificate_templates, competitions, races, starts};
+use crate::database::{DbBackend, Id};
+use crate::errors::{Error, Result};
+use crate::pdf::{self, PdfDocument};
+use axum::extract::Path;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{Form, Router};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/competitions/:competition_id/certificate.html",
+            axum::routing::get(render_edit_certificate_template),
+        )
+        .route(
+            "/competitions/:competition_id/certificate",
+            axum::routing::post(update_certificate_template),
+        )
+        .route(
+            "/categories/:category_id/certificates.pdf",
+            axum::routing::get(render_certificates_for_category),
+        )
+}
+
+/// All placeholders supported in certificate templates
+const PLACEHOLDERS: &[&str] = &[
+    "first_name",
+    "last_name",
+    "club",
+    "birth_year",
+    "category",
+    "race",
+    "rank",
+    "time",
+    "competition",
+    "date",
+    "location",
+];
+
+#[derive(Queryable, Selectable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = certificate_templates)]
+#[diesel(check_for_backend(DbBackend))]
+struct CertificateTemplate {
+    title: String,
+    body: String,
+}
+
+#[derive(Serialize)]
+struct EditCertificateTemplateData {
+    competition_id: Id,
+    competition_name: String,
+    template: CertificateTemplate,
+    placeholders: &'static [&'static str],
+}
+
+/// Template used if no custom template was stored for a competition yet
+fn default_template(state: &AppState) -> CertificateTemplate {
+    CertificateTemplate {
+        title: state.translation("certificate_default_title"),
+        body: state.translation("certificate_default_body"),
+    }
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_edit_certificate_template(
+    state: AppState,
+    competition_id: Path<Id>,
+) -> Result<Html<String>> {
+    let competition_id = competition_id.0;
+    let (competition_name, template) = state
+        .with_connection(move |conn| {
+            let competition_name = competitions::table
+                .find(competition_id)
+                .select(competitions::name)
+                .first::<String>(conn)
+                .optional()?;
+            let template = certificate_templates::table
+                .find(competition_id)
+                .select(CertificateTemplate::as_select())
+                .first(conn)
+                .optional()?;
+            QueryResult::Ok((competition_name, template))
+        })
+        .await?;
+    let competition_name = competition_name.ok_or_else(|| {
+        Error::NotFound(format!("Competition with id {competition_id} not found"))
+    })?;
+
+    state.render_template(
+        "edit_certificate_template.html",
+        EditCertificateTemplateData {
+            competition_id,
+            competition_name,
+            template: template.unwrap_or_else(|| default_template(&state)),
+            placeholders: PLACEHOLDERS,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn update_certificate_template(
+    state: AppState,
+    competition_id: Path<Id>,
+    data: Form<CertificateTemplate>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let competition_id = competition_id.0;
+    let CertificateTemplate { title, body } = data.0;
+    // normalize line endings as send by browsers
+    let body = body.replace("\r\n", "\n");
+    let count = state
+        .with_connection(move |conn| {
+            if !diesel::dsl::select(diesel::dsl::exists(
+                competitions::table.find(competition_id),
+            ))
+            .get_result::<bool>(conn)?
+            {
+                return Ok(0);
+            }
+            diesel::insert_into(certificate_templates::table)
+                .values((
+                    certificate_templates::competition_id.eq(competition_id),
+                    certificate_templates::title.eq(&title),
+                    certificate_templates::body.eq(&body),
+                ))
+                .on_conflict(certificate_templates::competition_id)
+                .do_update()
+                .set((
+                    certificate_templates::title.eq(&title),
+                    certificate_templates::body.eq(&body),
+                ))
+                .execute(conn)
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Competition with id {competition_id} not found"
+        )))
+    } else {
+        Ok(Redirect::to(&format!(
+            "{base_url}/admin/competitions/{competition_id}/certificate.html"
+        )))
+    }
+}
+
+/// Render one certificate per finisher of the given category
+///
+/// Participants without a result do not get a certificate
+#[axum::debug_handler(state = app_state::State)]
+async fn render_certificates_for_category(
+    state: AppState,
+    category_id: Path<Id>,
+) -> Result<Response> {
+    let category_id = category_id.0;
+    let (category, template, results) = state
+        .with_connection(move |conn| {
+            let category = categories::table
+                .inner_join(starts::table.inner_join(races::table.inner_join(competitions::table)))
+                .filter(categories::id.eq(category_id))
+                .select((
+                    categories::label,
+                    races::name,
+                    competitions::id,
+                    competitions::name,
+                    competitions::date,
+                    competitions::location,
+                ))
+                .first::<(String, String, Id, String, time::Date, String)>(conn)
+                .optional()?;
+            let Some(category) = category else {
+                return Ok((None, None, Vec::new()));
+            };
+            let template = certificate_templates::table
+                .find(category.2)
+                .select(CertificateTemplate::as_select())
+                .first(conn)
+                .optional()?;
+            let results = load_results_for_category(conn, category_id)?;
+            QueryResult::Ok((Some(category), template, results))
+        })
+        .await?;
+    let (category, race, _competition_id, competition, date, location) = category
+        .ok_or_else(|| Error::NotFound(format!("Category with id {category_id} not found")))?;
+    let template = template.unwrap_or_else(|| default_template(&state));
+
+    let mut doc = PdfDocument::new();
+    let mut rank = 0;
+    let mut previous_time = None;
+    for (idx, result) in results.into_iter().enumerate() {
+        let Some(finish_time) = result.finish_time else {
+            // results are ordered by finish time, so there are no further finishers
+            break;
+        };
+        // participants with the same finish time share their rank
+        if previous_time != Some(finish_time) {
+            rank = idx + 1;
+            previous_time = Some(finish_time);
+        }
+        let values = [
+            ("first_name", result.first_name),
+            ("last_name", result.last_name),
+            ("club", result.club.unwrap_or_default()),
+            ("birth_year", result.birth_year.to_string()),
+            ("category", category.clone()),
+            ("race", race.clone()),
+            ("rank", rank.to_string()),
+            ("time", format_time(finish_time)),
+            ("competition", competition.clone()),
+            ("date", date.to_string()),
+            ("location", location.clone()),
+        ];
+        doc.new_page();
+        let mut y = pdf::A4_HEIGHT - 200.0;
+        doc.centered_text(
+            y,
+            pdf::Font::Bold,
+            36.0,
+            &fill_placeholders(&template.title, &values),
+        );
+        y -= 80.0;
+        for line in fill_placeholders(&template.body, &values).lines() {
+            doc.centered_text(y, pdf::Font::Regular, 16.0, line);
+            y -= 28.0;
+        }
+    }
+
+    Ok((
+        [
+            (CONTENT_TYPE, String::from("application/pdf")),
+            (
+                CONTENT_DISPOSITION,
+                format!("inline; filename=\"certificates_{category_id}.pdf\""),
+            ),
+        ],
+        doc.finish(),
+    )
+        .into_response())
+}
+
+/// Replace all `%placeholder%` occurrences in the input by the provided values
+///
+/// The input is processed in a single pass, so values containing something
+/// like `%time%` are inserted verbatim. Unknown placeholders are kept as they are.
+fn fill_placeholders(input: &str, values: &[(&str, String)]) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('%') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after.find('%').and_then(|end| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &after[..end])
+                .map(|(_, value)| (value, end))
+        });
+        match placeholder {
+            Some((value, end)) => {
+                output.push_str(value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('%');
+                rest = after;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
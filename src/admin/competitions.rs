@@ -1,6 +1,9 @@
 //! Admin page setup for competitions
 
+use super::audit;
+use super::user::auth_session::AuthSession;
 use crate::app_state::{self, AppState};
+use crate::database::schema::competitions;
 use crate::database::shared_models::Competition;
 use crate::database::Id;
 use crate::errors::Error;
@@ -9,6 +12,7 @@ use axum::extract::Path;
 use axum::response::Html;
 use axum::response::Redirect;
 use axum::{Form, Router};
+use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 use time::Date;
 
@@ -20,7 +24,11 @@ pub fn routes() -> Router<app_state::State> {
             axum::routing::get(render_create_competition),
         )
         .route("/create", axum::routing::post(create_competition))
-        .route("/:id/delete.html", axum::routing::get(delete_competition))
+        .route(
+            "/:id/delete.html",
+            axum::routing::get(confirm_delete_competition),
+        )
+        .route("/:id/delete", axum::routing::post(delete_competition))
         .route(
             "/:id/edit.html",
             axum::routing::get(render_edit_competition),
@@ -52,7 +60,8 @@ pub(crate) struct NewCompetition {
 
 #[axum::debug_handler(state = app_state::State)]
 pub(crate) async fn list_competitions(state: AppState) -> Result<Html<String>> {
-    let competitions: Vec<CompetitionWithData> = todo!("Load data for competitions");
+    let competitions: Vec<CompetitionWithData> =
+        todo!("Load data for competitions (skip competitions in the trash)");
     state.render_template(
         "admin_competition_list.html",
         ListCompetitionData { competitions },
@@ -81,10 +90,19 @@ pub(crate) async fn render_create_competition(state: AppState) -> Result<Html<St
 #[axum::debug_handler(state = app_state::State)]
 pub(crate) async fn create_competition(
     state: AppState,
+    auth_session: AuthSession,
     data: Form<NewCompetition>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    todo!("Insert a new competition here");
+    let user_id = auth_session.user.map(|u| u.id);
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let competition_id: Id = todo!("Insert a new competition here");
+                audit::record_created(conn, user_id, audit::Entity::Competition, competition_id)
+            })
+        })
+        .await?;
 
     Ok(Redirect::to(&format!(
         "{base_url}/admin/competitions/index.html"
@@ -92,9 +110,54 @@ pub(crate) async fn create_competition(
 }
 
 #[axum::debug_handler(state = app_state::State)]
-pub(crate) async fn delete_competition(state: AppState, id: Path<Id>) -> Result<Redirect> {
+pub(crate) async fn confirm_delete_competition(
+    state: AppState,
+    id: Path<Id>,
+) -> Result<Html<String>> {
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "competition",
+            id: id.0,
+            target_url: format!("competitions/{}/delete", id.0),
+            cancel_url: "competitions/index.html".into(),
+            redirect_to: None,
+        },
+    )
+}
+
+/// Move a competition to the trash
+///
+/// The competition is only marked as deleted, so it can be restored
+/// together with all its races, starts and participants
+#[axum::debug_handler(state = app_state::State)]
+pub(crate) async fn delete_competition(
+    state: AppState,
+    auth_session: AuthSession,
+    id: Path<Id>,
+) -> Result<Redirect> {
     let base_url = state.base_url();
-    let count: usize = todo!("Delete competition here");
+    let competition_id = id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let count: usize = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Competition,
+                competition_id,
+                |conn| {
+                    diesel::update(
+                        competitions::table
+                            .find(competition_id)
+                            .filter(competitions::deleted_at.is_null()),
+                    )
+                    .set(competitions::deleted_at.eq(crate::database::now()))
+                    .execute(conn)
+                },
+            )
+        })
+        .await?;
     if count != 1 {
         Err(Error::NotFound(format!(
             "Competition with {} not found",
@@ -123,11 +186,24 @@ pub(crate) async fn render_edit_competition(state: AppState, id: Path<Id>) -> Re
 #[axum::debug_handler(state = app_state::State)]
 pub(crate) async fn update_competition(
     state: AppState,
+    auth_session: AuthSession,
     id: Path<Id>,
     data: Form<NewCompetition>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let count: usize = todo!("Update competition");
+    let competition_id = id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let count: usize = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Competition,
+                competition_id,
+                |conn| todo!("Update competition"),
+            )
+        })
+        .await?;
     if count != 1 {
         Err(Error::NotFound(format!(
             "Competition with {} not found",
@@ -0,0 +1,148 @@
+//! Protection against cross-site request forgery for the admin pages
+//!
+//! Each session gets a random token, which is rendered as hidden field into
+//! every admin form. Any request that is not a GET request needs to send
+//! this token back, either as form field or as `X-CSRF-Token` header.
+//! File uploads send it as field of their `multipart/form-data` body.
+use super::user::api_token::ApiUser;
+use crate::errors::{Error, Result};
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequest, Multipart, Request};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum_login::tower_sessions::Session;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+/// Name of the hidden form field containing the token
+const FORM_FIELD: &str = "csrf_token";
+/// Key used to store the token in the session
+const SESSION_KEY: &str = "csrf_token";
+/// Header that can be used instead of the form field
+static HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+/// Maximal accepted size of a form body, matches the default limit of axum
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+const TOKEN_LENGTH: usize = 32;
+
+/// The CSRF token of the current session
+///
+/// This is inserted into the request extensions by `csrf_protection`
+/// so that it can be rendered into the forms of the admin pages
+#[derive(Clone, Debug)]
+pub(crate) struct CsrfToken(pub(crate) String);
+
+/// Middleware that validates the CSRF token of any state changing request
+///
+/// The token field is removed from the form body before the request is passed
+/// on, so handlers do not need to care about it. Multipart bodies are passed
+/// on unchanged, their handlers have to skip the token field
+///
+/// Requests authenticated with an API token are passed on unchanged, they
+/// do not rely on the session cookie and therefore cannot be forged by
+/// another site
+pub(crate) async fn csrf_protection(
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    if request.extensions().get::<ApiUser>().is_some() {
+        return Ok(next.run(request).await);
+    }
+    let token = match session.get::<String>(SESSION_KEY).await? {
+        Some(token) => token,
+        None => {
+            let token = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(TOKEN_LENGTH)
+                .map(char::from)
+                .collect::<String>();
+            session.insert(SESSION_KEY, &token).await?;
+            token
+        }
+    };
+    let mut request = if request.method().is_safe() {
+        request
+    } else {
+        verify_token(request, &token).await?
+    };
+    request.extensions_mut().insert(CsrfToken(token));
+    Ok(next.run(request).await)
+}
+
+async fn verify_token(request: Request, expected: &str) -> Result<Request> {
+    if let Some(token) = request.headers().get(&HEADER) {
+        if token_matches(token.as_bytes(), expected) {
+            return Ok(request);
+        }
+        tracing::warn!(uri = %request.uri(), "Rejected request with invalid CSRF token");
+        return Err(Error::InvalidCsrfToken);
+    }
+    let (parts, body) = request.into_parts();
+    let body = axum::body::to_bytes(body, MAX_BODY_SIZE)
+        .await
+        .map_err(|e| Error::InvalidInput(e.to_string()))?;
+    if let Some(content_type) = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .filter(|v| v.as_bytes().starts_with(b"multipart/form-data"))
+    {
+        let token = multipart_token(content_type.clone(), body.clone()).await;
+        return match token {
+            Some(token) if token_matches(token.as_bytes(), expected) => {
+                Ok(Request::from_parts(parts, Body::from(body)))
+            }
+            _ => {
+                tracing::warn!(uri = %parts.uri, "Rejected request with invalid CSRF token");
+                Err(Error::InvalidCsrfToken)
+            }
+        };
+    }
+    let prefix = format!("{FORM_FIELD}=");
+    let mut token = None;
+    let mut remaining = Vec::with_capacity(body.len());
+    for pair in body.split(|b| *b == b'&') {
+        if let Some(value) = pair.strip_prefix(prefix.as_bytes()) {
+            token = Some(value);
+        } else if !pair.is_empty() {
+            if !remaining.is_empty() {
+                remaining.push(b'&');
+            }
+            remaining.extend_from_slice(pair);
+        }
+    }
+    match token {
+        Some(token) if token_matches(token, expected) => {
+            Ok(Request::from_parts(parts, Body::from(remaining)))
+        }
+        _ => {
+            tracing::warn!(uri = %parts.uri, "Rejected request with invalid CSRF token");
+            Err(Error::InvalidCsrfToken)
+        }
+    }
+}
+
+/// The value of the token field of a multipart body
+async fn multipart_token(content_type: HeaderValue, body: Bytes) -> Option<String> {
+    let mut request = Request::new(Body::from(body));
+    request.headers_mut().insert(CONTENT_TYPE, content_type);
+    let mut multipart = Multipart::from_request(request, &()).await.ok()?;
+    while let Some(field) = multipart.next_field().await.ok()? {
+        if field.name() == Some(FORM_FIELD) {
+            return field.text().await.ok();
+        }
+    }
+    None
+}
+
+/// Compare both tokens in constant time
+pub(crate) fn token_matches(token: &[u8], expected: &str) -> bool {
+    let expected = expected.as_bytes();
+    token.len() == expected.len()
+        && token
+            .iter()
+            .zip(expected)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
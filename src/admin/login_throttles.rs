@@ -0,0 +1,107 @@
+//! Admin page setup for throttled logins
+//!
+//! Lists the failed login attempts per user name and IP address and allows
+//! to clear them, e.g. to unlock a user that mistyped their password too often
+use super::user::auth_session::AuthSession;
+use super::user::login_throttle::{LoginThrottle, ThrottleKind};
+use crate::app_state::{self, AppState};
+use crate::database::schema::login_throttles;
+use crate::database::Id;
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::Router;
+use diesel::prelude::*;
+use serde::Serialize;
+use time::PrimitiveDateTime;
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/login_throttles.html",
+            axum::routing::get(list_login_throttles),
+        )
+        .route(
+            "/login_throttles/:throttle_id/clear",
+            axum::routing::post(clear_login_throttle),
+        )
+}
+
+#[derive(Serialize)]
+struct LoginThrottleEntry {
+    id: Id,
+    kind: ThrottleKind,
+    key: String,
+    failed_attempts: i32,
+    last_failed_at: PrimitiveDateTime,
+    /// set if login attempts are currently rejected
+    blocked_until: Option<PrimitiveDateTime>,
+    locked: bool,
+}
+
+#[derive(Serialize)]
+struct LoginThrottlesData {
+    throttles: Vec<LoginThrottleEntry>,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_login_throttles(state: AppState) -> Result<Html<String>> {
+    let settings = state.login_throttle();
+    let now = crate::database::now();
+    let throttles = state
+        .with_connection(|conn| {
+            login_throttles::table
+                .order_by(login_throttles::last_failed_at.desc())
+                .select(LoginThrottle::as_select())
+                .load(conn)
+        })
+        .await?;
+    let throttles = throttles
+        .into_iter()
+        .map(|throttle| {
+            let blocked_until = throttle.blocked_until(&settings, now);
+            LoginThrottleEntry {
+                id: throttle.id,
+                kind: throttle.kind,
+                locked: blocked_until.is_some() && throttle.locked_until.is_some(),
+                key: throttle.key,
+                failed_attempts: throttle.failed_attempts,
+                last_failed_at: throttle.last_failed_at,
+                blocked_until,
+            }
+        })
+        .collect();
+    state.render_template(
+        "admin_login_throttles.html",
+        LoginThrottlesData { throttles },
+    )
+}
+
+/// Remove the failed attempt counter, which also lifts any lockout
+#[axum::debug_handler(state = app_state::State)]
+async fn clear_login_throttle(
+    state: AppState,
+    auth_session: AuthSession,
+    throttle_id: Path<Id>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let throttle_id = throttle_id.0;
+    let cleared = state
+        .with_connection(move |conn| {
+            diesel::delete(login_throttles::table.find(throttle_id))
+                .returning((login_throttles::kind, login_throttles::key))
+                .get_result::<(ThrottleKind, String)>(conn)
+                .optional()
+        })
+        .await?;
+    let Some((kind, key)) = cleared else {
+        return Err(Error::NotFound(format!(
+            "Login throttle with id {throttle_id} not found"
+        )));
+    };
+    tracing::info!(?kind, key, ?user_id, "Cleared login throttle");
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/login_throttles.html"
+    )))
+}
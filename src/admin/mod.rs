@@ -1,32 +1,120 @@
 //! This module contains all admin page subroutes
 //! Additionally it contains the code for the user authentification for
 //! access to the admin pages
-use crate::app_state::{self};
+use crate::app_state::{self, AppState};
+use crate::database::Id;
+use crate::errors::{Error, Result};
+use axum::response::Html;
 use axum::Router;
 use axum_login::login_required;
-use user::auth_session::LoginBackend;
+use serde::{Deserialize, Serialize};
+use user::auth_session::{AuthSession, LoginBackend};
 
+mod api_tokens;
+mod audit;
+#[cfg(feature = "sqlite")]
+mod backup;
+mod branding;
 mod categories;
+mod certificates;
 mod competitions;
+pub(crate) mod csrf;
+mod login_throttles;
 mod participants;
+pub(crate) mod payments;
+pub(crate) mod persons;
 mod races;
+mod results;
+mod series;
+mod sessions;
 mod special_categories;
 mod starts;
+mod trash;
+mod two_factor;
 /// User authentication for the admin pages
 pub mod user;
 
 pub fn routes() -> Router<app_state::State> {
-    Router::new()
+    let router = Router::new()
         .nest("/competitions", competitions::routes())
         .merge(participants::routes())
         .merge(races::routes())
         .merge(starts::routes())
         .merge(categories::routes())
         .merge(special_categories::routes())
+        .merge(results::routes())
+        .merge(certificates::routes())
+        .merge(branding::routes())
+        .merge(series::routes())
+        .merge(persons::routes())
+        .merge(payments::routes())
+        .merge(audit::routes())
+        .merge(trash::routes())
+        .merge(login_throttles::routes())
+        .merge(sessions::routes())
+        .merge(two_factor::routes())
+        .merge(api_tokens::routes());
+    // backups are taken with the SQLite serialization API
+    #[cfg(feature = "sqlite")]
+    let router = router.merge(backup::routes());
+    router
+        .route(
+            "/password.html",
+            axum::routing::get(user::render_change_password),
+        )
+        .route("/password", axum::routing::post(user::change_password))
         .route_layer(login_required!(
             LoginBackend,
             login_url = "/admin/login.html"
         ))
         .route("/login.html", axum::routing::get(user::login_form))
         .route("/login", axum::routing::post(user::handle_login))
+        .route(
+            "/login/totp.html",
+            axum::routing::get(user::login_totp_form),
+        )
+        .route("/login/totp", axum::routing::post(user::handle_login_totp))
+        .layer(axum::middleware::from_fn(csrf::csrf_protection))
+        .layer(axum::middleware::from_fn(
+            user::api_token::bearer_authentication,
+        ))
+}
+
+/// Id of the logged in user, for pages that only deal with the own account
+fn current_user_id(auth_session: &AuthSession) -> Result<Id> {
+    auth_session
+        .user
+        .as_ref()
+        .map(|u| u.id)
+        .ok_or_else(|| Error::NotFound(String::from("No user logged in")))
+}
+
+/// Page of the admin area to return to, relative to `/admin/`
+#[derive(Deserialize)]
+struct RedirectInfo {
+    redirect_to: String,
+}
+
+/// Data used to render the page asking to confirm a deletion
+///
+/// See `templates/admin_confirm_delete.html` for the relevant template
+#[derive(Serialize)]
+struct ConfirmDeleteData {
+    /// translation key of the kind of entry that is deleted
+    entity: &'static str,
+    id: Id,
+    /// url the confirmation form is posted to, relative to `/admin/`
+    target_url: String,
+    /// url to go back to if the deletion is cancelled, relative to `/admin/`
+    cancel_url: String,
+    /// passed on to the deletion handler if set
+    redirect_to: Option<String>,
+}
+
+/// Render a confirmation page for deleting an entry
+///
+/// Deletions are only executed by a POST request to `target_url`,
+/// so they cannot be triggered by following a link
+fn render_delete_confirmation(state: &AppState, data: ConfirmDeleteData) -> Result<Html<String>> {
+    state.render_template("admin_confirm_delete.html", data)
 }
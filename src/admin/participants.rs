@@ -1,9 +1,12 @@
 //! Admin page setup for participants
+use super::audit;
+use super::user::auth_session::AuthSession;
+use super::RedirectInfo;
 use crate::app_state::{self, AppState};
 use crate::database::schema::{
     categories, participants, participants_in_special_category, races, special_categories, starts,
 };
-use crate::database::Id;
+use crate::database::{DbBackend, Id};
 use crate::errors::{Error, Result};
 use crate::registration::{ParticipantForForm, ParticipantWithSpecialCategories, RegistrationForm};
 use axum::extract::{Path, Query};
@@ -12,16 +15,19 @@ use axum::{Form, Router};
 use diesel::expression::{is_aggregate, MixedAggregates, ValidGrouping};
 use diesel::query_builder::QueryId;
 use diesel::sql_types::Bool;
-use diesel::sqlite::Sqlite;
 use diesel::QueryDsl;
 use diesel::{dsl, prelude::*};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 
 pub fn routes() -> Router<app_state::State> {
     let participants_routes = Router::new()
         .route(
             "/:participant_id/delete.html",
-            axum::routing::get(delete_participant),
+            axum::routing::get(confirm_delete_participant),
+        )
+        .route(
+            "/:participant_id/delete",
+            axum::routing::post(delete_participant),
         )
         .route(
             "/:participant_id/edit.html",
@@ -179,7 +185,7 @@ where
                 participants::table,
                 dsl::InnerJoin<categories::table, dsl::InnerJoin<starts::table, races::table>>,
             >,
-            Sqlite,
+            DbBackend,
             SqlType = Bool,
         > + ValidGrouping<()>
         + QueryId
@@ -187,7 +193,8 @@ where
         + 'static,
     F::IsAggregate: MixedAggregates<is_aggregate::No, Output = is_aggregate::No>,
 {
-    let participants: Vec<Participant> = todo!("Load participants");
+    let participants: Vec<Participant> =
+        todo!("Load participants (skip participants in the trash)");
     state.render_template(
         "admin_participant_list.html",
         ParticipantListData {
@@ -199,19 +206,49 @@ where
     )
 }
 
-#[derive(Deserialize)]
-struct RedirectInfo {
-    redirect_to: String,
+#[axum::debug_handler(state = app_state::State)]
+async fn confirm_delete_participant(
+    state: AppState,
+    participant_id: Path<Id>,
+    query: Query<RedirectInfo>,
+) -> Result<Html<String>> {
+    let redirect_to = query.0.redirect_to;
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "participant",
+            id: participant_id.0,
+            target_url: format!("participants/{}/delete", participant_id.0),
+            cancel_url: redirect_to.clone(),
+            redirect_to: Some(redirect_to),
+        },
+    )
 }
 
+/// Move a participant to the trash
 #[axum::debug_handler(state = app_state::State)]
 async fn delete_participant(
     state: AppState,
+    auth_session: AuthSession,
     participant_id: Path<Id>,
-    query: Query<RedirectInfo>,
+    data: Form<RedirectInfo>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let count: usize = todo!("Delete participant");
+    let user_id = auth_session.user.map(|u| u.id);
+    let id = participant_id.0;
+    let count: usize = state
+        .with_connection(move |conn| {
+            audit::audited(conn, user_id, audit::Entity::Participant, id, |conn| {
+                diesel::update(
+                    participants::table
+                        .find(id)
+                        .filter(participants::deleted_at.is_null()),
+                )
+                .set(participants::deleted_at.eq(crate::database::now()))
+                .execute(conn)
+            })
+        })
+        .await?;
     if count != 1 {
         Err(Error::NotFound(format!(
             "Participant with id {} not found",
@@ -220,7 +257,7 @@ async fn delete_participant(
     } else {
         Ok(Redirect::to(&format!(
             "{base_url}/admin/{}",
-            query.redirect_to
+            data.redirect_to
         )))
     }
 }
@@ -251,14 +288,23 @@ async fn render_edit_participant(
 #[axum::debug_handler(state = app_state::State)]
 async fn update_participant(
     state: AppState,
+    auth_session: AuthSession,
     participant_id: Path<Id>,
     query: Query<RedirectInfo>,
     data: Form<RegistrationForm>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let (_participant, competition_id) = load_participant_by_id(&state, participant_id.0).await?;
-    data.0
-        .into_database(&state, competition_id, Some(participant_id.0))
+    let user_id = auth_session.user.map(|u| u.id);
+    let id = participant_id.0;
+    let (_participant, competition_id) = load_participant_by_id(&state, id).await?;
+    let form = data.0;
+    form.is_valid()?;
+    state
+        .with_connection(move |conn| {
+            audit::audited(conn, user_id, audit::Entity::Participant, id, |conn| {
+                form.into_database(conn, competition_id, Some(id))
+            })
+        })
         .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/{}",
@@ -331,12 +377,24 @@ async fn render_add_participant(
 #[axum::debug_handler(state = app_state::State)]
 async fn add_participant(
     state: AppState,
+    auth_session: AuthSession,
     competition_id: Path<Id>,
     redirect: Query<RedirectInfo>,
     form: Form<RegistrationForm>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    form.0.into_database(&state, competition_id.0, None).await?;
+    let user_id = auth_session.user.map(|u| u.id);
+    let form = form.0;
+    form.is_valid()?;
+    let competition_id = competition_id.0;
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let participant_id = form.into_database(conn, competition_id, None)?;
+                audit::record_created(conn, user_id, audit::Entity::Participant, participant_id)
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/{}",
         redirect.redirect_to
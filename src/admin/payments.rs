@@ -0,0 +1,801 @@
+//! Admin page setup for entry fees and payments
+//!
+//! Entry fees are configured per race. A race can have a late entry fee
+//! that applies for all registrations from a certain date on. Special
+//! categories can add a surcharge on top of that. All amounts are stored
+//! in cents.
+//!
+//! Each participant gets a reference code derived from its id, which should
+//! be used for bank transfers. Bank statements can be imported as CSV to
+//! mark all participants with a matching reference code as paid.
+//! Participants that do not need to pay, e.g. helpers or participants of a
+//! free race, can be selected on the same page to waive their entry fee.
+use super::audit;
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::{
+    categories, competitions, participants, participants_in_special_category, races,
+    special_categories, starts,
+};
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::{Form, Router};
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use time::macros::format_description;
+use time::{Date, PrimitiveDateTime};
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/competitions/:competition_id/fees.html",
+            axum::routing::get(render_edit_fees),
+        )
+        .route(
+            "/competitions/:competition_id/fees",
+            axum::routing::post(update_fees),
+        )
+        .route(
+            "/competitions/:competition_id/unpaid.html",
+            axum::routing::get(list_unpaid_participants),
+        )
+        .route(
+            "/competitions/:competition_id/mark_paid",
+            axum::routing::post(mark_paid),
+        )
+        .route(
+            "/competitions/:competition_id/waive_fees",
+            axum::routing::post(waive_fees),
+        )
+        .route(
+            "/competitions/:competition_id/import_payments",
+            axum::routing::post(import_payments),
+        )
+}
+
+/// Payment status of a participant
+#[derive(AsExpression, FromSqlRow, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PaymentStatus {
+    /// The entry fee was not paid yet
+    Open,
+    /// The entry fee was paid
+    Paid,
+    /// The participant does not need to pay an entry fee
+    Waived,
+}
+
+impl PaymentStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Paid => "paid",
+            Self::Waived => "waived",
+        }
+    }
+}
+
+impl ToSql<Text, DbBackend> for PaymentStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DbBackend>) -> serialize::Result {
+        <str as ToSql<Text, DbBackend>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, DbBackend> for PaymentStatus {
+    fn from_sql(bytes: <DbBackend as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, DbBackend>>::from_sql(bytes)?.as_str() {
+            "open" => Ok(Self::Open),
+            "paid" => Ok(Self::Paid),
+            "waived" => Ok(Self::Waived),
+            other => Err(format!("Unknown payment status `{other}`").into()),
+        }
+    }
+}
+
+/// Largest participant id that fits into the six digits of a reference code
+const MAX_REFERENCE_ID: Id = 999_999;
+
+/// Payment reference code for a participant, e.g. `RT00004269` for id 42
+///
+/// The last two digits are a checksum, so that typos in a bank transfer
+/// do not match a different participant. Participants with ids above
+/// `MAX_REFERENCE_ID` have no reference code and have to be marked as paid
+/// manually
+pub(crate) fn reference_code(participant_id: Id) -> Option<String> {
+    (0..=MAX_REFERENCE_ID)
+        .contains(&participant_id)
+        .then(|| format!("RT{participant_id:06}{:02}", checksum(participant_id)))
+}
+
+fn checksum(participant_id: Id) -> i64 {
+    98 - (i64::from(participant_id) * 100).rem_euclid(97)
+}
+
+/// Find all valid reference codes in a line of text and return the
+/// corresponding participant ids
+///
+/// A reference code consists of exactly eight digits, longer numbers
+/// are not considered to be a reference code
+fn find_reference_codes(line: &str) -> Vec<Id> {
+    let line = line.to_ascii_uppercase();
+    line.match_indices("RT")
+        .filter_map(|(idx, _)| {
+            let digits = line[idx + 2..]
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>();
+            if digits.len() != 8 {
+                return None;
+            }
+            let (id, check) = digits.split_at(6);
+            let id = id.parse::<Id>().ok()?;
+            (check.parse::<i64>().ok()? == checksum(id)).then_some(id)
+        })
+        .collect()
+}
+
+/// Largest fee or surcharge that can be entered, in cents
+const MAX_AMOUNT: i32 = 1_000_000;
+
+/// Format an amount given in cents, e.g. `12.50`
+pub(crate) fn format_amount(cents: impl Into<i64>) -> String {
+    let cents = cents.into();
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    format!("{sign}{}.{:02}", cents / 100, cents % 100)
+}
+
+/// Parse a non negative amount like `12`, `12.5` or `12,50` into cents
+///
+/// Amounts above `MAX_AMOUNT` are rejected, so that sums of many fees
+/// and surcharges cannot overflow
+pub(crate) fn parse_amount(input: &str) -> Option<i32> {
+    let input = input.trim().replace(',', ".");
+    let (whole, fraction) = input.split_once('.').unwrap_or((&input, ""));
+    if whole.is_empty() || fraction.len() > 2 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let whole = whole.parse::<u32>().ok()?;
+    let fraction = format!("{fraction:0<2}").parse::<u32>().ok()?;
+    i32::try_from(u64::from(whole) * 100 + u64::from(fraction))
+        .ok()
+        .filter(|cents| *cents <= MAX_AMOUNT)
+}
+
+/// Fee configuration of a race
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = races)]
+#[diesel(check_for_backend(DbBackend))]
+struct RaceFees {
+    id: Id,
+    name: String,
+    entry_fee: i32,
+    late_entry_fee: Option<i32>,
+    late_entry_from: Option<Date>,
+}
+
+impl RaceFees {
+    /// Entry fee for a registration at the given point in time
+    ///
+    /// Participants without registration time are treated as early
+    /// registrations
+    fn fee_at(&self, registered_at: Option<PrimitiveDateTime>) -> i32 {
+        match (self.late_entry_fee, self.late_entry_from, registered_at) {
+            (Some(fee), Some(from), Some(at)) if at.date() >= from => fee,
+            _ => self.entry_fee,
+        }
+    }
+}
+
+fn load_race_fees(conn: &mut DbConnection, competition_id: Id) -> QueryResult<Vec<RaceFees>> {
+    races::table
+        .filter(races::competition_id.eq(competition_id))
+        .order_by(races::id)
+        .select(RaceFees::as_select())
+        .load(conn)
+}
+
+/// Compute the amount due for all participants of a competition
+///
+/// The amount consists of the entry fee of the race and the surcharges
+/// of all special categories the participant registered for. Participants
+/// in the trash or of a competition in the trash are skipped
+fn load_amounts_due(conn: &mut DbConnection, competition_id: Id) -> QueryResult<HashMap<Id, i32>> {
+    let race_fees = load_race_fees(conn, competition_id)?
+        .into_iter()
+        .map(|r| (r.id, r))
+        .collect::<HashMap<_, _>>();
+    let participants = participants::table
+        .inner_join(
+            categories::table
+                .inner_join(starts::table.inner_join(races::table.inner_join(competitions::table))),
+        )
+        .filter(races::competition_id.eq(competition_id))
+        .filter(competitions::deleted_at.is_null())
+        .filter(participants::deleted_at.is_null())
+        .select((participants::id, participants::registered_at, races::id))
+        .load::<(Id, Option<PrimitiveDateTime>, Id)>(conn)?;
+    let surcharges = participants_in_special_category::table
+        .inner_join(special_categories::table.inner_join(races::table))
+        .filter(races::competition_id.eq(competition_id))
+        .select((
+            participants_in_special_category::participant_id,
+            special_categories::surcharge,
+        ))
+        .load::<(Id, i32)>(conn)?;
+
+    let mut amounts = participants
+        .into_iter()
+        .map(|(id, registered_at, race_id)| {
+            let fee = race_fees
+                .get(&race_id)
+                .map(|r| r.fee_at(registered_at))
+                .unwrap_or_default();
+            (id, fee)
+        })
+        .collect::<HashMap<_, _>>();
+    for (participant_id, surcharge) in surcharges {
+        if let Some(amount) = amounts.get_mut(&participant_id) {
+            *amount = amount.saturating_add(surcharge);
+        }
+    }
+    Ok(amounts)
+}
+
+/// What a participant needs to know to pay the entry fee
+#[derive(Serialize, Debug)]
+pub(crate) struct PaymentInstructions {
+    /// missing for ids that do not fit into a reference code
+    reference_code: Option<String>,
+    amount_due: String,
+}
+
+/// Payment instructions for a participant, shown after the registration
+///
+/// Returns `None` if the participant does not need to pay anything
+pub(crate) fn payment_instructions(
+    conn: &mut DbConnection,
+    competition_id: Id,
+    participant_id: Id,
+) -> QueryResult<Option<PaymentInstructions>> {
+    let amount = load_amounts_due(conn, competition_id)?
+        .get(&participant_id)
+        .copied()
+        .unwrap_or_default();
+    Ok((amount > 0).then(|| PaymentInstructions {
+        reference_code: reference_code(participant_id),
+        amount_due: format_amount(amount),
+    }))
+}
+
+/// Mark the given participants of a competition as paid or waive their fee
+///
+/// Paid participants store the amount due at this point in time. Participants
+/// that are not open anymore or belong to a different competition are ignored.
+/// Returns the ids of all updated participants
+fn settle_payments(
+    conn: &mut DbConnection,
+    user_id: Option<Id>,
+    competition_id: Id,
+    participant_ids: &[Id],
+    status: PaymentStatus,
+) -> QueryResult<Vec<Id>> {
+    conn.transaction(|conn| {
+        let amounts = load_amounts_due(conn, competition_id)?;
+        let now = crate::database::now();
+        let mut updated = Vec::new();
+        for participant_id in participant_ids {
+            let Some(amount) = amounts.get(participant_id) else {
+                continue;
+            };
+            let (paid_amount, paid_at) = match status {
+                PaymentStatus::Paid => (Some(*amount), Some(now)),
+                PaymentStatus::Open | PaymentStatus::Waived => (None, None),
+            };
+            let count = audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Participant,
+                *participant_id,
+                |conn| {
+                    diesel::update(
+                        participants::table
+                            .find(participant_id)
+                            .filter(participants::payment_status.eq(PaymentStatus::Open)),
+                    )
+                    .set((
+                        participants::payment_status.eq(status),
+                        participants::paid_amount.eq(paid_amount),
+                        participants::paid_at.eq(paid_at),
+                    ))
+                    .execute(conn)
+                },
+            )?;
+            if count == 1 {
+                updated.push(*participant_id);
+            }
+        }
+        Ok(updated)
+    })
+}
+
+#[derive(Serialize)]
+struct RaceFeeData {
+    id: Id,
+    name: String,
+    entry_fee: String,
+    late_entry_fee: String,
+    late_entry_from: String,
+}
+
+#[derive(Serialize)]
+struct SurchargeData {
+    id: Id,
+    name: String,
+    race: String,
+    surcharge: String,
+}
+
+#[derive(Serialize)]
+struct EditFeesData {
+    competition_id: Id,
+    competition_name: String,
+    races: Vec<RaceFeeData>,
+    special_categories: Vec<SurchargeData>,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_edit_fees(state: AppState, competition_id: Path<Id>) -> Result<Html<String>> {
+    let competition_id = competition_id.0;
+    let (competition_name, races, special_categories) = state
+        .with_connection(move |conn| {
+            let competition_name = competitions::table
+                .find(competition_id)
+                .filter(competitions::deleted_at.is_null())
+                .select(competitions::name)
+                .first::<String>(conn)
+                .optional()?;
+            let races = load_race_fees(conn, competition_id)?;
+            let special_categories = special_categories::table
+                .inner_join(races::table)
+                .filter(races::competition_id.eq(competition_id))
+                .order_by((races::id, special_categories::id))
+                .select((
+                    special_categories::id,
+                    special_categories::name,
+                    races::name,
+                    special_categories::surcharge,
+                ))
+                .load::<(Id, String, String, i32)>(conn)?;
+            QueryResult::Ok((competition_name, races, special_categories))
+        })
+        .await?;
+    let competition_name = competition_name.ok_or_else(|| {
+        Error::NotFound(format!("Competition with id {competition_id} not found"))
+    })?;
+
+    let races = races
+        .into_iter()
+        .map(|r| RaceFeeData {
+            id: r.id,
+            name: r.name,
+            entry_fee: format_amount(r.entry_fee),
+            late_entry_fee: r.late_entry_fee.map(format_amount).unwrap_or_default(),
+            late_entry_from: r.late_entry_from.map(|d| d.to_string()).unwrap_or_default(),
+        })
+        .collect();
+    let special_categories = special_categories
+        .into_iter()
+        .map(|(id, name, race, surcharge)| SurchargeData {
+            id,
+            name,
+            race,
+            surcharge: format_amount(surcharge),
+        })
+        .collect();
+
+    state.render_template(
+        "admin_fees.html",
+        EditFeesData {
+            competition_id,
+            competition_name,
+            races,
+            special_categories,
+        },
+    )
+}
+
+fn parse_required_amount(form: &HashMap<String, String>, key: &str) -> Result<i32> {
+    let value = form.get(key).map(String::as_str).unwrap_or_default();
+    parse_amount(value).ok_or_else(|| Error::InvalidInput(format!("Invalid amount: `{value}`")))
+}
+
+/// Validated fee settings of a single race
+struct RaceFeeInput {
+    race_id: Id,
+    entry_fee: i32,
+    late_entry_fee: Option<i32>,
+    late_entry_from: Option<Date>,
+}
+
+/// Parse the fee settings for a race from the fees form
+///
+/// Fields are named `entry_fee_{id}`, `late_entry_fee_{id}` and
+/// `late_entry_from_{id}`
+fn parse_race_fee_input(form: &HashMap<String, String>, race_id: Id) -> Result<RaceFeeInput> {
+    let entry_fee = parse_required_amount(form, &format!("entry_fee_{race_id}"))?;
+    let late_entry_fee = form
+        .get(&format!("late_entry_fee_{race_id}"))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            parse_amount(v).ok_or_else(|| Error::InvalidInput(format!("Invalid amount: `{v}`")))
+        })
+        .transpose()?;
+    let late_entry_from = form
+        .get(&format!("late_entry_from_{race_id}"))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            Date::parse(v, format_description!("[year]-[month]-[day]"))
+                .map_err(|e| Error::InvalidInput(format!("Invalid date `{v}`: {e}")))
+        })
+        .transpose()?;
+    if late_entry_fee.is_some() != late_entry_from.is_some() {
+        return Err(Error::InvalidInput(String::from(
+            "A late entry fee requires a date from which it applies and vice versa",
+        )));
+    }
+    Ok(RaceFeeInput {
+        race_id,
+        entry_fee,
+        late_entry_fee,
+        late_entry_from,
+    })
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn update_fees(
+    state: AppState,
+    auth_session: AuthSession,
+    competition_id: Path<Id>,
+    data: Form<HashMap<String, String>>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id = competition_id.0;
+    let (race_ids, special_category_ids) = state
+        .with_connection(move |conn| {
+            let race_ids = races::table
+                .filter(races::competition_id.eq(competition_id))
+                .select(races::id)
+                .load::<Id>(conn)?;
+            let special_category_ids = special_categories::table
+                .filter(special_categories::race_id.eq_any(&race_ids))
+                .select(special_categories::id)
+                .load::<Id>(conn)?;
+            QueryResult::Ok((race_ids, special_category_ids))
+        })
+        .await?;
+
+    // only races and special categories of this competition are updated
+    let races = race_ids
+        .into_iter()
+        .map(|race_id| parse_race_fee_input(&data, race_id))
+        .collect::<Result<Vec<_>>>()?;
+    let surcharges = special_category_ids
+        .into_iter()
+        .map(|id| {
+            Ok((
+                id,
+                parse_required_amount(&data, &format!("surcharge_{id}"))?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                for race in races {
+                    audit::audited(conn, user_id, audit::Entity::Race, race.race_id, |conn| {
+                        diesel::update(races::table.find(race.race_id))
+                            .set((
+                                races::entry_fee.eq(race.entry_fee),
+                                races::late_entry_fee.eq(race.late_entry_fee),
+                                races::late_entry_from.eq(race.late_entry_from),
+                            ))
+                            .execute(conn)
+                    })?;
+                }
+                for (id, surcharge) in surcharges {
+                    audit::audited(conn, user_id, audit::Entity::SpecialCategory, id, |conn| {
+                        diesel::update(special_categories::table.find(id))
+                            .set(special_categories::surcharge.eq(surcharge))
+                            .execute(conn)
+                    })?;
+                }
+                QueryResult::Ok(())
+            })
+        })
+        .await?;
+
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/competitions/{competition_id}/fees.html"
+    )))
+}
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+struct UnpaidParticipant {
+    id: Id,
+    first_name: String,
+    last_name: String,
+    club: Option<String>,
+    #[diesel(select_expression = races::name)]
+    race: String,
+}
+
+#[derive(Serialize)]
+struct UnpaidParticipantData {
+    id: Id,
+    first_name: String,
+    last_name: String,
+    club: Option<String>,
+    race: String,
+    reference_code: Option<String>,
+    amount_due: String,
+}
+
+#[derive(Serialize)]
+struct UnpaidListData {
+    competition_id: Id,
+    competition_name: String,
+    participants: Vec<UnpaidParticipantData>,
+    total_due: String,
+    total_received: String,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_unpaid_participants(
+    state: AppState,
+    competition_id: Path<Id>,
+) -> Result<Html<String>> {
+    let competition_id = competition_id.0;
+    let (competition_name, participants, amounts, received) = state
+        .with_connection(move |conn| {
+            let competition_name = competitions::table
+                .find(competition_id)
+                .filter(competitions::deleted_at.is_null())
+                .select(competitions::name)
+                .first::<String>(conn)
+                .optional()?;
+            let participants = participants::table
+                .inner_join(categories::table.inner_join(starts::table.inner_join(races::table)))
+                .filter(races::competition_id.eq(competition_id))
+                .filter(participants::payment_status.eq(PaymentStatus::Open))
+                .filter(participants::deleted_at.is_null())
+                .order_by((participants::last_name, participants::first_name))
+                .select(UnpaidParticipant::as_select())
+                .load(conn)?;
+            let amounts = load_amounts_due(conn, competition_id)?;
+            let received = participants::table
+                .inner_join(categories::table.inner_join(starts::table.inner_join(races::table)))
+                .filter(races::competition_id.eq(competition_id))
+                .filter(participants::payment_status.eq(PaymentStatus::Paid))
+                .filter(participants::deleted_at.is_null())
+                .select(participants::paid_amount)
+                .load::<Option<i32>>(conn)?;
+            QueryResult::Ok((competition_name, participants, amounts, received))
+        })
+        .await?;
+    let competition_name = competition_name.ok_or_else(|| {
+        Error::NotFound(format!("Competition with id {competition_id} not found"))
+    })?;
+
+    let mut total_due = 0_i64;
+    let participants = participants
+        .into_iter()
+        .map(|p| {
+            let amount = amounts.get(&p.id).copied().unwrap_or_default();
+            total_due += i64::from(amount);
+            UnpaidParticipantData {
+                reference_code: reference_code(p.id),
+                amount_due: format_amount(amount),
+                id: p.id,
+                first_name: p.first_name,
+                last_name: p.last_name,
+                club: p.club,
+                race: p.race,
+            }
+        })
+        .collect();
+    let total_received = received.into_iter().flatten().map(i64::from).sum::<i64>();
+
+    state.render_template(
+        "admin_unpaid_list.html",
+        UnpaidListData {
+            competition_id,
+            competition_name,
+            participants,
+            total_due: format_amount(total_due),
+            total_received: format_amount(total_received),
+        },
+    )
+}
+
+/// Ids of the participants selected on the unpaid participants page
+///
+/// The form contains a checkbox named `participant_{id}` for each participant
+fn selected_participants(data: &HashMap<String, String>) -> Result<Vec<Id>> {
+    data.keys()
+        .filter_map(|k| k.strip_prefix("participant_"))
+        .map(|id| {
+            id.parse::<Id>()
+                .map_err(|e| Error::InvalidInput(e.to_string()))
+        })
+        .collect()
+}
+
+/// Mark all selected participants as paid
+#[axum::debug_handler(state = app_state::State)]
+async fn mark_paid(
+    state: AppState,
+    auth_session: AuthSession,
+    competition_id: Path<Id>,
+    data: Form<HashMap<String, String>>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id = competition_id.0;
+    let participant_ids = selected_participants(&data)?;
+    let updated = state
+        .with_connection(move |conn| {
+            settle_payments(
+                conn,
+                user_id,
+                competition_id,
+                &participant_ids,
+                PaymentStatus::Paid,
+            )
+        })
+        .await?;
+    tracing::info!(
+        competition_id,
+        count = updated.len(),
+        "Marked participants as paid"
+    );
+
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/competitions/{competition_id}/unpaid.html"
+    )))
+}
+
+/// Waive the entry fee of all selected participants
+#[axum::debug_handler(state = app_state::State)]
+async fn waive_fees(
+    state: AppState,
+    auth_session: AuthSession,
+    competition_id: Path<Id>,
+    data: Form<HashMap<String, String>>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id = competition_id.0;
+    let participant_ids = selected_participants(&data)?;
+    let updated = state
+        .with_connection(move |conn| {
+            settle_payments(
+                conn,
+                user_id,
+                competition_id,
+                &participant_ids,
+                PaymentStatus::Waived,
+            )
+        })
+        .await?;
+    tracing::info!(competition_id, count = updated.len(), "Waived entry fees");
+
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/competitions/{competition_id}/unpaid.html"
+    )))
+}
+
+#[derive(Deserialize)]
+struct ImportPaymentsForm {
+    /// Content of the bank statement as CSV
+    statement: String,
+}
+
+#[derive(Serialize)]
+struct ImportedPayment {
+    id: Id,
+    first_name: String,
+    last_name: String,
+    reference_code: String,
+}
+
+#[derive(Serialize)]
+struct ImportPaymentsData {
+    competition_id: Id,
+    paid: Vec<ImportedPayment>,
+    unmatched_lines: Vec<String>,
+}
+
+/// Import a bank statement and mark all participants as paid whose
+/// reference code is contained in the statement
+///
+/// The statement format differs between banks, therefore each line is
+/// searched for reference codes instead of relying on specific columns
+#[axum::debug_handler(state = app_state::State)]
+async fn import_payments(
+    state: AppState,
+    auth_session: AuthSession,
+    competition_id: Path<Id>,
+    data: Form<ImportPaymentsForm>,
+) -> Result<Html<String>> {
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id = competition_id.0;
+    let mut participant_ids = Vec::new();
+    let mut unmatched_lines = Vec::new();
+    for line in data.statement.lines().filter(|l| !l.trim().is_empty()) {
+        let ids = find_reference_codes(line);
+        if ids.is_empty() {
+            unmatched_lines.push(line.to_owned());
+        }
+        participant_ids.extend(ids);
+    }
+
+    let paid = state
+        .with_connection(move |conn| {
+            let updated = settle_payments(
+                conn,
+                user_id,
+                competition_id,
+                &participant_ids,
+                PaymentStatus::Paid,
+            )?;
+            participants::table
+                .filter(participants::id.eq_any(updated))
+                .order_by((participants::last_name, participants::first_name))
+                .select((
+                    participants::id,
+                    participants::first_name,
+                    participants::last_name,
+                ))
+                .load::<(Id, String, String)>(conn)
+        })
+        .await?
+        .into_iter()
+        .map(|(id, first_name, last_name)| ImportedPayment {
+            id,
+            first_name,
+            last_name,
+            reference_code: reference_code(id)
+                .expect("Ids parsed from a reference code have a reference code"),
+        })
+        .collect::<Vec<_>>();
+    tracing::info!(
+        competition_id,
+        count = paid.len(),
+        "Imported payments from bank statement"
+    );
+
+    state.render_template(
+        "admin_payment_import.html",
+        ImportPaymentsData {
+            competition_id,
+            paid,
+            unmatched_lines,
+        },
+    )
+}
@@ -0,0 +1,370 @@
+//! Admin page setup for persons
+//!
+//! A person represents the identity of a runner across several competitions.
+//! Each participant entry is linked to a person. Returning runners are matched
+//! by their name and birth year.
+use super::audit;
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::{categories, competitions, participants, persons, races, starts};
+use crate::database::shared_models::ymd_date;
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::{Form, Router};
+use diesel::connection::DefaultLoadingMode;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    let persons_router = Router::new()
+        .route("/index.html", axum::routing::get(list_persons))
+        .route("/match", axum::routing::post(match_participants))
+        .route(
+            "/:person_id/history.html",
+            axum::routing::get(render_person_history),
+        )
+        .route("/:person_id/link", axum::routing::post(link_participant))
+        .route(
+            "/:person_id/unlink/:participant_id",
+            axum::routing::post(unlink_participant),
+        );
+    Router::new().nest("/persons", persons_router)
+}
+
+diesel::define_sql_function! {
+    /// SQL `lower()` function, SQLite only handles ASCII characters
+    fn lower(x: Text) -> Text;
+}
+
+diesel::define_sql_function! {
+    /// SQL `trim()` function
+    fn trim(x: Text) -> Text;
+}
+
+/// Find a person matching the given name and birth year or create a new one
+///
+/// Names are compared case insensitive and without surrounding whitespace
+pub(crate) fn find_or_create_person(
+    conn: &mut DbConnection,
+    first_name: &str,
+    last_name: &str,
+    birth_year: i32,
+    club: Option<&str>,
+) -> QueryResult<Id> {
+    let existing = persons::table
+        .filter(lower(trim(persons::first_name)).eq(lower(trim(first_name))))
+        .filter(lower(trim(persons::last_name)).eq(lower(trim(last_name))))
+        .filter(persons::birth_year.eq(birth_year))
+        .order_by(persons::id)
+        .select(persons::id)
+        .first::<Id>(conn)
+        .optional()?;
+    if let Some(id) = existing {
+        // remember the latest known club
+        if club.is_some() {
+            diesel::update(persons::table.find(id))
+                .set(persons::club.eq(club))
+                .execute(conn)?;
+        }
+        return Ok(id);
+    }
+    diesel::insert_into(persons::table)
+        .values((
+            persons::first_name.eq(first_name.trim()),
+            persons::last_name.eq(last_name.trim()),
+            persons::birth_year.eq(birth_year),
+            persons::club.eq(club),
+        ))
+        .returning(persons::id)
+        .get_result(conn)
+}
+
+/// Link a participant to a matching person, creating the person if required
+pub(crate) fn link_to_person(conn: &mut DbConnection, participant_id: Id) -> QueryResult<Id> {
+    let (first_name, last_name, birth_year, club) = participants::table
+        .find(participant_id)
+        .select((
+            participants::first_name,
+            participants::last_name,
+            participants::birth_year,
+            participants::club,
+        ))
+        .first::<(String, String, i32, Option<String>)>(conn)?;
+    let person_id =
+        find_or_create_person(conn, &first_name, &last_name, birth_year, club.as_deref())?;
+    diesel::update(participants::table.find(participant_id))
+        .set(participants::person_id.eq(person_id))
+        .execute(conn)?;
+    Ok(person_id)
+}
+
+/// Link all participants that are not linked to a person yet
+///
+/// Returns the number of newly linked participants
+pub(crate) fn link_unmatched_participants(conn: &mut DbConnection) -> QueryResult<usize> {
+    conn.transaction(|conn| {
+        let unmatched = participants::table
+            .filter(participants::person_id.is_null())
+            .order_by(participants::id)
+            .select(participants::id)
+            .load::<Id>(conn)?;
+        for participant_id in &unmatched {
+            link_to_person(conn, *participant_id)?;
+        }
+        Ok(unmatched.len())
+    })
+}
+
+/// Remove all persons that are not linked to any participant anymore
+fn delete_orphaned_persons(conn: &mut DbConnection) -> QueryResult<usize> {
+    diesel::delete(persons::table.filter(diesel::dsl::not(diesel::dsl::exists(
+        participants::table.filter(participants::person_id.eq(persons::id.nullable())),
+    ))))
+    .execute(conn)
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = persons)]
+#[diesel(check_for_backend(DbBackend))]
+struct Person {
+    id: Id,
+    first_name: String,
+    last_name: String,
+    birth_year: i32,
+    club: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PersonWithData {
+    #[serde(flatten)]
+    person: Person,
+    participation_count: i64,
+}
+
+#[derive(Serialize)]
+struct ListPersonsData {
+    persons: Vec<PersonWithData>,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_persons(state: AppState) -> Result<Html<String>> {
+    let persons = state
+        .with_connection(|conn| {
+            persons::table
+                .left_join(
+                    participants::table.on(participants::person_id
+                        .eq(persons::id.nullable())
+                        .and(participants::deleted_at.is_null())),
+                )
+                .group_by(persons::id)
+                .order_by((persons::last_name, persons::first_name, persons::birth_year))
+                .select((
+                    Person::as_select(),
+                    diesel::dsl::count(participants::id.nullable()),
+                ))
+                .load_iter::<(Person, i64), DefaultLoadingMode>(conn)?
+                .map(|r| {
+                    r.map(|(person, participation_count)| PersonWithData {
+                        person,
+                        participation_count,
+                    })
+                })
+                .collect::<QueryResult<Vec<_>>>()
+        })
+        .await?;
+    state.render_template("admin_person_list.html", ListPersonsData { persons })
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn match_participants(state: AppState) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let linked = state.with_connection(link_unmatched_participants).await?;
+    tracing::info!(linked, "Linked participants to persons");
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/persons/index.html"
+    )))
+}
+
+/// A single participation of a person in a competition
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+struct Participation {
+    #[diesel(column_name = id)]
+    participant_id: Id,
+    first_name: String,
+    last_name: String,
+    club: Option<String>,
+    #[diesel(select_expression = competitions::id)]
+    competition_id: Id,
+    #[diesel(select_expression = competitions::name)]
+    competition: String,
+    #[diesel(select_expression = competitions::date)]
+    #[serde(serialize_with = "ymd_date")]
+    date: time::Date,
+    #[diesel(select_expression = races::name)]
+    race: String,
+    #[diesel(select_expression = categories::label)]
+    category: String,
+}
+
+#[derive(Serialize)]
+struct PersonHistoryData {
+    person: Person,
+    participations: Vec<Participation>,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_person_history(state: AppState, person_id: Path<Id>) -> Result<Html<String>> {
+    let person_id = person_id.0;
+    let (person, participations) = state
+        .with_connection(move |conn| {
+            let person = persons::table
+                .find(person_id)
+                .select(Person::as_select())
+                .first(conn)
+                .optional()?;
+            let participations = participants::table
+                .inner_join(categories::table.inner_join(
+                    starts::table.inner_join(races::table.inner_join(competitions::table)),
+                ))
+                .filter(participants::person_id.eq(person_id))
+                .filter(participants::deleted_at.is_null())
+                .filter(competitions::deleted_at.is_null())
+                .order_by((competitions::date.desc(), participants::id))
+                .select(Participation::as_select())
+                .load(conn)?;
+            QueryResult::Ok((person, participations))
+        })
+        .await?;
+    let person =
+        person.ok_or_else(|| Error::NotFound(format!("Person with id {person_id} not found")))?;
+    state.render_template(
+        "admin_person_history.html",
+        PersonHistoryData {
+            person,
+            participations,
+        },
+    )
+}
+
+#[derive(Deserialize)]
+struct LinkParticipantForm {
+    participant_id: Id,
+}
+
+/// Link an existing participant entry to this person
+#[axum::debug_handler(state = app_state::State)]
+async fn link_participant(
+    state: AppState,
+    auth_session: AuthSession,
+    person_id: Path<Id>,
+    data: Form<LinkParticipantForm>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let person_id = person_id.0;
+    let participant_id = data.participant_id;
+    let count = state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                if !diesel::dsl::select(diesel::dsl::exists(persons::table.find(person_id)))
+                    .get_result::<bool>(conn)?
+                {
+                    return Ok(0);
+                }
+                let count = audit::audited(
+                    conn,
+                    user_id,
+                    audit::Entity::Participant,
+                    participant_id,
+                    |conn| {
+                        diesel::update(participants::table.find(participant_id))
+                            .set(participants::person_id.eq(person_id))
+                            .execute(conn)
+                    },
+                )?;
+                delete_orphaned_persons(conn)?;
+                Ok(count)
+            })
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Person with id {person_id} or participant with id {participant_id} not found"
+        )))
+    } else {
+        Ok(Redirect::to(&format!(
+            "{base_url}/admin/persons/{person_id}/history.html"
+        )))
+    }
+}
+
+/// Unlink a participant entry from this person
+///
+/// The participant entry is moved to a new person on its own, as it
+/// obviously belongs to a different runner with the same name
+#[axum::debug_handler(state = app_state::State)]
+async fn unlink_participant(
+    state: AppState,
+    auth_session: AuthSession,
+    ids: Path<(Id, Id)>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let (person_id, participant_id) = ids.0;
+    let count = state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let participant = participants::table
+                    .filter(participants::id.eq(participant_id))
+                    .filter(participants::person_id.eq(person_id))
+                    .select((
+                        participants::first_name,
+                        participants::last_name,
+                        participants::birth_year,
+                        participants::club,
+                    ))
+                    .first::<(String, String, i32, Option<String>)>(conn)
+                    .optional()?;
+                let Some((first_name, last_name, birth_year, club)) = participant else {
+                    return Ok(0);
+                };
+                let new_person_id = diesel::insert_into(persons::table)
+                    .values((
+                        persons::first_name.eq(first_name),
+                        persons::last_name.eq(last_name),
+                        persons::birth_year.eq(birth_year),
+                        persons::club.eq(club),
+                    ))
+                    .returning(persons::id)
+                    .get_result::<Id>(conn)?;
+                let count = audit::audited(
+                    conn,
+                    user_id,
+                    audit::Entity::Participant,
+                    participant_id,
+                    |conn| {
+                        diesel::update(participants::table.find(participant_id))
+                            .set(participants::person_id.eq(new_person_id))
+                            .execute(conn)
+                    },
+                )?;
+                delete_orphaned_persons(conn)?;
+                QueryResult::Ok(count)
+            })
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Participant with id {participant_id} is not linked to person {person_id}"
+        )))
+    } else {
+        Ok(Redirect::to(&format!(
+            "{base_url}/admin/persons/index.html"
+        )))
+    }
+}
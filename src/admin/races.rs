@@ -1,15 +1,22 @@
 //! Admin page setup for races
+use super::audit;
+use super::user::auth_session::AuthSession;
 use crate::app_state::{self, AppState};
 use crate::database::Id;
 use crate::errors::Result;
-use axum::extract::Path;
+use axum::extract::{Path, Query};
 use axum::response::{Html, Redirect};
 use axum::{Form, Router};
+use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
 pub fn routes() -> Router<app_state::State> {
     let races_router = Router::new()
-        .route("/:race_id/delete.html", axum::routing::get(delete_race))
+        .route(
+            "/:race_id/delete.html",
+            axum::routing::get(confirm_delete_race),
+        )
+        .route("/:race_id/delete", axum::routing::post(delete_race))
         .route("/:race_id/edit.html", axum::routing::get(render_edit_race))
         .route("/:race_id", axum::routing::post(update_race));
     Router::new()
@@ -73,9 +80,39 @@ async fn render_new_race(state: AppState, competition_id: Path<Id>) -> Result<Ht
 }
 
 #[axum::debug_handler(state = app_state::State)]
-async fn delete_race(state: AppState, race_id: Path<Id>) -> Result<Redirect> {
+async fn confirm_delete_race(
+    state: AppState,
+    race_id: Path<Id>,
+    query: Query<super::RedirectInfo>,
+) -> Result<Html<String>> {
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "race",
+            id: race_id.0,
+            target_url: format!("races/{}/delete", race_id.0),
+            cancel_url: query.0.redirect_to,
+            redirect_to: None,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn delete_race(
+    state: AppState,
+    auth_session: AuthSession,
+    race_id: Path<Id>,
+) -> Result<Redirect> {
     let base_url = state.base_url();
-    let competition_id: Id = todo!("Delete the race + get the competition id");
+    let race_id = race_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited_delete(conn, user_id, audit::Entity::Race, race_id, |conn| {
+                todo!("Delete the race + get the competition id")
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/competitions/{}/races.html",
         competition_id
@@ -117,11 +154,20 @@ struct RaceFormInput {
 #[axum::debug_handler(state = app_state::State)]
 async fn update_race(
     state: AppState,
+    auth_session: AuthSession,
     race_id: Path<Id>,
     data: Form<RaceFormInput>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let competition_id: Id = todo!("Update the race + get the competition id");
+    let race_id = race_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited(conn, user_id, audit::Entity::Race, race_id, |conn| {
+                todo!("Update the race + get the competition id")
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/competitions/{}/races.html",
         competition_id
@@ -131,11 +177,20 @@ async fn update_race(
 #[axum::debug_handler(state = app_state::State)]
 async fn new_race(
     state: AppState,
+    auth_session: AuthSession,
     competition_id: Path<Id>,
     data: Form<RaceFormInput>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    todo!("Create a new race");
+    let user_id = auth_session.user.map(|u| u.id);
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let race_id: Id = todo!("Create a new race");
+                audit::record_created(conn, user_id, audit::Entity::Race, race_id)
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/competitions/{}/races.html",
         competition_id.0
@@ -0,0 +1,212 @@
+//! Admin page setup for entering results
+use super::audit;
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::{categories, participants, results};
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::{Form, Router};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/categories/:category_id/results.html",
+            axum::routing::get(render_results_for_category),
+        )
+        .route(
+            "/categories/:category_id/results",
+            axum::routing::post(update_results_for_category),
+        )
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+pub(crate) struct ResultEntry {
+    pub(crate) id: Id,
+    pub(crate) first_name: String,
+    pub(crate) last_name: String,
+    pub(crate) club: Option<String>,
+    pub(crate) birth_year: i32,
+    #[diesel(select_expression = results::finish_time.nullable())]
+    pub(crate) finish_time: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct ResultEntryData {
+    #[serde(flatten)]
+    entry: ResultEntry,
+    formatted_time: String,
+}
+
+#[derive(Serialize)]
+struct ResultListData {
+    category_id: Id,
+    category_label: String,
+    start_id: Id,
+    participants: Vec<ResultEntryData>,
+}
+
+/// Load all participants of a category together with their result, if any
+///
+/// Participants with a result are ordered by finish time, followed by all
+/// participants without result
+pub(crate) fn load_results_for_category(
+    conn: &mut DbConnection,
+    category_id: Id,
+) -> QueryResult<Vec<ResultEntry>> {
+    participants::table
+        .left_join(results::table)
+        .filter(participants::category_id.eq(category_id))
+        .filter(participants::deleted_at.is_null())
+        .order_by((
+            results::finish_time.is_null(),
+            results::finish_time,
+            participants::last_name,
+            participants::first_name,
+        ))
+        .select(ResultEntry::as_select())
+        .load(conn)
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_results_for_category(
+    state: AppState,
+    category_id: Path<Id>,
+) -> Result<Html<String>> {
+    let category_id = category_id.0;
+    let (category, participants) = state
+        .with_connection(move |conn| {
+            let category = categories::table
+                .find(category_id)
+                .select((categories::label, categories::start_id))
+                .first::<(String, Id)>(conn)
+                .optional()?;
+            let participants = load_results_for_category(conn, category_id)?;
+            QueryResult::Ok((category, participants))
+        })
+        .await?;
+    let (category_label, start_id) = category
+        .ok_or_else(|| Error::NotFound(format!("Category with id {category_id} not found")))?;
+    let participants = participants
+        .into_iter()
+        .map(|entry| ResultEntryData {
+            formatted_time: entry.finish_time.map(format_time).unwrap_or_default(),
+            entry,
+        })
+        .collect();
+
+    state.render_template(
+        "admin_results.html",
+        ResultListData {
+            category_id,
+            category_label,
+            start_id,
+            participants,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn update_results_for_category(
+    state: AppState,
+    auth_session: AuthSession,
+    category_id: Path<Id>,
+    // maps participant ids to the entered finish time
+    data: Form<HashMap<Id, String>>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let category_id = category_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let times = data
+        .0
+        .into_iter()
+        .map(|(participant_id, time)| {
+            let time = time.trim();
+            if time.is_empty() {
+                Ok((participant_id, None))
+            } else {
+                parse_time(time)
+                    .map(|t| (participant_id, Some(t)))
+                    .ok_or_else(|| Error::InvalidInput(format!("Invalid finish time: {time}")))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let participants_in_category = participants::table
+                    .filter(participants::category_id.eq(category_id))
+                    .filter(participants::deleted_at.is_null())
+                    .select(participants::id)
+                    .load::<Id>(conn)?;
+                for (participant_id, time) in times {
+                    // ignore any participant that does not belong to this category
+                    if !participants_in_category.contains(&participant_id) {
+                        continue;
+                    }
+                    audit::audited(
+                        conn,
+                        user_id,
+                        audit::Entity::Result,
+                        participant_id,
+                        |conn| {
+                            if let Some(time) = time {
+                                diesel::insert_into(results::table)
+                                    .values((
+                                        results::participant_id.eq(participant_id),
+                                        results::finish_time.eq(time),
+                                    ))
+                                    .on_conflict(results::participant_id)
+                                    .do_update()
+                                    .set(results::finish_time.eq(time))
+                                    .execute(conn)
+                            } else {
+                                diesel::delete(results::table.find(participant_id)).execute(conn)
+                            }
+                        },
+                    )?;
+                }
+                QueryResult::Ok(())
+            })
+        })
+        .await?;
+
+    Ok(Redirect::to(&format!(
+        "{base_url}/admin/categories/{category_id}/results.html"
+    )))
+}
+
+/// Parse a finish time in the format `H:MM:SS` or `MM:SS` into seconds
+pub(crate) fn parse_time(input: &str) -> Option<i32> {
+    let parts = input
+        .split(':')
+        .map(|p| p.trim().parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [minutes, seconds] => (0, *minutes, *seconds),
+        [hours, minutes, seconds] if *minutes < 60 => (*hours, *minutes, *seconds),
+        _ => return None,
+    };
+    if seconds >= 60 {
+        return None;
+    }
+    let total = u64::from(hours) * 3600 + u64::from(minutes) * 60 + u64::from(seconds);
+    i32::try_from(total).ok()
+}
+
+/// Format a finish time given in seconds as `H:MM:SS`
+pub(crate) fn format_time(seconds: i32) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,360 @@
+//! Admin page setup for series of competitions (e.g. a cup)
+
+use super::audit;
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::{competitions, competitions_in_series, series};
+use crate::database::shared_models::{parse_points_scheme, Competition, Series};
+use crate::database::{DbConnection, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::{Form, Router};
+use diesel::connection::DefaultLoadingMode;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    let series_router = Router::new()
+        .route("/index.html", axum::routing::get(list_series))
+        .route("/create.html", axum::routing::get(render_create_series))
+        .route("/create", axum::routing::post(create_series))
+        .route(
+            "/:series_id/delete.html",
+            axum::routing::get(confirm_delete_series),
+        )
+        .route("/:series_id/delete", axum::routing::post(delete_series))
+        .route(
+            "/:series_id/edit.html",
+            axum::routing::get(render_edit_series),
+        )
+        .route("/:series_id", axum::routing::post(update_series));
+    Router::new().nest("/series", series_router)
+}
+
+#[derive(Serialize)]
+struct SeriesWithData {
+    #[serde(flatten)]
+    series: Series,
+    competition_count: i64,
+}
+
+#[derive(Serialize)]
+struct ListSeriesData {
+    series: Vec<SeriesWithData>,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_series(state: AppState) -> Result<Html<String>> {
+    let series = state
+        .with_connection(|conn| {
+            // competitions in the trash are not counted
+            series::table
+                .left_join(
+                    competitions_in_series::table.inner_join(
+                        competitions::table.on(competitions::id
+                            .eq(competitions_in_series::competition_id)
+                            .and(competitions::deleted_at.is_null())),
+                    ),
+                )
+                .group_by(series::id)
+                .select((
+                    Series::as_select(),
+                    diesel::dsl::count(competitions::id.nullable()),
+                ))
+                .order_by(series::id)
+                .load_iter::<(Series, i64), DefaultLoadingMode>(conn)?
+                .map(|r| {
+                    r.map(|(series, competition_count)| SeriesWithData {
+                        series,
+                        competition_count,
+                    })
+                })
+                .collect::<QueryResult<Vec<_>>>()
+        })
+        .await?;
+    state.render_template("admin_series_list.html", ListSeriesData { series })
+}
+
+#[derive(Serialize)]
+struct CompetitionSelection {
+    #[serde(flatten)]
+    competition: Competition,
+    selected: bool,
+}
+
+#[derive(Serialize)]
+struct EditSeriesData {
+    series: Option<Series>,
+    competitions: Vec<CompetitionSelection>,
+    target_url: String,
+    title: String,
+}
+
+/// Data returned from the series form
+#[derive(Deserialize)]
+struct SeriesForm {
+    name: String,
+    description: String,
+    points_per_rank: String,
+    best_n: String,
+    /// checkboxes for the competitions, named `competition_{id}`
+    #[serde(flatten)]
+    competitions: HashMap<String, String>,
+}
+
+/// Maximal points for a single rank, keeps the totals of the standings small
+const MAX_POINTS: i32 = 10_000;
+
+/// Validated series data ready to be stored in the database
+struct ValidatedSeries {
+    name: String,
+    description: String,
+    points_per_rank: String,
+    best_n: i32,
+    competitions: Vec<Id>,
+}
+
+impl SeriesForm {
+    fn validate(self) -> Result<ValidatedSeries> {
+        let points = parse_points_scheme(&self.points_per_rank).map_err(|e| {
+            Error::InvalidInput(format!(
+                "Invalid points scheme `{}`: {e}",
+                self.points_per_rank
+            ))
+        })?;
+        if points.is_empty() || points.iter().any(|p| !(0..=MAX_POINTS).contains(p)) {
+            return Err(Error::InvalidInput(format!(
+                "The points scheme must contain at least one entry and only points \
+                 between 0 and {MAX_POINTS}"
+            )));
+        }
+        let best_n = self
+            .best_n
+            .trim()
+            .parse::<i32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("Invalid number of results: {}", self.best_n))
+            })?;
+        let competitions = self
+            .competitions
+            .keys()
+            .filter_map(|k| k.strip_prefix("competition_"))
+            .map(|id| {
+                id.parse::<Id>()
+                    .map_err(|e| Error::InvalidInput(e.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ValidatedSeries {
+            name: self.name,
+            description: self.description,
+            points_per_rank: points
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+            best_n,
+            competitions,
+        })
+    }
+}
+
+/// Load the series (if any) and the list of all competitions for the edit form
+async fn render_series_form(
+    state: AppState,
+    series_id: Option<Id>,
+    target_url: String,
+    title: String,
+) -> Result<Html<String>> {
+    let (series, competitions, selected) = state
+        .with_connection(move |conn| {
+            let series = series_id
+                .map(|id| {
+                    series::table
+                        .find(id)
+                        .select(Series::as_select())
+                        .first(conn)
+                })
+                .transpose()?;
+            let competitions = competitions::table
+                .filter(competitions::deleted_at.is_null())
+                .order_by(competitions::date.desc())
+                .select(Competition::as_select())
+                .load(conn)?;
+            let selected = competitions_in_series::table
+                .filter(competitions_in_series::series_id.nullable().eq(series_id))
+                .select(competitions_in_series::competition_id)
+                .load::<Id>(conn)?;
+            QueryResult::Ok((series, competitions, selected))
+        })
+        .await?;
+    let competitions = competitions
+        .into_iter()
+        .map(|competition| CompetitionSelection {
+            selected: selected.contains(&competition.id),
+            competition,
+        })
+        .collect();
+    state.render_template(
+        "edit_series.html",
+        EditSeriesData {
+            series,
+            competitions,
+            target_url,
+            title,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_create_series(state: AppState) -> Result<Html<String>> {
+    let title = state.translation("new_series");
+    render_series_form(state, None, "series/create".into(), title).await
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_edit_series(state: AppState, series_id: Path<Id>) -> Result<Html<String>> {
+    let title = state.translation("edit_series");
+    render_series_form(
+        state,
+        Some(series_id.0),
+        format!("series/{}", series_id.0),
+        title,
+    )
+    .await
+}
+
+/// Replace the competitions assigned to a series
+fn set_competitions_for_series(
+    conn: &mut DbConnection,
+    series_id: Id,
+    competitions: &[Id],
+) -> QueryResult<()> {
+    diesel::delete(
+        competitions_in_series::table.filter(competitions_in_series::series_id.eq(series_id)),
+    )
+    .execute(conn)?;
+    diesel::insert_into(competitions_in_series::table)
+        .values(
+            competitions
+                .iter()
+                .map(|c| {
+                    (
+                        competitions_in_series::series_id.eq(series_id),
+                        competitions_in_series::competition_id.eq(*c),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)?;
+    Ok(())
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn create_series(
+    state: AppState,
+    auth_session: AuthSession,
+    data: Form<SeriesForm>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let data = data.0.validate()?;
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let series_id = diesel::insert_into(series::table)
+                    .values((
+                        series::name.eq(data.name),
+                        series::description.eq(data.description),
+                        series::points_per_rank.eq(data.points_per_rank),
+                        series::best_n.eq(data.best_n),
+                    ))
+                    .returning(series::id)
+                    .get_result::<Id>(conn)?;
+                set_competitions_for_series(conn, series_id, &data.competitions)?;
+                audit::record_created(conn, user_id, audit::Entity::Series, series_id)
+            })
+        })
+        .await?;
+    Ok(Redirect::to(&format!("{base_url}/admin/series/index.html")))
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn update_series(
+    state: AppState,
+    auth_session: AuthSession,
+    series_id: Path<Id>,
+    data: Form<SeriesForm>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let series_id = series_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let data = data.0.validate()?;
+    let count = state
+        .with_connection(move |conn| {
+            audit::audited(conn, user_id, audit::Entity::Series, series_id, |conn| {
+                let count = diesel::update(series::table.find(series_id))
+                    .set((
+                        series::name.eq(data.name),
+                        series::description.eq(data.description),
+                        series::points_per_rank.eq(data.points_per_rank),
+                        series::best_n.eq(data.best_n),
+                    ))
+                    .execute(conn)?;
+                if count == 1 {
+                    set_competitions_for_series(conn, series_id, &data.competitions)?;
+                }
+                QueryResult::Ok(count)
+            })
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Series with id {series_id} not found"
+        )))
+    } else {
+        Ok(Redirect::to(&format!("{base_url}/admin/series/index.html")))
+    }
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn confirm_delete_series(state: AppState, series_id: Path<Id>) -> Result<Html<String>> {
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "series",
+            id: series_id.0,
+            target_url: format!("series/{}/delete", series_id.0),
+            cancel_url: "series/index.html".into(),
+            redirect_to: None,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn delete_series(
+    state: AppState,
+    auth_session: AuthSession,
+    series_id: Path<Id>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let series_id = series_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let count = state
+        .with_connection(move |conn| {
+            audit::audited_delete(conn, user_id, audit::Entity::Series, series_id, |conn| {
+                diesel::delete(series::table.find(series_id)).execute(conn)
+            })
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Series with id {series_id} not found"
+        )))
+    } else {
+        Ok(Redirect::to(&format!("{base_url}/admin/series/index.html")))
+    }
+}
@@ -0,0 +1,153 @@
+//! Admin page setup for the sessions of the current user
+//!
+//! Users can see where they are logged in and revoke sessions they do not
+//! trust anymore, e.g. after logging in on a shared computer
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::session_records;
+use crate::database::DbBackend;
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::Router;
+use axum_login::tower_sessions::Session;
+use diesel::prelude::*;
+use serde::Serialize;
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    let sessions_router = Router::new()
+        .route("/:public_id/revoke", axum::routing::post(revoke_session))
+        .route("/revoke_others", axum::routing::post(revoke_other_sessions));
+    Router::new()
+        .route("/sessions.html", axum::routing::get(list_sessions))
+        .nest("/sessions", sessions_router)
+        .route("/logout", axum::routing::post(logout))
+}
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = session_records)]
+#[diesel(check_for_backend(DbBackend))]
+struct ActiveSession {
+    id: Vec<u8>,
+    public_id: String,
+    created_at: PrimitiveDateTime,
+    expiry_date: OffsetDateTime,
+}
+
+#[derive(Serialize)]
+struct SessionEntry {
+    public_id: String,
+    created_at: PrimitiveDateTime,
+    expiry_date: PrimitiveDateTime,
+    /// whether this is the session used for the current request
+    current: bool,
+}
+
+#[derive(Serialize)]
+struct SessionListData {
+    sessions: Vec<SessionEntry>,
+}
+
+/// The raw id of the current session, as stored in the database
+fn current_session_id(session: &Session) -> Option<Vec<u8>> {
+    session.id().map(|id| id.0.to_be_bytes().to_vec())
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn list_sessions(
+    state: AppState,
+    auth_session: AuthSession,
+    session: Session,
+) -> Result<Html<String>> {
+    let user_id = auth_session.user.map(|u| u.id);
+    let current = current_session_id(&session);
+    let now = OffsetDateTime::now_utc();
+    let sessions = state
+        .with_connection(move |conn| {
+            session_records::table
+                .filter(session_records::user_id.eq(user_id))
+                .filter(session_records::expiry_date.gt(now))
+                .order_by(session_records::created_at.desc())
+                .select(ActiveSession::as_select())
+                .load(conn)
+        })
+        .await?;
+    let sessions = sessions
+        .into_iter()
+        .map(|s| {
+            let expiry_date = s.expiry_date.to_offset(UtcOffset::UTC);
+            SessionEntry {
+                current: Some(&s.id) == current.as_ref(),
+                public_id: s.public_id,
+                created_at: s.created_at,
+                expiry_date: PrimitiveDateTime::new(expiry_date.date(), expiry_date.time()),
+            }
+        })
+        .collect();
+    state.render_template("admin_sessions.html", SessionListData { sessions })
+}
+
+/// Revoke a single session of the current user
+///
+/// The device using this session needs to login again
+#[axum::debug_handler(state = app_state::State)]
+async fn revoke_session(
+    state: AppState,
+    auth_session: AuthSession,
+    public_id: Path<String>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let public_id = public_id.0;
+    let count = state
+        .with_connection({
+            let public_id = public_id.clone();
+            move |conn| {
+                diesel::delete(
+                    session_records::table
+                        .filter(session_records::public_id.eq(public_id))
+                        .filter(session_records::user_id.eq(user_id)),
+                )
+                .execute(conn)
+            }
+        })
+        .await?;
+    if count != 1 {
+        return Err(Error::NotFound(format!("Session {public_id} not found")));
+    }
+    tracing::info!(?user_id, public_id, "Revoked session");
+    Ok(Redirect::to(&format!("{base_url}/admin/sessions.html")))
+}
+
+/// Revoke all sessions of the current user, except the current one
+#[axum::debug_handler(state = app_state::State)]
+async fn revoke_other_sessions(
+    state: AppState,
+    auth_session: AuthSession,
+    session: Session,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let current = current_session_id(&session).unwrap_or_default();
+    let count = state
+        .with_connection(move |conn| {
+            diesel::delete(
+                session_records::table
+                    .filter(session_records::user_id.eq(user_id))
+                    .filter(session_records::id.ne(current)),
+            )
+            .execute(conn)
+        })
+        .await?;
+    tracing::info!(?user_id, count, "Revoked other sessions");
+    Ok(Redirect::to(&format!("{base_url}/admin/sessions.html")))
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn logout(state: AppState, mut auth_session: AuthSession) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user = auth_session.logout().await?;
+    tracing::info!(user_id = ?user.map(|u| u.id), "Logged out");
+    Ok(Redirect::to(&format!("{base_url}/admin/login.html")))
+}
@@ -1,17 +1,24 @@
 //! Admin page setup for special_categories
+use super::audit;
+use super::user::auth_session::AuthSession;
 use crate::app_state::{self, AppState};
 use crate::database::Id;
 use crate::errors::Result;
-use axum::extract::Path;
+use axum::extract::{Path, Query};
 use axum::response::{Html, Redirect};
 use axum::{Form, Router};
+use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
 pub(crate) fn routes() -> Router<app_state::State> {
     let special_categories_router = Router::new()
         .route(
             "/:special_id/delete.html",
-            axum::routing::get(delete_special_category),
+            axum::routing::get(confirm_delete_special_category),
+        )
+        .route(
+            "/:special_id/delete",
+            axum::routing::post(delete_special_category),
         )
         .route(
             "/:special_id/edit.html",
@@ -63,9 +70,43 @@ async fn list_special_categories(state: AppState, race_id: Path<Id>) -> Result<H
 }
 
 #[axum::debug_handler(state = app_state::State)]
-async fn delete_special_category(state: AppState, special_id: Path<Id>) -> Result<Redirect> {
+async fn confirm_delete_special_category(
+    state: AppState,
+    special_id: Path<Id>,
+    query: Query<super::RedirectInfo>,
+) -> Result<Html<String>> {
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "special_category",
+            id: special_id.0,
+            target_url: format!("special_categories/{}/delete", special_id.0),
+            cancel_url: query.0.redirect_to,
+            redirect_to: None,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn delete_special_category(
+    state: AppState,
+    auth_session: AuthSession,
+    special_id: Path<Id>,
+) -> Result<Redirect> {
     let base_url = state.base_url();
-    let race_id: Id = todo!("Delete the special category and load the corresponding race id");
+    let special_id = special_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let race_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited_delete(
+                conn,
+                user_id,
+                audit::Entity::SpecialCategory,
+                special_id,
+                |conn| todo!("Delete the special category and load the corresponding race id"),
+            )
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/races/{}/special_categories.html",
         race_id
@@ -128,11 +169,24 @@ struct SpecialCategoryFormInputData {
 #[axum::debug_handler(state = app_state::State)]
 async fn update_special_category(
     state: AppState,
+    auth_session: AuthSession,
     special_id: Path<Id>,
     data: Form<SpecialCategoryFormInputData>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let race_id: Id = todo!("Update the special category and get the race id");
+    let special_id = special_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let race_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::SpecialCategory,
+                special_id,
+                |conn| todo!("Update the special category and get the race id"),
+            )
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/races/{}/special_categories.html",
         race_id
@@ -142,11 +196,20 @@ async fn update_special_category(
 #[axum::debug_handler(state = app_state::State)]
 async fn add_special_category(
     state: AppState,
+    auth_session: AuthSession,
     race_id: Path<Id>,
     data: Form<SpecialCategoryFormInputData>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    todo!("Insert a new special category");
+    let user_id = auth_session.user.map(|u| u.id);
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let special_id: Id = todo!("Insert a new special category");
+                audit::record_created(conn, user_id, audit::Entity::SpecialCategory, special_id)
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/races/{}/special_categories.html",
         race_id.0
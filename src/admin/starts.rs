@@ -1,21 +1,35 @@
 //! Admin page setup for starts
+use super::audit;
+use super::user::auth_session::AuthSession;
 use crate::app_state::{self, AppState};
-use crate::database::Id;
-use crate::errors::Result;
-use axum::extract::Path;
-use axum::response::{Html, Redirect};
+use crate::database::schema::{categories, competitions, participants, races, starts};
+use crate::database::{DbBackend, Id};
+use crate::errors::{Error, Result};
+use crate::pdf;
+use axum::extract::{Path, Query};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::{Html, IntoResponse, Redirect, Response};
 use axum::{Form, Router};
+use diesel::prelude::*;
 use serde::{Deserialize, Deserializer, Serialize};
 use time::macros::format_description;
 use time::PrimitiveDateTime;
 
 pub fn routes() -> Router<app_state::State> {
     let start_routes = Router::new()
-        .route("/:start_id/delete.html", axum::routing::get(delete_start))
+        .route(
+            "/:start_id/delete.html",
+            axum::routing::get(confirm_delete_start),
+        )
+        .route("/:start_id/delete", axum::routing::post(delete_start))
         .route(
             "/:start_id/edit.html",
             axum::routing::get(render_edit_start),
         )
+        .route(
+            "/:start_id/start_list.pdf",
+            axum::routing::get(render_start_list_pdf),
+        )
         .route("/:start_id", axum::routing::post(update_start));
     Router::new()
         .nest("/starts", start_routes)
@@ -110,11 +124,20 @@ where
 #[axum::debug_handler(state = app_state::State)]
 async fn create_start(
     state: AppState,
+    auth_session: AuthSession,
     race_id: Path<Id>,
     data: Form<StartInputData>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    todo!("Insert a new start");
+    let user_id = auth_session.user.map(|u| u.id);
+    state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let start_id: Id = todo!("Insert a new start");
+                audit::record_created(conn, user_id, audit::Entity::Start, start_id)
+            })
+        })
+        .await?;
 
     Ok(Redirect::to(&format!(
         "{base_url}/admin/races/{}/starts.html",
@@ -123,9 +146,39 @@ async fn create_start(
 }
 
 #[axum::debug_handler(state = app_state::State)]
-async fn delete_start(state: AppState, start_id: Path<Id>) -> Result<Redirect> {
+async fn confirm_delete_start(
+    state: AppState,
+    start_id: Path<Id>,
+    query: Query<super::RedirectInfo>,
+) -> Result<Html<String>> {
+    super::render_delete_confirmation(
+        &state,
+        super::ConfirmDeleteData {
+            entity: "start",
+            id: start_id.0,
+            target_url: format!("starts/{}/delete", start_id.0),
+            cancel_url: query.0.redirect_to,
+            redirect_to: None,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn delete_start(
+    state: AppState,
+    auth_session: AuthSession,
+    start_id: Path<Id>,
+) -> Result<Redirect> {
     let base_url = state.base_url();
-    let race_id: Id = todo!("Delete the given start and get the corresponding race id");
+    let start_id = start_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let race_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited_delete(conn, user_id, audit::Entity::Start, start_id, |conn| {
+                todo!("Delete the given start and get the corresponding race id")
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/races/{}/starts.html",
         race_id,
@@ -149,13 +202,108 @@ async fn render_edit_start(state: AppState, start_id: Path<Id>) -> Result<Html<S
 #[axum::debug_handler(state = app_state::State)]
 async fn update_start(
     state: AppState,
+    auth_session: AuthSession,
     start_id: Path<Id>,
     data: Form<StartInputData>,
 ) -> Result<Redirect> {
     let base_url = state.base_url();
-    let race_id: Id = todo!("Update the given start and return the id of the relevant race");
+    let start_id = start_id.0;
+    let user_id = auth_session.user.map(|u| u.id);
+    let race_id: Id = state
+        .with_connection(move |conn| {
+            audit::audited(conn, user_id, audit::Entity::Start, start_id, |conn| {
+                todo!("Update the given start and return the id of the relevant race")
+            })
+        })
+        .await?;
     Ok(Redirect::to(&format!(
         "{base_url}/admin/races/{}/starts.html",
         race_id
     )))
 }
+
+/// A single row of the printable start list
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+struct StartListEntry {
+    /// The participant id is used as bib number
+    id: Id,
+    last_name: String,
+    first_name: String,
+    club: Option<String>,
+    birth_year: i32,
+    #[diesel(select_expression = categories::label)]
+    category: String,
+}
+
+/// Render a printable list of all participants of a start sorted by bib number
+#[axum::debug_handler(state = app_state::State)]
+async fn render_start_list_pdf(state: AppState, start_id: Path<Id>) -> Result<Response> {
+    let start_id = start_id.0;
+    let (header, entries) = state
+        .with_connection(move |conn| {
+            let header = starts::table
+                .inner_join(races::table.inner_join(competitions::table))
+                .filter(starts::id.eq(start_id))
+                .filter(competitions::deleted_at.is_null())
+                .select((competitions::name, races::name, starts::name, starts::time))
+                .first::<(String, String, String, PrimitiveDateTime)>(conn)
+                .optional()?;
+            let entries = participants::table
+                .inner_join(categories::table)
+                .filter(categories::start_id.eq(start_id))
+                .filter(participants::deleted_at.is_null())
+                .order_by(participants::id)
+                .select(StartListEntry::as_select())
+                .load(conn)?;
+            QueryResult::Ok((header, entries))
+        })
+        .await?;
+    let (competition_name, race_name, start_name, start_time) =
+        header.ok_or_else(|| Error::NotFound(format!("Start with id {start_id} not found")))?;
+
+    let rows = entries
+        .into_iter()
+        .map(|e| {
+            vec![
+                e.id.to_string(),
+                format!("{}, {}", e.last_name, e.first_name),
+                e.club.unwrap_or_default(),
+                e.category,
+                e.birth_year.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let time_format = format_description!("[year]-[month]-[day] [hour]:[minute]");
+    let start_time = start_time
+        .format(&time_format)
+        .expect("Can format this timestamp");
+    let table = pdf::Table {
+        title: vec![
+            format!("{} - {competition_name}", state.translation("start_list")),
+            format!("{race_name} / {start_name}"),
+            format!("{}: {start_time}", state.translation("start_time")),
+        ],
+        columns: vec![
+            (state.translation("bib"), 1.0),
+            (state.translation("name"), 4.0),
+            (state.translation("club"), 4.0),
+            (state.translation("category"), 2.0),
+            (state.translation("birth_year"), 1.5),
+        ],
+        rows: &rows,
+    };
+
+    Ok((
+        [
+            (CONTENT_TYPE, String::from("application/pdf")),
+            (
+                CONTENT_DISPOSITION,
+                format!("inline; filename=\"start_list_{start_id}.pdf\""),
+            ),
+        ],
+        table.render(),
+    )
+        .into_response())
+}
@@ -0,0 +1,238 @@
+//! Admin page setup for the trash
+//!
+//! Deleting a competition or a participant only marks it as deleted, so it
+//! can be restored later on. Entries older than the configured retention
+//! period can be purged, which removes them permanently.
+use super::audit;
+use super::user::auth_session::AuthSession;
+use crate::app_state::{self, AppState};
+use crate::database::schema::{categories, competitions, participants, races, starts};
+use crate::database::shared_models::ymd_date;
+use crate::database::{DbBackend, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::{Html, Redirect};
+use axum::Router;
+use diesel::prelude::*;
+use serde::Serialize;
+use time::PrimitiveDateTime;
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    let trash_router = Router::new()
+        .route(
+            "/competitions/:competition_id/restore",
+            axum::routing::post(restore_competition),
+        )
+        .route(
+            "/participants/:participant_id/restore",
+            axum::routing::post(restore_participant),
+        )
+        .route("/purge", axum::routing::post(purge_trash));
+    Router::new()
+        .route("/trash.html", axum::routing::get(render_trash))
+        .nest("/trash", trash_router)
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = competitions)]
+#[diesel(check_for_backend(DbBackend))]
+struct DeletedCompetition {
+    id: Id,
+    name: String,
+    #[serde(serialize_with = "ymd_date")]
+    date: time::Date,
+    #[diesel(select_expression = competitions::deleted_at.assume_not_null())]
+    deleted_at: PrimitiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+struct DeletedParticipant {
+    id: Id,
+    first_name: String,
+    last_name: String,
+    #[diesel(select_expression = competitions::name)]
+    competition: String,
+    #[diesel(select_expression = participants::deleted_at.assume_not_null())]
+    deleted_at: PrimitiveDateTime,
+}
+
+#[derive(Serialize)]
+struct TrashEntry<T> {
+    #[serde(flatten)]
+    entry: T,
+    /// whether the retention period for this entry is over
+    purgeable: bool,
+}
+
+#[derive(Serialize)]
+struct TrashData {
+    competitions: Vec<TrashEntry<DeletedCompetition>>,
+    participants: Vec<TrashEntry<DeletedParticipant>>,
+    retention_days: i64,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_trash(state: AppState) -> Result<Html<String>> {
+    let purge_before = crate::database::now() - state.trash_retention();
+    let (competitions, participants) = state
+        .with_connection(|conn| {
+            let competitions = competitions::table
+                .filter(competitions::deleted_at.is_not_null())
+                .order_by(competitions::deleted_at.desc())
+                .select(DeletedCompetition::as_select())
+                .load(conn)?;
+            let participants = participants::table
+                .inner_join(categories::table.inner_join(
+                    starts::table.inner_join(races::table.inner_join(competitions::table)),
+                ))
+                .filter(participants::deleted_at.is_not_null())
+                .order_by(participants::deleted_at.desc())
+                .select(DeletedParticipant::as_select())
+                .load(conn)?;
+            QueryResult::Ok((competitions, participants))
+        })
+        .await?;
+
+    state.render_template(
+        "admin_trash.html",
+        TrashData {
+            competitions: competitions
+                .into_iter()
+                .map(|entry| TrashEntry {
+                    purgeable: entry.deleted_at < purge_before,
+                    entry,
+                })
+                .collect(),
+            participants: participants
+                .into_iter()
+                .map(|entry| TrashEntry {
+                    purgeable: entry.deleted_at < purge_before,
+                    entry,
+                })
+                .collect(),
+            retention_days: state.trash_retention().whole_days(),
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn restore_competition(
+    state: AppState,
+    auth_session: AuthSession,
+    competition_id: Path<Id>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let competition_id = competition_id.0;
+    let count = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Competition,
+                competition_id,
+                |conn| {
+                    diesel::update(
+                        competitions::table
+                            .find(competition_id)
+                            .filter(competitions::deleted_at.is_not_null()),
+                    )
+                    .set(competitions::deleted_at.eq(None::<PrimitiveDateTime>))
+                    .execute(conn)
+                },
+            )
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Competition with id {competition_id} not found in the trash"
+        )))
+    } else {
+        Ok(Redirect::to(&format!("{base_url}/admin/trash.html")))
+    }
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn restore_participant(
+    state: AppState,
+    auth_session: AuthSession,
+    participant_id: Path<Id>,
+) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let participant_id = participant_id.0;
+    let count = state
+        .with_connection(move |conn| {
+            audit::audited(
+                conn,
+                user_id,
+                audit::Entity::Participant,
+                participant_id,
+                |conn| {
+                    diesel::update(
+                        participants::table
+                            .find(participant_id)
+                            .filter(participants::deleted_at.is_not_null()),
+                    )
+                    .set(participants::deleted_at.eq(None::<PrimitiveDateTime>))
+                    .execute(conn)
+                },
+            )
+        })
+        .await?;
+    if count != 1 {
+        Err(Error::NotFound(format!(
+            "Participant with id {participant_id} not found in the trash"
+        )))
+    } else {
+        Ok(Redirect::to(&format!("{base_url}/admin/trash.html")))
+    }
+}
+
+/// Permanently remove all entries whose retention period is over
+///
+/// Purging a competition removes all its races, starts, categories
+/// and participants as well
+#[axum::debug_handler(state = app_state::State)]
+async fn purge_trash(state: AppState, auth_session: AuthSession) -> Result<Redirect> {
+    let base_url = state.base_url();
+    let user_id = auth_session.user.map(|u| u.id);
+    let purge_before = crate::database::now() - state.trash_retention();
+    let (competitions, participants) = state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let participant_ids = participants::table
+                    .filter(participants::deleted_at.lt(purge_before))
+                    .select(participants::id)
+                    .load::<Id>(conn)?;
+                for id in &participant_ids {
+                    audit::audited_delete(
+                        conn,
+                        user_id,
+                        audit::Entity::Participant,
+                        *id,
+                        |conn| diesel::delete(participants::table.find(id)).execute(conn),
+                    )?;
+                }
+                let competition_ids = competitions::table
+                    .filter(competitions::deleted_at.lt(purge_before))
+                    .select(competitions::id)
+                    .load::<Id>(conn)?;
+                for id in &competition_ids {
+                    audit::audited_delete(
+                        conn,
+                        user_id,
+                        audit::Entity::Competition,
+                        *id,
+                        |conn| diesel::delete(competitions::table.find(id)).execute(conn),
+                    )?;
+                }
+                QueryResult::Ok((competition_ids.len(), participant_ids.len()))
+            })
+        })
+        .await?;
+    tracing::info!(competitions, participants, "Purged trash");
+    Ok(Redirect::to(&format!("{base_url}/admin/trash.html")))
+}
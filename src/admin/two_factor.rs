@@ -0,0 +1,208 @@
+//! Admin page setup for two-factor authentication
+//!
+//! Users can enable time-based one-time passwords for their own account.
+//! The new secret is kept in the session until the user confirmed it with
+//! a code from their authenticator app, only then it is stored and the
+//! recovery codes are shown, once.
+use super::current_user_id;
+use super::user::auth_session::AuthSession;
+use super::user::totp;
+use crate::app_state::{self, AppState};
+use crate::database::schema::users;
+use crate::database::Id;
+use crate::errors::Result;
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::{Form, Router};
+use axum_login::tower_sessions::Session;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Session key of the secret that is currently set up
+const PENDING_SECRET_KEY: &str = "pending_totp_secret";
+
+pub(crate) fn routes() -> Router<app_state::State> {
+    let two_factor_router = Router::new()
+        .route("/setup", axum::routing::post(setup_two_factor))
+        .route("/enable", axum::routing::post(enable_two_factor))
+        .route("/disable", axum::routing::post(disable_two_factor));
+    Router::new()
+        .route("/two_factor.html", axum::routing::get(render_two_factor))
+        .nest("/two_factor", two_factor_router)
+}
+
+#[derive(Deserialize)]
+struct CodeForm {
+    code: String,
+}
+
+/// Data used to render the two-factor authentication overview
+///
+/// See `templates/admin_two_factor.html` for the relevant template
+#[derive(Serialize)]
+struct TwoFactorData {
+    enabled: bool,
+    remaining_recovery_codes: i64,
+    /// whether the last entered code was rejected
+    invalid_code: bool,
+}
+
+/// Data used to render the setup page
+///
+/// See `templates/admin_two_factor_setup.html` for the relevant template
+#[derive(Serialize)]
+struct SetupData {
+    /// QR code containing the provisioning uri as SVG image
+    qr_code: String,
+    /// secret for entering it manually in the authenticator app
+    secret: String,
+    /// whether the last entered code was rejected
+    invalid_code: bool,
+}
+
+/// Data used to render the recovery codes after enabling two-factor authentication
+///
+/// See `templates/admin_two_factor_recovery_codes.html` for the relevant template
+#[derive(Serialize)]
+struct RecoveryCodesData {
+    recovery_codes: Vec<String>,
+}
+
+async fn render_overview(
+    state: &AppState,
+    user_id: Id,
+    invalid_code: bool,
+) -> Result<Html<String>> {
+    let (enabled, remaining_recovery_codes) = state
+        .with_connection(move |conn| {
+            let enabled = totp::secret(conn, user_id)?.is_some();
+            let remaining = totp::remaining_recovery_codes(conn, user_id)?;
+            QueryResult::Ok((enabled, remaining))
+        })
+        .await?;
+    state.render_template(
+        "admin_two_factor.html",
+        TwoFactorData {
+            enabled,
+            remaining_recovery_codes,
+            invalid_code,
+        },
+    )
+}
+
+async fn render_setup(
+    state: &AppState,
+    user_id: Id,
+    secret: String,
+    invalid_code: bool,
+) -> Result<Html<String>> {
+    let username = state
+        .with_connection(move |conn| {
+            users::table
+                .find(user_id)
+                .select(users::name)
+                .first::<String>(conn)
+        })
+        .await?;
+    let qr_code = totp::qr_code_svg(&totp::provisioning_uri(&secret, &username))?;
+    state.render_template(
+        "admin_two_factor_setup.html",
+        SetupData {
+            qr_code,
+            secret,
+            invalid_code,
+        },
+    )
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_two_factor(state: AppState, auth_session: AuthSession) -> Result<Html<String>> {
+    let user_id = current_user_id(&auth_session)?;
+    render_overview(&state, user_id, false).await
+}
+
+/// Start the setup by generating a new secret
+#[axum::debug_handler(state = app_state::State)]
+async fn setup_two_factor(
+    state: AppState,
+    auth_session: AuthSession,
+    session: Session,
+) -> Result<Html<String>> {
+    let user_id = current_user_id(&auth_session)?;
+    let secret = totp::generate_secret();
+    session.insert(PENDING_SECRET_KEY, &secret).await?;
+    render_setup(&state, user_id, secret, false).await
+}
+
+/// Finish the setup once the user entered a valid code for the new secret
+#[axum::debug_handler(state = app_state::State)]
+async fn enable_two_factor(
+    state: AppState,
+    auth_session: AuthSession,
+    session: Session,
+    Form(form): Form<CodeForm>,
+) -> Result<Response> {
+    let user_id = current_user_id(&auth_session)?;
+    let Some(secret) = session.get::<String>(PENDING_SECRET_KEY).await? else {
+        let base_url = state.base_url();
+        return Ok(Redirect::to(&format!("{base_url}/admin/two_factor.html")).into_response());
+    };
+    let Some(step) = totp::verify_code(&secret, &form.code, state.totp_time(), None) else {
+        tracing::info!(
+            user_id,
+            "Rejected code while enabling two-factor authentication"
+        );
+        return Ok(render_setup(&state, user_id, secret, true)
+            .await?
+            .into_response());
+    };
+    let recovery_codes = totp::generate_recovery_codes();
+    state
+        .with_connection({
+            let recovery_codes = recovery_codes.clone();
+            move |conn| totp::enable(conn, user_id, &secret, step, &recovery_codes)
+        })
+        .await?;
+    session.remove::<String>(PENDING_SECRET_KEY).await?;
+    tracing::info!(user_id, "Enabled two-factor authentication");
+    Ok(state
+        .render_template(
+            "admin_two_factor_recovery_codes.html",
+            RecoveryCodesData { recovery_codes },
+        )?
+        .into_response())
+}
+
+/// Disable two-factor authentication, which requires a valid code
+#[axum::debug_handler(state = app_state::State)]
+async fn disable_two_factor(
+    state: AppState,
+    auth_session: AuthSession,
+    Form(form): Form<CodeForm>,
+) -> Result<Response> {
+    let user_id = current_user_id(&auth_session)?;
+    let unix_time = state.totp_time();
+    let now = crate::database::now();
+    let disabled = state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                if totp::verify(conn, user_id, &form.code, unix_time, now)?.is_none() {
+                    return QueryResult::Ok(false);
+                }
+                totp::disable(conn, user_id)?;
+                Ok(true)
+            })
+        })
+        .await?;
+    if !disabled {
+        tracing::info!(
+            user_id,
+            "Rejected code while disabling two-factor authentication"
+        );
+        return Ok(render_overview(&state, user_id, true)
+            .await?
+            .into_response());
+    }
+    tracing::info!(user_id, "Disabled two-factor authentication");
+    let base_url = state.base_url();
+    Ok(Redirect::to(&format!("{base_url}/admin/two_factor.html")).into_response())
+}
@@ -1,14 +1,29 @@
 //! Admin page setup for users
 use crate::app_state::{self, AppState};
-use crate::errors::Result;
-use axum::extract::State;
+use crate::database::schema::users;
+use crate::errors::{Error, Result};
+use auth_session::User;
+use axum::extract::{ConnectInfo, State};
+use axum::http::header::RETRY_AFTER;
 use axum::http::StatusCode;
-use axum::response::{Html, IntoResponse, Redirect};
+use axum::response::{Html, IntoResponse, Redirect, Response};
 use axum::Form;
-use serde::Deserialize;
+use axum_login::tower_sessions::Session;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 
+pub mod api_token;
 pub mod auth_session;
-pub mod sqlite_session_store;
+pub mod login_throttle;
+pub(crate) mod password;
+pub mod session_store;
+pub mod totp;
+
+/// Session key of a login that still waits for the second factor
+const PENDING_LOGIN_KEY: &str = "pending_login";
+/// How long the second factor can be entered after the password was accepted
+const PENDING_LOGIN_TIMEOUT: time::Duration = time::Duration::minutes(5);
 
 #[derive(Clone, Deserialize)]
 pub struct Credentials {
@@ -24,21 +39,360 @@ pub async fn login_form(state: AppState) -> Result<Html<String>> {
 
 /// Handler for handling the form data from the login page
 /// This is where the actual login happens
+///
+/// Failed attempts are throttled per user name and client address,
+/// see `login_throttle` for details
+///
+/// Users with two-factor authentication enabled are not logged in here,
+/// but redirected to a second step asking for a one-time password
 #[axum::debug_handler]
 pub async fn handle_login(
     state: State<app_state::State>,
     mut auth_session: self::auth_session::AuthSession,
+    session: Session,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
     Form(creds): Form<Credentials>,
 ) -> impl IntoResponse {
+    let ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let settings = state.login_throttle;
+    let now = crate::database::now();
+    if let Some(response) = reject_throttled(&state, &creds.name, ip).await {
+        return response;
+    }
+
     let user = match auth_session.authenticate(creds.clone()).await {
         Ok(Some(user)) => user,
-        Ok(None) => return StatusCode::UNAUTHORIZED.into_response(),
+        Ok(None) => {
+            let username = creds.name.clone();
+            let failed_attempts = match state
+                .with_connection(move |conn| {
+                    login_throttle::record_failure(conn, &settings, &username, ip, now)
+                })
+                .await
+            {
+                Ok(failed_attempts) => failed_attempts,
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+            tracing::warn!(
+                username = %creds.name,
+                ip = ?ip,
+                failed_attempts,
+                locked = failed_attempts >= settings.max_attempts,
+                "Failed login attempt"
+            );
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let user_id = user.id;
+    let totp_enabled = match state
+        .with_connection(move |conn| totp::secret(conn, user_id))
+        .await
+    {
+        Ok(secret) => secret.is_some(),
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
+    if totp_enabled {
+        // the throttle is only reset once the second factor is accepted as well
+        let pending = PendingLogin {
+            user_id,
+            username: creds.name,
+            started_at: now,
+        };
+        if session.insert(PENDING_LOGIN_KEY, pending).await.is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+        let base_url = &state.base_url;
+        return Redirect::to(&format!("{base_url}/admin/login/totp.html")).into_response();
+    }
+    finish_login(&state, &mut auth_session, &user, creds.name).await
+}
+
+/// Reject the login attempt if this user name or client address is throttled
+async fn reject_throttled(
+    state: &app_state::State,
+    username: &str,
+    ip: Option<IpAddr>,
+) -> Option<Response> {
+    let settings = state.login_throttle;
+    let now = crate::database::now();
+    let key = username.to_owned();
+    let blocked_until = match state
+        .with_connection(move |conn| login_throttle::blocked_until(conn, &settings, &key, ip, now))
+        .await
+    {
+        Ok(blocked_until) => blocked_until?,
+        Err(_) => return Some(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+    tracing::warn!(
+        username,
+        ip = ?ip,
+        %blocked_until,
+        "Rejected login attempt while throttled"
+    );
+    let retry_after = (blocked_until - now).whole_seconds().max(1);
+    Some(
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.to_string())],
+        )
+            .into_response(),
+    )
+}
+
+/// Reset the failed attempts of this user and log them in
+async fn finish_login(
+    state: &app_state::State,
+    auth_session: &mut self::auth_session::AuthSession,
+    user: &User,
+    username: String,
+) -> Response {
+    if state
+        .with_connection(move |conn| login_throttle::reset(conn, &username))
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
 
-    if auth_session.login(&user).await.is_err() {
+    if auth_session.login(user).await.is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
     let base_url = &state.base_url;
     Redirect::to(&format!("{base_url}/admin/competitions/index.html")).into_response()
 }
+
+/// A login whose password was accepted, but that still needs the second factor
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    user_id: crate::database::Id,
+    username: String,
+    started_at: time::PrimitiveDateTime,
+}
+
+/// Load the pending login from the session, if it did not time out yet
+async fn pending_login(session: &Session) -> Result<Option<PendingLogin>> {
+    let pending = session.get::<PendingLogin>(PENDING_LOGIN_KEY).await?;
+    Ok(pending.filter(|p| p.started_at + PENDING_LOGIN_TIMEOUT > crate::database::now()))
+}
+
+#[derive(Deserialize)]
+pub struct TotpForm {
+    code: String,
+}
+
+/// Handler for rendering the page asking for the second factor
+#[axum::debug_handler(state = app_state::State)]
+pub async fn login_totp_form(state: AppState, session: Session) -> Result<Response> {
+    if pending_login(&session).await?.is_none() {
+        let base_url = state.base_url();
+        return Ok(Redirect::to(&format!("{base_url}/admin/login.html")).into_response());
+    }
+    Ok(state
+        .render_template("login_totp.html", ())?
+        .into_response())
+}
+
+/// Handler for the second login step
+///
+/// Accepts either a code from the authenticator app or a recovery code.
+/// Failed attempts count towards the same throttling as wrong passwords
+#[axum::debug_handler]
+pub async fn handle_login_totp(
+    state: State<app_state::State>,
+    mut auth_session: self::auth_session::AuthSession,
+    session: Session,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<TotpForm>,
+) -> impl IntoResponse {
+    let ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+    let settings = state.login_throttle;
+    let now = crate::database::now();
+    let base_url = &state.base_url;
+    let pending = match pending_login(&session).await {
+        Ok(Some(pending)) => pending,
+        Ok(None) => {
+            return Redirect::to(&format!("{base_url}/admin/login.html")).into_response();
+        }
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if let Some(response) = reject_throttled(&state, &pending.username, ip).await {
+        return response;
+    }
+
+    let user_id = pending.user_id;
+    let unix_time = state.totp_time();
+    let second_factor = match state
+        .with_connection(move |conn| totp::verify(conn, user_id, &form.code, unix_time, now))
+        .await
+    {
+        Ok(second_factor) => second_factor,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let Some(second_factor) = second_factor else {
+        let username = pending.username.clone();
+        let failed_attempts = match state
+            .with_connection(move |conn| {
+                login_throttle::record_failure(conn, &settings, &username, ip, now)
+            })
+            .await
+        {
+            Ok(failed_attempts) => failed_attempts,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+        tracing::warn!(
+            username = %pending.username,
+            ip = ?ip,
+            failed_attempts,
+            locked = failed_attempts >= settings.max_attempts,
+            "Failed second factor"
+        );
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    tracing::info!(user_id, ?second_factor, "Accepted second factor");
+
+    let user = match state
+        .with_connection(move |conn| {
+            users::table
+                .find(user_id)
+                .select(User::as_select())
+                .first(conn)
+        })
+        .await
+    {
+        Ok(user) => user,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    if session
+        .remove::<PendingLogin>(PENDING_LOGIN_KEY)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    finish_login(&state, &mut auth_session, &user, pending.username).await
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordForm {
+    current_password: String,
+    new_password: String,
+    new_password_confirmation: String,
+}
+
+/// Data used to render the change password page
+///
+/// See `templates/change_password.html` for the relevant template
+#[derive(Serialize)]
+struct ChangePasswordData {
+    /// translation keys of the reasons why the password was not changed
+    errors: Vec<&'static str>,
+    changed: bool,
+    min_length: usize,
+}
+
+/// Handler for rendering the change password page
+#[axum::debug_handler(state = app_state::State)]
+pub async fn render_change_password(state: AppState) -> Result<Html<String>> {
+    state.render_template(
+        "change_password.html",
+        ChangePasswordData {
+            errors: Vec::new(),
+            changed: false,
+            min_length: password::MIN_PASSWORD_LENGTH,
+        },
+    )
+}
+
+/// Handler for changing the password of the logged in user
+///
+/// All other sessions of this user are logged out by this, as
+/// the session auth hash is derived from the password hash
+///
+/// A wrong current password counts towards the login throttling of the
+/// user name, otherwise a stolen session could be used to guess the password
+#[axum::debug_handler(state = app_state::State)]
+pub async fn change_password(
+    state: AppState,
+    throttle_state: State<app_state::State>,
+    mut auth_session: self::auth_session::AuthSession,
+    Form(form): Form<ChangePasswordForm>,
+) -> Result<Response> {
+    let user = auth_session
+        .user
+        .clone()
+        .ok_or_else(|| Error::NotFound(String::from("No user logged in")))?;
+    let user_id = user.id;
+    let username = state
+        .with_connection(move |conn| {
+            users::table
+                .find(user_id)
+                .select(users::name)
+                .first::<String>(conn)
+        })
+        .await?;
+
+    if let Some(response) = reject_throttled(&throttle_state, &username, None).await {
+        return Ok(response);
+    }
+
+    let mut errors = Vec::new();
+    if !password::verify_password(&form.current_password, &user.password)? {
+        let settings = throttle_state.login_throttle;
+        let now = crate::database::now();
+        let key = username.clone();
+        let failed_attempts = state
+            .with_connection(move |conn| {
+                login_throttle::record_failure(conn, &settings, &key, None, now)
+            })
+            .await?;
+        tracing::warn!(
+            user_id,
+            failed_attempts,
+            locked = failed_attempts >= settings.max_attempts,
+            "Wrong current password when changing the password"
+        );
+        errors.push("wrong_current_password");
+    }
+    if form.new_password != form.new_password_confirmation {
+        errors.push("password_confirmation_mismatch");
+    }
+    if form.new_password == form.current_password {
+        errors.push("password_unchanged");
+    }
+    errors.extend(
+        password::check_policy(&form.new_password, &username)
+            .into_iter()
+            .map(password::PolicyViolation::translation_key),
+    );
+    if errors.is_empty() {
+        let new_hash = password::hash_password(&form.new_password)?;
+        let user = state
+            .with_connection(move |conn| {
+                diesel::update(users::table.find(user_id))
+                    .set(users::password.eq(new_hash))
+                    .returning(User::as_returning())
+                    .get_result(conn)
+            })
+            .await?;
+        state
+            .with_connection(move |conn| login_throttle::reset(conn, &username))
+            .await?;
+        // keep the current session logged in with the new auth hash
+        auth_session.login(&user).await?;
+        tracing::info!(user_id, "Changed password");
+    } else {
+        tracing::info!(user_id, ?errors, "Rejected password change");
+    }
+    Ok(state
+        .render_template(
+            "change_password.html",
+            ChangePasswordData {
+                changed: errors.is_empty(),
+                errors,
+                min_length: password::MIN_PASSWORD_LENGTH,
+            },
+        )?
+        .into_response())
+}
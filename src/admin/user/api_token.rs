@@ -0,0 +1,191 @@
+//! API tokens for machine clients
+//!
+//! Scripts cannot use the login form, instead they send an API token as
+//! `Authorization: Bearer <token>` header. Tokens belong to a user and act
+//! on behalf of that user. Only a hash of each token is stored, the token
+//! itself is shown once after creating it.
+use super::auth_session::{AuthSession, User};
+use crate::database::schema::{api_tokens, users};
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::errors::{Error, Result};
+use axum::extract::{FromRequestParts, Request};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum_extra::headers::authorization::Bearer;
+use axum_extra::headers::Authorization;
+use axum_extra::TypedHeader;
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use time::PrimitiveDateTime;
+
+/// Prefix of all tokens, makes them easy to recognize e.g. in leaked logs
+const TOKEN_PREFIX: &str = "rt_";
+/// Number of random characters of a token
+const TOKEN_LENGTH: usize = 40;
+
+/// What a token is allowed to do
+#[derive(AsExpression, FromSqlRow, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ApiTokenScope {
+    /// only requests with safe methods like `GET`
+    Read,
+    /// all requests
+    Write,
+}
+
+impl ApiTokenScope {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+
+    /// Whether a request with this method is allowed
+    fn allows(self, method: &axum::http::Method) -> bool {
+        match self {
+            Self::Read => method.is_safe(),
+            Self::Write => true,
+        }
+    }
+}
+
+impl ToSql<Text, DbBackend> for ApiTokenScope {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DbBackend>) -> serialize::Result {
+        <str as ToSql<Text, DbBackend>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, DbBackend> for ApiTokenScope {
+    fn from_sql(bytes: <DbBackend as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, DbBackend>>::from_sql(bytes)?.as_str() {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            other => Err(format!("Unknown API token scope `{other}`").into()),
+        }
+    }
+}
+
+/// Generate a new random token
+pub(crate) fn generate_token() -> String {
+    let random = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect::<String>();
+    format!("{TOKEN_PREFIX}{random}")
+}
+
+/// Hash of a token as stored in the database
+///
+/// Tokens are random enough that a fast hash function is sufficient
+pub(crate) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Find the user owning this token, if the token exists and did not expire
+///
+/// This also records when the token was last used
+pub(crate) fn find_user(
+    conn: &mut DbConnection,
+    token: &str,
+    now: PrimitiveDateTime,
+) -> QueryResult<Option<(User, ApiTokenScope)>> {
+    let found = api_tokens::table
+        .inner_join(users::table)
+        .filter(api_tokens::token_hash.eq(hash_token(token)))
+        .filter(
+            api_tokens::expires_at
+                .is_null()
+                .or(api_tokens::expires_at.gt(now)),
+        )
+        .select((api_tokens::id, User::as_select(), api_tokens::scope))
+        .first::<(Id, User, ApiTokenScope)>(conn)
+        .optional()?;
+    let Some((token_id, user, scope)) = found else {
+        return Ok(None);
+    };
+    diesel::update(api_tokens::table.find(token_id))
+        .set(api_tokens::last_used_at.eq(now))
+        .execute(conn)?;
+    Ok(Some((user, scope)))
+}
+
+/// Extractor for the user owning the API token sent with the request
+///
+/// Rejects requests without a valid token and requests that are not
+/// allowed by the scope of the token
+#[derive(Clone)]
+pub struct ApiUser {
+    pub user: User,
+    pub(crate) scope: ApiTokenScope,
+}
+
+#[async_trait::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiUser {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        if let Some(api_user) = parts.extensions.get::<ApiUser>() {
+            return Ok(api_user.clone());
+        }
+        let TypedHeader(Authorization(bearer)) =
+            TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state)
+                .await
+                .map_err(|_| Error::InvalidApiToken)?;
+        // the backend is only available with the auth layer in place
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(|_| Error::InvalidApiToken)?;
+        let Some((user, scope)) = auth_session
+            .backend
+            .authenticate_api_token(bearer.token())
+            .await?
+        else {
+            tracing::warn!("Rejected invalid or expired API token");
+            return Err(Error::InvalidApiToken);
+        };
+        if !scope.allows(&parts.method) {
+            tracing::warn!(
+                user_id = user.id,
+                ?scope,
+                method = %parts.method,
+                "Rejected request outside of the API token scope"
+            );
+            return Err(Error::InsufficientScope);
+        }
+        Ok(Self { user, scope })
+    }
+}
+
+/// Middleware that logs in requests carrying an API token
+///
+/// Requests with an `Authorization` header are handled as if the owner of
+/// the token was logged in, so all handlers see the same user as with a
+/// session. Requests without that header are passed on unchanged.
+pub(crate) async fn bearer_authentication(request: Request, next: Next) -> Result<Response> {
+    if !request.headers().contains_key(AUTHORIZATION) {
+        return Ok(next.run(request).await);
+    }
+    let (mut parts, body) = request.into_parts();
+    let api_user = ApiUser::from_request_parts(&mut parts, &()).await?;
+    if let Some(auth_session) = parts.extensions.get_mut::<AuthSession>() {
+        auth_session.user = Some(api_user.user.clone());
+    }
+    parts.extensions.insert(api_user);
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
@@ -1,19 +1,17 @@
 //! Authentication setup for our application
+use super::api_token::{self, ApiTokenScope};
+use super::password;
 use super::Credentials;
 use crate::database::schema::users;
-use crate::database::Id;
+use crate::database::{DbBackend, DbPool, Id};
 use crate::errors::Result;
-use argon2::Argon2;
-use argon2::PasswordHash;
-use argon2::PasswordVerifier;
 use axum_login::AuthUser;
 use axum_login::AuthnBackend;
 use axum_login::UserId;
 use diesel::prelude::*;
-use diesel::sqlite::Sqlite;
 
 #[derive(Debug, Clone, Queryable, Selectable)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct User {
     pub(crate) id: Id,
     pub(crate) password: String,
@@ -33,13 +31,30 @@ impl AuthUser for User {
 
 #[derive(Clone)]
 pub struct LoginBackend {
-    pub(crate) pool: deadpool_diesel::sqlite::Pool,
+    pub(crate) pool: DbPool,
 }
 
 impl LoginBackend {
-    pub fn new(pool: deadpool_diesel::sqlite::Pool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
+
+    /// Find the user owning this API token
+    ///
+    /// Returns `None` for unknown or expired tokens
+    pub(crate) async fn authenticate_api_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<(User, ApiTokenScope)>> {
+        let token = token.to_owned();
+        let now = crate::database::now();
+        Ok(self
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| api_token::find_user(conn, &token, now))
+            .await??)
+    }
 }
 
 #[async_trait::async_trait]
@@ -64,18 +79,31 @@ impl AuthnBackend for LoginBackend {
                     .optional()
             })
             .await??;
-        if let Some(user) = user {
-            if Argon2::default()
-                .verify_password(password.as_bytes(), &PasswordHash::new(&user.password)?)
-                .is_ok()
-            {
-                Ok(Some(user))
-            } else {
-                Ok(None)
-            }
-        } else {
-            Ok(None)
+        let Some(user) = user else {
+            return Ok(None);
+        };
+        if !password::verify_password(&password, &user.password)? {
+            return Ok(None);
         }
+        if !password::needs_rehash(&user.password)? {
+            return Ok(Some(user));
+        }
+        // transparently upgrade hashes created with outdated parameters
+        let new_hash = password::hash_password(&password)?;
+        let user_id = user.id;
+        let user = self
+            .pool
+            .get()
+            .await?
+            .interact(move |conn| {
+                diesel::update(users::table.find(user_id))
+                    .set(users::password.eq(new_hash))
+                    .returning(User::as_returning())
+                    .get_result(conn)
+            })
+            .await??;
+        tracing::info!(user_id, "Rehashed password with current parameters");
+        Ok(Some(user))
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
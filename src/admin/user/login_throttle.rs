@@ -0,0 +1,240 @@
+//! Throttling of failed login attempts
+//!
+//! Failed attempts are counted per user name and per IP address. After a few
+//! failed attempts each further attempt needs to wait exponentially longer.
+//! Once `max_attempts` is reached, logins are locked for the configured
+//! lockout period. A counter is reset by a successful login for that user name
+//! or if there was no failed attempt for longer than the lockout period.
+//! Such expired counters are deleted whenever a failed attempt is recorded,
+//! so trying random user names does not grow the table without bound.
+use crate::database::schema::login_throttles;
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::service_config::Config;
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::prelude::*;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Text;
+use serde::Serialize;
+use std::net::IpAddr;
+use time::{Duration, PrimitiveDateTime};
+
+/// Number of failed attempts that are allowed without any delay
+const FREE_ATTEMPTS: i32 = 3;
+/// Delay after the first throttled attempt, doubled for each further failure
+const BASE_DELAY: Duration = Duration::seconds(1);
+
+/// Settings for the login throttling
+#[derive(Clone, Copy, Debug)]
+pub struct LoginThrottleSettings {
+    /// number of failed attempts after which logins are locked
+    pub max_attempts: i32,
+    /// how long logins stay locked
+    pub lockout: Duration,
+}
+
+impl LoginThrottleSettings {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_attempts: i32::try_from(config.login_lockout_attempts).unwrap_or(i32::MAX),
+            lockout: Duration::minutes(config.login_lockout_minutes.into()),
+        }
+    }
+}
+
+/// What a failed attempt counter is tracked for
+#[derive(AsExpression, FromSqlRow, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ThrottleKind {
+    Username,
+    Ip,
+}
+
+impl ThrottleKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Username => "username",
+            Self::Ip => "ip",
+        }
+    }
+}
+
+impl ToSql<Text, DbBackend> for ThrottleKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, DbBackend>) -> serialize::Result {
+        <str as ToSql<Text, DbBackend>>::to_sql(self.as_str(), out)
+    }
+}
+
+impl FromSql<Text, DbBackend> for ThrottleKind {
+    fn from_sql(bytes: <DbBackend as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, DbBackend>>::from_sql(bytes)?.as_str() {
+            "username" => Ok(Self::Username),
+            "ip" => Ok(Self::Ip),
+            other => Err(format!("Unknown login throttle kind `{other}`").into()),
+        }
+    }
+}
+
+/// Failed attempt counter for a single user name or IP address
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(check_for_backend(DbBackend))]
+pub(crate) struct LoginThrottle {
+    pub(crate) id: Id,
+    pub(crate) kind: ThrottleKind,
+    pub(crate) key: String,
+    pub(crate) failed_attempts: i32,
+    pub(crate) last_failed_at: PrimitiveDateTime,
+    pub(crate) locked_until: Option<PrimitiveDateTime>,
+}
+
+impl LoginThrottle {
+    /// Whether this counter is outdated and should be treated as reset
+    fn is_expired(&self, settings: &LoginThrottleSettings, now: PrimitiveDateTime) -> bool {
+        self.last_failed_at + settings.lockout < now
+            && self.locked_until.is_none_or(|until| until < now)
+    }
+
+    /// Point in time from which the next login attempt is accepted again
+    ///
+    /// Returns `None` if attempts are currently accepted
+    pub(crate) fn blocked_until(
+        &self,
+        settings: &LoginThrottleSettings,
+        now: PrimitiveDateTime,
+    ) -> Option<PrimitiveDateTime> {
+        if self.is_expired(settings, now) {
+            return None;
+        }
+        let until = match self.locked_until {
+            Some(until) => until,
+            None if self.failed_attempts < FREE_ATTEMPTS => return None,
+            None => {
+                let exponent = (self.failed_attempts - FREE_ATTEMPTS).min(20);
+                let delay = (BASE_DELAY * 2_i32.pow(exponent.unsigned_abs())).min(settings.lockout);
+                self.last_failed_at + delay
+            }
+        };
+        (until > now).then_some(until)
+    }
+}
+
+fn keys(username: &str, ip: Option<IpAddr>) -> Vec<(ThrottleKind, String)> {
+    let mut keys = vec![(ThrottleKind::Username, username.to_owned())];
+    if let Some(ip) = ip {
+        keys.push((ThrottleKind::Ip, ip.to_string()));
+    }
+    keys
+}
+
+fn load_throttle(
+    conn: &mut DbConnection,
+    kind: ThrottleKind,
+    key: &str,
+) -> QueryResult<Option<LoginThrottle>> {
+    login_throttles::table
+        .filter(login_throttles::kind.eq(kind))
+        .filter(login_throttles::key.eq(key))
+        .select(LoginThrottle::as_select())
+        .first(conn)
+        .optional()
+}
+
+/// Check whether login attempts for this user name or from this IP address are blocked
+///
+/// Returns the point in time from which the next attempt is accepted
+pub(crate) fn blocked_until(
+    conn: &mut DbConnection,
+    settings: &LoginThrottleSettings,
+    username: &str,
+    ip: Option<IpAddr>,
+    now: PrimitiveDateTime,
+) -> QueryResult<Option<PrimitiveDateTime>> {
+    let mut blocked_until = None;
+    for (kind, key) in keys(username, ip) {
+        if let Some(throttle) = load_throttle(conn, kind, &key)? {
+            blocked_until = blocked_until.max(throttle.blocked_until(settings, now));
+        }
+    }
+    Ok(blocked_until)
+}
+
+/// Delete all counters that are expired and therefore treated as reset
+///
+/// Returns the number of deleted counters
+pub(crate) fn delete_expired(
+    conn: &mut DbConnection,
+    settings: &LoginThrottleSettings,
+    now: PrimitiveDateTime,
+) -> QueryResult<usize> {
+    diesel::delete(
+        login_throttles::table
+            .filter(login_throttles::last_failed_at.lt(now - settings.lockout))
+            .filter(
+                login_throttles::locked_until
+                    .is_null()
+                    .or(login_throttles::locked_until.lt(now)),
+            ),
+    )
+    .execute(conn)
+}
+
+/// Count a failed login attempt for this user name and IP address
+///
+/// Returns the number of failed attempts for the user name
+pub(crate) fn record_failure(
+    conn: &mut DbConnection,
+    settings: &LoginThrottleSettings,
+    username: &str,
+    ip: Option<IpAddr>,
+    now: PrimitiveDateTime,
+) -> QueryResult<i32> {
+    conn.transaction(|conn| {
+        delete_expired(conn, settings, now)?;
+        let mut username_attempts = 0;
+        for (kind, key) in keys(username, ip) {
+            let failed_attempts = match load_throttle(conn, kind, &key)? {
+                Some(throttle) if !throttle.is_expired(settings, now) => {
+                    throttle.failed_attempts + 1
+                }
+                _ => 1,
+            };
+            let locked_until =
+                (failed_attempts >= settings.max_attempts).then(|| now + settings.lockout);
+            diesel::insert_into(login_throttles::table)
+                .values((
+                    login_throttles::kind.eq(kind),
+                    login_throttles::key.eq(&key),
+                    login_throttles::failed_attempts.eq(failed_attempts),
+                    login_throttles::last_failed_at.eq(now),
+                    login_throttles::locked_until.eq(locked_until),
+                ))
+                .on_conflict((login_throttles::kind, login_throttles::key))
+                .do_update()
+                .set((
+                    login_throttles::failed_attempts.eq(failed_attempts),
+                    login_throttles::last_failed_at.eq(now),
+                    login_throttles::locked_until.eq(locked_until),
+                ))
+                .execute(conn)?;
+            if kind == ThrottleKind::Username {
+                username_attempts = failed_attempts;
+            }
+        }
+        Ok(username_attempts)
+    })
+}
+
+/// Reset the counter for this user name after a successful login
+///
+/// The counter of the IP address is kept, otherwise an attacker with
+/// a valid account could reset it at will
+pub(crate) fn reset(conn: &mut DbConnection, username: &str) -> QueryResult<usize> {
+    diesel::delete(
+        login_throttles::table
+            .filter(login_throttles::kind.eq(ThrottleKind::Username))
+            .filter(login_throttles::key.eq(username)),
+    )
+    .execute(conn)
+}
@@ -0,0 +1,100 @@
+//! Password hashing and password policy
+//!
+//! All passwords are hashed with Argon2id using the parameters defined here.
+//! Hashes created with different parameters are still accepted, but should
+//! be replaced on the next successful login, see `needs_rehash`.
+use crate::errors::Result;
+use argon2::password_hash::SaltString;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+
+/// Memory cost in KiB, as recommended by OWASP for Argon2id
+const MEMORY_COST: u32 = 19 * 1024;
+/// Number of iterations
+const TIME_COST: u32 = 2;
+/// Degree of parallelism
+const PARALLELISM: u32 = 1;
+
+/// Minimal number of characters of a new password
+pub(crate) const MIN_PASSWORD_LENGTH: usize = 12;
+/// Maximal number of characters of a new password, hashing very long
+/// passwords is needlessly expensive
+pub(crate) const MAX_PASSWORD_LENGTH: usize = 128;
+
+fn params() -> Params {
+    Params::new(MEMORY_COST, TIME_COST, PARALLELISM, None).expect("Valid Argon2 parameters")
+}
+
+fn hasher() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params())
+}
+
+/// Hash a password with the current parameters
+pub(crate) fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    Ok(hasher()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// Check a password against a stored hash
+///
+/// The parameters stored in the hash are used for verification
+pub(crate) fn verify_password(password: &str, hash: &str) -> Result<bool> {
+    let hash = PasswordHash::new(hash)?;
+    Ok(hasher().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// Whether the hash was created with different parameters than the current ones
+pub(crate) fn needs_rehash(hash: &str) -> Result<bool> {
+    let hash = PasswordHash::new(hash)?;
+    let Ok(stored_params) = Params::try_from(&hash) else {
+        return Ok(true);
+    };
+    let current = params();
+    Ok(hash.algorithm != Algorithm::Argon2id.ident()
+        || hash.version != Some(Version::V0x13.into())
+        || stored_params.m_cost() != current.m_cost()
+        || stored_params.t_cost() != current.t_cost()
+        || stored_params.p_cost() != current.p_cost())
+}
+
+/// Reasons why a new password is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PolicyViolation {
+    TooShort,
+    TooLong,
+    ContainsUsername,
+}
+
+impl PolicyViolation {
+    /// Translation key of the message shown to the user
+    pub(crate) fn translation_key(self) -> &'static str {
+        match self {
+            Self::TooShort => "password_too_short",
+            Self::TooLong => "password_too_long",
+            Self::ContainsUsername => "password_contains_username",
+        }
+    }
+}
+
+/// Check a new password against the password policy
+///
+/// Returns all violated rules
+pub(crate) fn check_policy(password: &str, username: &str) -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        violations.push(PolicyViolation::TooShort);
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        violations.push(PolicyViolation::TooLong);
+    }
+    if !username.trim().is_empty()
+        && password
+            .to_lowercase()
+            .contains(&username.trim().to_lowercase())
+    {
+        violations.push(PolicyViolation::ContainsUsername);
+    }
+    violations
+}
@@ -1,33 +1,39 @@
 //! Custom `SessionStore` implementation to store session data in our database
 //!
-//! `tower_sessions` does not provide this out of the box
+//! `tower_sessions` does not provide this out of the box. The same store is
+//! used for all database backends
 use crate::database::schema::session_records;
+use crate::database::{DbBackend, DbConnection, DbPool, Id};
 use axum_login::tower_sessions::session::{self, Record};
 use axum_login::tower_sessions::session_store;
-use axum_login::tower_sessions::SessionStore;
+use axum_login::tower_sessions::{ExpiredDeletion, SessionStore};
 use diesel::prelude::*;
-use diesel::sqlite::Sqlite;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 use time::OffsetDateTime;
 
+/// Session key used by `axum_login` to store the logged in user
+pub(crate) const AUTH_DATA_KEY: &str = "axum-login.data";
+
 #[derive(Clone)]
-pub struct SqliteSessionStore {
-    pub(crate) pool: deadpool_diesel::sqlite::Pool,
+pub struct DatabaseSessionStore {
+    pub(crate) pool: DbPool,
 }
 
-impl std::fmt::Debug for SqliteSessionStore {
+impl std::fmt::Debug for DatabaseSessionStore {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("SqliteSessionStore").finish()
+        f.debug_struct("DatabaseSessionStore").finish()
     }
 }
 
-impl SqliteSessionStore {
-    pub fn new(pool: deadpool_diesel::sqlite::Pool) -> Self {
+impl DatabaseSessionStore {
+    pub fn new(pool: DbPool) -> Self {
         Self { pool }
     }
 
     pub(crate) async fn with_connection<T: Send + 'static>(
         &self,
-        c: impl FnOnce(&mut SqliteConnection) -> QueryResult<T> + Send + 'static,
+        c: impl FnOnce(&mut DbConnection) -> QueryResult<T> + Send + 'static,
     ) -> session_store::Result<T> {
         self.pool
             .get()
@@ -38,11 +44,27 @@ impl SqliteSessionStore {
             .map_err(|e| session_store::Error::Backend(e.to_string()))?
             .map_err(|e| session_store::Error::Backend(e.to_string()))
     }
+
+    /// Remove expired sessions every `period`
+    ///
+    /// This runs until the application stops, errors are only logged
+    /// so that a temporary database problem does not stop the cleanup
+    pub async fn run_cleanup_task(self, period: std::time::Duration) {
+        let mut interval = tokio::time::interval(period);
+        // the first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.delete_expired().await {
+                tracing::error!(error = %e, "Failed to delete expired sessions");
+            }
+        }
+    }
 }
 
 #[derive(Insertable, Queryable, Selectable)]
 #[diesel(table_name = session_records)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub(crate) struct SessionRecord {
     pub(crate) id: Vec<u8>,
     pub(crate) data: String,
@@ -61,6 +83,25 @@ impl TryFrom<&'_ Record> for SessionRecord {
     }
 }
 
+/// The id of the user logged in with this session, as stored by `axum_login`
+fn logged_in_user(record: &Record) -> Option<Id> {
+    record
+        .data
+        .get(AUTH_DATA_KEY)?
+        .get("user_id")?
+        .as_i64()?
+        .try_into()
+        .ok()
+}
+
+fn generate_public_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect()
+}
+
 impl TryFrom<SessionRecord> for Record {
     type Error = session_store::Error;
 
@@ -80,18 +121,26 @@ impl TryFrom<SessionRecord> for Record {
 }
 
 #[async_trait::async_trait]
-impl SessionStore for SqliteSessionStore {
+impl SessionStore for DatabaseSessionStore {
     async fn save(&self, session_record: &Record) -> session_store::Result<()> {
         let record_to_insert = SessionRecord::try_from(session_record)
             .map_err(|e| session_store::Error::Decode(e.to_string()))?;
+        let user_id = logged_in_user(session_record);
+        // only used for new sessions, existing ones keep their public id
+        let public_id = generate_public_id();
         self.with_connection(move |conn| {
             diesel::insert_into(session_records::table)
-                .values(&record_to_insert)
+                .values((
+                    &record_to_insert,
+                    session_records::public_id.eq(public_id),
+                    session_records::user_id.eq(user_id),
+                ))
                 .on_conflict(session_records::id)
                 .do_update()
                 .set((
                     session_records::data.eq(&record_to_insert.data),
                     session_records::expiry_date.eq(&record_to_insert.expiry_date),
+                    session_records::user_id.eq(user_id),
                 ))
                 .execute(conn)
                 .map(|_| ())
@@ -105,6 +154,7 @@ impl SessionStore for SqliteSessionStore {
             .with_connection(move |conn| {
                 session_records::table
                     .find(id)
+                    .filter(session_records::expiry_date.gt(OffsetDateTime::now_utc()))
                     .select(SessionRecord::as_select())
                     .first(conn)
                     .optional()
@@ -122,3 +172,20 @@ impl SessionStore for SqliteSessionStore {
         .await
     }
 }
+
+#[async_trait::async_trait]
+impl ExpiredDeletion for DatabaseSessionStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        let count = self
+            .with_connection(|conn| {
+                diesel::delete(
+                    session_records::table
+                        .filter(session_records::expiry_date.le(OffsetDateTime::now_utc())),
+                )
+                .execute(conn)
+            })
+            .await?;
+        tracing::debug!(count, "Deleted expired sessions");
+        Ok(())
+    }
+}
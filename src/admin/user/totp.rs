@@ -0,0 +1,313 @@
+//! Time-based one-time passwords (RFC 6238) as second login factor
+//!
+//! Codes are derived with HMAC-SHA1 from a shared secret, as this is what
+//! all common authenticator apps expect. All verification functions take
+//! the current time as argument, so they can be tested with a fixed clock.
+//!
+//! Each user enabling two-factor authentication also gets a set of recovery
+//! codes. Each of them can be used once instead of a code from the app, e.g.
+//! if the phone got lost. Only hashes of the recovery codes are stored.
+use crate::admin::csrf::token_matches;
+use crate::database::schema::{recovery_codes, users};
+use crate::database::{DbConnection, Id};
+use crate::errors::Result;
+use diesel::prelude::*;
+use hmac::{Hmac, Mac};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use time::PrimitiveDateTime;
+
+/// Number of seconds each code is valid
+const PERIOD: u64 = 30;
+/// Number of digits of each code
+const DIGITS: usize = 6;
+/// Number of time steps before and after the current one that are accepted
+/// as well, to allow for clocks that are slightly off
+const ALLOWED_DRIFT: u64 = 1;
+/// Length of the shared secret in bytes, as recommended by RFC 4226
+const SECRET_LENGTH: usize = 20;
+/// Number of recovery codes generated when enabling two-factor authentication
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Number of characters of each half of a recovery code
+const RECOVERY_CODE_HALF_LENGTH: usize = 5;
+/// Issuer shown in the authenticator app
+const ISSUER: &str = "Race Timing";
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// How a login was confirmed in the second step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SecondFactor {
+    Totp,
+    RecoveryCode,
+}
+
+/// Generate a new random base32 encoded secret
+pub(crate) fn generate_secret() -> String {
+    let mut secret = [0_u8; SECRET_LENGTH];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    base32_encode(&secret)
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(char::from(
+                BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize],
+            ));
+        }
+    }
+    if bits > 0 {
+        encoded.push(char::from(
+            BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize],
+        ));
+    }
+    encoded
+}
+
+/// Decode a base32 string, ignoring case, whitespace and padding
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let c = u8::try_from(c.to_ascii_uppercase()).ok()?;
+        let value = BASE32_ALPHABET.iter().position(|a| *a == c)?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push(((buffer >> bits) & 0xff) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+/// HOTP value for the given counter as defined in RFC 4226
+fn hotp(key: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+    let value = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    let code = value % 10_u32.pow(DIGITS as u32);
+    format!("{code:0DIGITS$}")
+}
+
+/// The code for the given secret that is valid at the given unix timestamp
+///
+/// Returns `None` if the secret is not valid base32
+pub fn code_at(secret: &str, unix_time: u64) -> Option<String> {
+    let key = base32_decode(secret)?;
+    Some(hotp(&key, unix_time / PERIOD))
+}
+
+/// Check a code from the authenticator app against the secret
+///
+/// Codes of the neighbouring time steps are accepted as well. Codes of time
+/// steps up to `last_used_step` are rejected, so each code works only once.
+/// Returns the time step of the matching code
+pub(crate) fn verify_code(
+    secret: &str,
+    code: &str,
+    unix_time: u64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let key = base32_decode(secret)?;
+    let code = code
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    if code.len() != DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let current = unix_time / PERIOD;
+    (current.saturating_sub(ALLOWED_DRIFT)..=current + ALLOWED_DRIFT)
+        .filter_map(|step| Some((step, i64::try_from(step).ok()?)))
+        .filter(|(_, step)| last_used_step.is_none_or(|last| *step > last))
+        .find(|(counter, _)| token_matches(hotp(&key, *counter).as_bytes(), &code))
+        .map(|(_, step)| step)
+}
+
+/// URI to setup the secret in an authenticator app, usually shown as QR code
+pub(crate) fn provisioning_uri(secret: &str, username: &str) -> String {
+    let issuer = percent_encode(ISSUER);
+    let account = percent_encode(username);
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}"
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Render the given data as QR code in SVG format
+pub(crate) fn qr_code_svg(data: &str) -> Result<String> {
+    let code = QrCode::new(data)?;
+    Ok(code
+        .render::<svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+/// Generate a new set of recovery codes, formatted as `xxxxx-xxxxx`
+pub(crate) fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::rngs::OsRng;
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut half = || {
+                (0..RECOVERY_CODE_HALF_LENGTH)
+                    .map(|_| {
+                        char::from(BASE32_ALPHABET[rng.gen_range(0..BASE32_ALPHABET.len())])
+                            .to_ascii_lowercase()
+                    })
+                    .collect::<String>()
+            };
+            format!("{}-{}", half(), half())
+        })
+        .collect()
+}
+
+/// Hash of a recovery code as stored in the database
+///
+/// Recovery codes are random enough that a fast hash function is sufficient
+fn hash_recovery_code(code: &str) -> String {
+    let normalized = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect::<String>();
+    Sha256::digest(normalized.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// The secret of this user, if two-factor authentication is enabled
+pub(crate) fn secret(conn: &mut DbConnection, user_id: Id) -> QueryResult<Option<String>> {
+    users::table
+        .find(user_id)
+        .select(users::totp_secret)
+        .first(conn)
+}
+
+/// Enable two-factor authentication for this user
+///
+/// `used_step` is the time step of the code used to confirm the setup,
+/// any existing recovery codes are replaced by the given ones
+pub(crate) fn enable(
+    conn: &mut DbConnection,
+    user_id: Id,
+    secret: &str,
+    used_step: i64,
+    codes: &[String],
+) -> QueryResult<()> {
+    conn.transaction(|conn| {
+        diesel::update(users::table.find(user_id))
+            .set((
+                users::totp_secret.eq(secret),
+                users::totp_last_used_step.eq(used_step),
+            ))
+            .execute(conn)?;
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id)))
+            .execute(conn)?;
+        let codes = codes
+            .iter()
+            .map(|code| {
+                (
+                    recovery_codes::user_id.eq(user_id),
+                    recovery_codes::code_hash.eq(hash_recovery_code(code)),
+                )
+            })
+            .collect::<Vec<_>>();
+        diesel::insert_into(recovery_codes::table)
+            .values(codes)
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Disable two-factor authentication for this user and remove the recovery codes
+pub(crate) fn disable(conn: &mut DbConnection, user_id: Id) -> QueryResult<()> {
+    conn.transaction(|conn| {
+        diesel::update(users::table.find(user_id))
+            .set((
+                users::totp_secret.eq(None::<String>),
+                users::totp_last_used_step.eq(None::<i64>),
+            ))
+            .execute(conn)?;
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id)))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+/// Number of recovery codes of this user that were not used yet
+pub(crate) fn remaining_recovery_codes(conn: &mut DbConnection, user_id: Id) -> QueryResult<i64> {
+    recovery_codes::table
+        .filter(recovery_codes::user_id.eq(user_id))
+        .filter(recovery_codes::used_at.is_null())
+        .count()
+        .get_result(conn)
+}
+
+/// Check the second factor of this user
+///
+/// Accepts either a code from the authenticator app or an unused recovery
+/// code. The accepted code is marked as used, so it cannot be replayed.
+/// Returns `None` if the code is invalid or two-factor authentication is
+/// not enabled for this user
+pub(crate) fn verify(
+    conn: &mut DbConnection,
+    user_id: Id,
+    code: &str,
+    unix_time: u64,
+    now: PrimitiveDateTime,
+) -> QueryResult<Option<SecondFactor>> {
+    conn.transaction(|conn| {
+        let (secret, last_used_step) = users::table
+            .find(user_id)
+            .select((users::totp_secret, users::totp_last_used_step))
+            .first::<(Option<String>, Option<i64>)>(conn)?;
+        let Some(secret) = secret else {
+            return Ok(None);
+        };
+        if let Some(step) = verify_code(&secret, code, unix_time, last_used_step) {
+            diesel::update(users::table.find(user_id))
+                .set(users::totp_last_used_step.eq(step))
+                .execute(conn)?;
+            return Ok(Some(SecondFactor::Totp));
+        }
+        let used = diesel::update(
+            recovery_codes::table
+                .filter(recovery_codes::user_id.eq(user_id))
+                .filter(recovery_codes::code_hash.eq(hash_recovery_code(code)))
+                .filter(recovery_codes::used_at.is_null()),
+        )
+        .set(recovery_codes::used_at.eq(now))
+        .execute(conn)?;
+        Ok((used > 0).then_some(SecondFactor::RecoveryCode))
+    })
+}
@@ -1,17 +1,28 @@
+use crate::admin::csrf::CsrfToken;
+use crate::admin::user::login_throttle::LoginThrottleSettings;
+use crate::assets;
 use crate::axum_ext::AcceptLanguage;
+use crate::branding::{self, Branding};
+use crate::database::instrumentation::QueryInstrumentation;
+use crate::database::{DbConnection, DbPool, Hook, HookError, Id};
 use crate::errors::Result;
+use crate::metrics::Metrics;
 use crate::service_config::Config;
+use crate::templates;
 use axum::response::Html;
 use axum_extra::TypedHeader;
-use deadpool_diesel::sqlite::{Hook, HookError};
 use deadpool_sync::SyncWrapper;
-use diesel::connection::InstrumentationEvent;
-use diesel::{Connection, QueryResult, RunQueryDsl, SqliteConnection};
+#[cfg(feature = "sqlite")]
+use diesel::RunQueryDsl;
+use diesel::{Connection, QueryResult};
 use fluent_templates::Loader;
 use minijinja::value::ViaDeserialize;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use time::format_description;
 use time::macros::format_description;
 
@@ -27,11 +38,25 @@ fluent_templates::static_loader! {
 #[derive(Clone)]
 pub struct State {
     /// database connection pool
-    pub pool: deadpool_diesel::sqlite::Pool,
+    pub pool: DbPool,
     /// template context used for rendering HTML pages
-    pub templates: minijinja::Environment<'static>,
+    ///
+    /// Shared between all clones of the state, so loaded templates are cached
+    pub templates: Arc<minijinja::Environment<'static>>,
+    /// directory with templates overriding the embedded ones, see `crate::templates`
+    pub template_dir: Option<PathBuf>,
+    /// whether templates are reloaded on every render, for development
+    pub template_reload: bool,
     /// base url path the application is served at
     pub base_url: Arc<str>,
+    /// how long soft deleted entries are kept before they can be purged
+    pub trash_retention: time::Duration,
+    /// how failed login attempts are throttled
+    pub login_throttle: LoginThrottleSettings,
+    /// used instead of the system clock to verify one-time passwords, only set for tests
+    pub fixed_time: Option<time::OffsetDateTime>,
+    /// Prometheus metrics, see `crate::metrics`
+    pub metrics: Metrics,
 }
 
 impl State {
@@ -47,92 +72,133 @@ impl State {
             manager_config,
         );
         let mut templates = minijinja::Environment::new();
-        templates.set_loader(minijinja::path_loader(&config.template_dir));
+        templates::set_loader(&mut templates, config.template_dir.clone());
         templates.add_filter("format_date", format_date);
         templates.add_filter("format_timestamp", format_timestamp);
         templates.add_function("translate", translate);
+        templates.add_function("asset_url", assets::asset_url);
+        // backups can only be downloaded with SQLite, see `crate::database::backup`
+        templates.add_global("backups_enabled", cfg!(feature = "sqlite"));
+        let metrics = Metrics::new();
         let mut builder = deadpool_diesel::Pool::builder(manager);
         if is_test {
             // for tests set the poolsize to 1
             builder = builder.max_size(1);
         }
 
+        let query_duration = metrics.query_duration.clone();
+        let slow_query_threshold = (config.slow_query_threshold_ms > 0)
+            .then(|| Duration::from_millis(config.slow_query_threshold_ms));
         let pool = builder
             .post_create(Hook::async_fn(move |conn, _metrics| {
-                Box::pin(custom_connection_setup(conn, is_test))
+                let instrumentation =
+                    QueryInstrumentation::new(slow_query_threshold, query_duration.clone());
+                Box::pin(custom_connection_setup(conn, is_test, instrumentation))
             }))
             .build()
             .expect("Could not build the connection pool");
         Self {
             pool,
-            templates,
+            templates: Arc::new(templates),
+            template_dir: config.template_dir.clone(),
+            template_reload: config.template_reload,
             base_url: config.base_url.clone().into(),
+            trash_retention: time::Duration::days(config.trash_retention_days.into()),
+            login_throttle: LoginThrottleSettings::from_config(config),
+            fixed_time: config.fixed_time,
+            metrics,
         }
     }
 
     pub async fn with_connection<T: Send + 'static>(
         &self,
-        callback: impl FnOnce(&mut SqliteConnection) -> QueryResult<T> + Send + 'static,
+        callback: impl FnOnce(&mut DbConnection) -> QueryResult<T> + Send + 'static,
     ) -> Result<T> {
         Ok(self.pool.get().await?.interact(callback).await??)
     }
+
+    /// The template environment, in the reload mode with all templates loaded again
+    pub fn template_env(&self) -> Cow<'_, minijinja::Environment<'static>> {
+        if self.template_reload {
+            let mut templates = minijinja::Environment::clone(&self.templates);
+            templates.clear_templates();
+            Cow::Owned(templates)
+        } else {
+            Cow::Borrowed(&self.templates)
+        }
+    }
+
+    /// Current unix timestamp used to verify one-time passwords
+    pub fn totp_time(&self) -> u64 {
+        let now = self
+            .fixed_time
+            .unwrap_or_else(time::OffsetDateTime::now_utc);
+        u64::try_from(now.unix_timestamp()).unwrap_or_default()
+    }
 }
 
 /// apply various custom settings to each database connection
 ///
 /// 1. Call `Connection::begin_test_transaction` if the is_test flag is set
 /// 2. Register custom SQL functions
-/// 3. Setup instrumentation for logging
-/// 4. Setup various configs to make SQLite a suitable solution for hosting a web application
+/// 3. Setup instrumentation for logging, slow query detection and query duration metrics,
+///    see `QueryInstrumentation`
+/// 4. Setup various configs to make SQLite a suitable solution for hosting a web application,
+///    see `configure_sqlite`
 async fn custom_connection_setup(
-    conn: &mut SyncWrapper<SqliteConnection>,
+    conn: &mut SyncWrapper<DbConnection>,
     is_test: bool,
+    instrumentation: QueryInstrumentation,
 ) -> Result<(), HookError> {
     let _ = conn
         .interact(move |conn| {
             // setup test configurations
             if is_test {
-                // not required as we use `:memory:` databases for testing
+                // not required as each test uses its own database,
+                // e.g. `:memory:` for SQLite
                 // otherwise this would be what you want
                 //conn.begin_test_transaction()?;
             }
-            // setup instrumentation to log every query
-            conn.set_instrumentation(|event: InstrumentationEvent<'_>| {
-                // This is a really simple setup that just logs every query
-                // A real world implementation might also want to record more events
-                // and some timings here
-                if let InstrumentationEvent::StartQuery { query, .. } = event {
-                    tracing::debug!(?query)
-                }
-            });
-            // see https://fractaledmind.github.io/2023/09/07/enhancing-rails-sqlite-fine-tuning/
-            // sleep if the database is busy
-            // this corresponds to 2 seconds
-            // if we ever see errors regarding busy_timeout in production
-            // we might want to consider to increase this time
-            diesel::sql_query("PRAGMA busy_timeout = 2000;").execute(conn)?;
-            // better write-concurrency
-            diesel::sql_query("PRAGMA journal_mode = WAL;").execute(conn)?;
-            // fsync only in critical moments
-            diesel::sql_query("PRAGMA synchronous = NORMAL;").execute(conn)?;
-            // write WAL changes back every 1000 pages, for an in average 1MB WAL file. May affect readers if number is increased
-            diesel::sql_query("PRAGMA wal_autocheckpoint = 1000;").execute(conn)?;
-            // free some space by truncating possibly massive WAL files from the last run
-            diesel::sql_query("PRAGMA wal_checkpoint(TRUNCATE);").execute(conn)?;
-            // maximum size of the WAL file, corresponds to 64MB
-            diesel::sql_query("PRAGMA journal_size_limit = 67108864;").execute(conn)?;
-            // maximum size of the internal mmap pool. Corresponds to 128MB, matches postgres default settings
-            diesel::sql_query("PRAGMA mmap_size = 134217728;").execute(conn)?;
-            // maximum number of database disk pages that will be hold in memory. Corresponds to ~8MB
-            diesel::sql_query("PRAGMA cache_size = 2000;").execute(conn)?;
-            //enforce foreign keys
-            diesel::sql_query("PRAGMA foreign_keys = ON;").execute(conn)?;
+            // setup instrumentation to log every query and record its duration
+            conn.set_instrumentation(instrumentation);
+            // the SQLite defaults are not suitable for a web application,
+            // PostgreSQL needs no further setup
+            #[cfg(feature = "sqlite")]
+            configure_sqlite(conn)?;
             QueryResult::Ok(())
         })
         .await;
     Ok(())
 }
 
+/// Settings to make SQLite a suitable solution for hosting a web application
+#[cfg(feature = "sqlite")]
+fn configure_sqlite(conn: &mut DbConnection) -> QueryResult<()> {
+    // see https://fractaledmind.github.io/2023/09/07/enhancing-rails-sqlite-fine-tuning/
+    // sleep if the database is busy
+    // this corresponds to 2 seconds
+    // if we ever see errors regarding busy_timeout in production
+    // we might want to consider to increase this time
+    diesel::sql_query("PRAGMA busy_timeout = 2000;").execute(conn)?;
+    // better write-concurrency
+    diesel::sql_query("PRAGMA journal_mode = WAL;").execute(conn)?;
+    // fsync only in critical moments
+    diesel::sql_query("PRAGMA synchronous = NORMAL;").execute(conn)?;
+    // write WAL changes back every 1000 pages, for an in average 1MB WAL file. May affect readers if number is increased
+    diesel::sql_query("PRAGMA wal_autocheckpoint = 1000;").execute(conn)?;
+    // free some space by truncating possibly massive WAL files from the last run
+    diesel::sql_query("PRAGMA wal_checkpoint(TRUNCATE);").execute(conn)?;
+    // maximum size of the WAL file, corresponds to 64MB
+    diesel::sql_query("PRAGMA journal_size_limit = 67108864;").execute(conn)?;
+    // maximum size of the internal mmap pool. Corresponds to 128MB, matches postgres default settings
+    diesel::sql_query("PRAGMA mmap_size = 134217728;").execute(conn)?;
+    // maximum number of database disk pages that will be hold in memory. Corresponds to ~8MB
+    diesel::sql_query("PRAGMA cache_size = 2000;").execute(conn)?;
+    //enforce foreign keys
+    diesel::sql_query("PRAGMA foreign_keys = ON;").execute(conn)?;
+    Ok(())
+}
+
 fn format_date(arg: ViaDeserialize<time::PrimitiveDateTime>) -> String {
     arg.0
         .format(
@@ -173,6 +239,7 @@ fn lookup_translation(lang_keys: &AcceptLanguage, key: &str, args: HashMap<&str,
 pub struct AppState {
     state: State,
     lang_keys: AcceptLanguage,
+    csrf_token: Option<CsrfToken>,
 }
 
 #[async_trait::async_trait]
@@ -185,9 +252,12 @@ impl axum::extract::FromRequestParts<State> for AppState {
         state: &State,
     ) -> Result<Self, Self::Rejection> {
         let TypedHeader(lang_keys) = <Option<TypedHeader<AcceptLanguage>> as axum::extract::FromRequestParts<_>>::from_request_parts(parts, state).await?.unwrap_or_else(|| TypedHeader(AcceptLanguage::default()));
+        // only set for the admin pages, see `crate::admin::csrf`
+        let csrf_token = parts.extensions.get::<CsrfToken>().cloned();
         Ok(Self {
             state: state.clone(),
             lang_keys,
+            csrf_token,
         })
     }
 }
@@ -196,6 +266,9 @@ impl axum::extract::FromRequestParts<State> for AppState {
 struct TemplateData<'a, T> {
     base_url: &'a str,
     lang_keys: &'a AcceptLanguage,
+    csrf_token: Option<&'a str>,
+    /// only set for pages of a competition, see `crate::branding`
+    branding: Option<&'a Branding>,
     #[serde(flatten)]
     inner: T,
 }
@@ -207,11 +280,38 @@ impl AppState {
         name: &'static str,
         data: impl Serialize,
     ) -> Result<Html<String>> {
-        let template = self.state.templates.get_template(name)?;
+        self.render_with_branding(name, None, data)
+    }
+
+    /// render a template of a page belonging to a competition
+    ///
+    /// The page uses the logo, colors and footer of the competition, if any
+    pub async fn render_competition_template(
+        &self,
+        name: &'static str,
+        competition_id: Id,
+        data: impl Serialize + Send,
+    ) -> Result<Html<String>> {
+        let branding = self
+            .with_connection(move |conn| branding::load(conn, competition_id))
+            .await?;
+        self.render_with_branding(name, branding.as_ref(), data)
+    }
+
+    fn render_with_branding(
+        &self,
+        name: &'static str,
+        branding: Option<&Branding>,
+        data: impl Serialize,
+    ) -> Result<Html<String>> {
+        let templates = self.state.template_env();
+        let template = templates.get_template(name)?;
         let base_url = &self.state.base_url;
         Ok(Html(template.render(TemplateData {
             base_url,
             lang_keys: &self.lang_keys,
+            csrf_token: self.csrf_token.as_ref().map(|t| t.0.as_str()),
+            branding,
             inner: data,
         })?))
     }
@@ -219,7 +319,7 @@ impl AppState {
     /// Interact with a database connection
     pub async fn with_connection<T: Send + 'static>(
         &self,
-        callback: impl FnOnce(&mut SqliteConnection) -> QueryResult<T> + Send + 'static,
+        callback: impl FnOnce(&mut DbConnection) -> QueryResult<T> + Send + 'static,
     ) -> Result<T> {
         Ok(self.state.pool.get().await?.interact(callback).await??)
     }
@@ -228,6 +328,18 @@ impl AppState {
         &self.state.base_url
     }
 
+    pub fn trash_retention(&self) -> time::Duration {
+        self.state.trash_retention
+    }
+
+    pub fn login_throttle(&self) -> LoginThrottleSettings {
+        self.state.login_throttle
+    }
+
+    pub fn totp_time(&self) -> u64 {
+        self.state.totp_time()
+    }
+
     pub fn translation(&self, key: &str) -> String {
         lookup_translation(&self.lang_keys, key, HashMap::new())
     }
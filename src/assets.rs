@@ -0,0 +1,194 @@
+//! Static assets and HTTP caching
+//!
+//! The stylesheets are embedded into the binary. Templates link them with a
+//! hash of their content, see `asset_url`, so responses for the current
+//! content can be cached forever while a changed stylesheet gets a new url.
+//! Requests without or with an outdated hash have to revalidate their cached
+//! copy with the `ETag` header. Generated assets like the theme of a
+//! competition use the same scheme with their own version, see
+//! `crate::branding`.
+//!
+//! Public pages change while a competition takes place, so they are never
+//! cached without revalidation. They get an `ETag` of their content instead,
+//! so an unchanged page is answered with `304 Not Modified`, see `etag_page`.
+//!
+//! All `ETag`s are weak, as responses are compressed depending on the
+//! `Accept-Encoding` of the request.
+use crate::app_state;
+use axum::body::Body;
+use axum::extract::{Query, Request};
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, X_CONTENT_TYPE_OPTIONS};
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use axum_extra::headers::{ETag, HeaderMapExt, IfNoneMatch};
+use axum_extra::TypedHeader;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+pub fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route("/assets/simple.min.css", axum::routing::get(get_simple_css))
+        .route("/assets/custom.css", axum::routing::get(get_custom_css))
+}
+
+/// Caching of assets requested with their current version
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// Caching of assets requested without or with an outdated version, and of pages
+const REVALIDATE: &str = "no-cache";
+
+/// Stylesheets embedded into the binary, by their name below `/assets/`
+const STATIC_ASSETS: &[(&str, &str)] = &[
+    ("simple.min.css", include_str!("../assets/simple.min.css")),
+    ("custom.css", include_str!("../assets/custom.css")),
+];
+
+/// Hash of the content of each static asset, used as its version
+static VERSIONS: LazyLock<BTreeMap<&'static str, String>> = LazyLock::new(|| {
+    STATIC_ASSETS
+        .iter()
+        .map(|(name, content)| (*name, content_hash(content.as_bytes())))
+        .collect()
+});
+
+/// Short hash identifying some content, used for urls and `ETag`s
+fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .take(8)
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Url of a static asset with the hash of its content, relative to the base url
+///
+/// Available to the templates as `asset_url("custom.css")`
+pub(crate) fn asset_url(name: &str) -> Result<minijinja::Value, minijinja::Error> {
+    let version = VERSIONS.get(name).ok_or_else(|| {
+        minijinja::Error::new(
+            minijinja::ErrorKind::InvalidOperation,
+            format!("There is no static asset `{name}`"),
+        )
+    })?;
+    // only known names and hex digits, nothing to escape
+    Ok(minijinja::Value::from_safe_string(format!(
+        "/assets/{name}?v={version}"
+    )))
+}
+
+/// The version requested as part of an asset url
+#[derive(Deserialize)]
+pub(crate) struct AssetVersion {
+    v: Option<String>,
+}
+
+fn weak_etag(version: &str) -> (HeaderValue, ETag) {
+    let etag = format!("W/\"{version}\"");
+    (
+        HeaderValue::from_str(&etag).expect("A version is a valid header value"),
+        etag.parse().expect("A quoted version is a valid ETag"),
+    )
+}
+
+/// Respond with an asset of the given version
+///
+/// Answers with `304 Not Modified` if the client already has this version
+pub(crate) fn versioned_asset(
+    version: &str,
+    requested: AssetVersion,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+    content_type: HeaderValue,
+    body: impl IntoResponse,
+) -> Response {
+    let cache_control = if requested.v.as_deref() == Some(version) {
+        IMMUTABLE
+    } else {
+        REVALIDATE
+    };
+    let (etag_header, etag) = weak_etag(version);
+    let headers = [
+        (ETAG, etag_header),
+        (CACHE_CONTROL, HeaderValue::from_static(cache_control)),
+    ];
+    if if_none_match.is_some_and(|TypedHeader(h)| !h.precondition_passes(&etag)) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    (
+        headers,
+        [
+            (CONTENT_TYPE, content_type),
+            (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+fn static_asset(
+    name: &str,
+    requested: AssetVersion,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    let content = STATIC_ASSETS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, content)| *content)
+        .expect("Only called for embedded assets");
+    versioned_asset(
+        &VERSIONS[name],
+        requested,
+        if_none_match,
+        HeaderValue::from_static("text/css"),
+        content,
+    )
+}
+
+#[axum::debug_handler]
+async fn get_simple_css(
+    requested: Query<AssetVersion>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    static_asset("simple.min.css", requested.0, if_none_match)
+}
+
+#[axum::debug_handler]
+async fn get_custom_css(
+    requested: Query<AssetVersion>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    static_asset("custom.css", requested.0, if_none_match)
+}
+
+/// Middleware adding an `ETag` of the content to successfully rendered pages
+///
+/// Pages are still rendered for every request, but only sent if they changed
+/// since the client requested them the last time.
+pub(crate) async fn etag_page(request: Request, next: Next) -> Response {
+    let if_none_match = request.headers().typed_get::<IfNoneMatch>();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to read the rendered page");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let (etag_header, etag) = weak_etag(&content_hash(&body));
+    parts.headers.insert(ETAG, etag_header);
+    parts
+        .headers
+        .insert(CACHE_CONTROL, HeaderValue::from_static(REVALIDATE));
+    if if_none_match.is_some_and(|h| !h.precondition_passes(&etag)) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,157 @@
+//! Per-competition branding of the public pages
+//!
+//! Each competition can have its own logo, accent colors and footer, which
+//! are edited on the admin pages, see `crate::admin::branding`. Pages of a
+//! competition get the branding passed to `templates/base.html`, which links
+//! a generated stylesheet and the logo served below
+//! `/assets/competition/:competition_id/`.
+//!
+//! The asset urls contain the version of the branding, so they are cached
+//! like the static assets, see `crate::assets`.
+use crate::app_state::{self, AppState};
+use crate::assets::{versioned_asset, AssetVersion};
+use crate::database::schema::{competition_branding, competitions};
+use crate::database::{DbBackend, DbConnection, Id};
+use crate::errors::{Error, Result};
+use axum::extract::{Path, Query};
+use axum::http::HeaderValue;
+use axum::response::Response;
+use axum::Router;
+use axum_extra::headers::IfNoneMatch;
+use axum_extra::TypedHeader;
+use diesel::prelude::*;
+use serde::Serialize;
+use time::PrimitiveDateTime;
+
+pub fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/assets/competition/:competition_id/theme.css",
+            axum::routing::get(get_theme),
+        )
+        .route(
+            "/assets/competition/:competition_id/logo",
+            axum::routing::get(get_logo),
+        )
+}
+
+/// Branding of a competition, as available to the templates
+#[derive(Queryable, Selectable, Serialize, Debug)]
+#[diesel(table_name = competition_branding)]
+#[diesel(check_for_backend(DbBackend))]
+pub(crate) struct Branding {
+    pub(crate) competition_id: Id,
+    /// CSS color like `#0f9e18`
+    pub(crate) accent_color: Option<String>,
+    /// CSS color like `#d0f0c0`
+    pub(crate) accent_background_color: Option<String>,
+    /// plain text shown at the bottom of each page
+    pub(crate) footer: String,
+    #[diesel(
+        select_expression = competition_branding::logo.is_not_null(),
+        select_expression_type = diesel::dsl::IsNotNull<competition_branding::logo>
+    )]
+    pub(crate) has_logo: bool,
+    /// changes with every update, part of the asset urls
+    #[serde(rename = "version", serialize_with = "serialize_version")]
+    pub(crate) updated_at: PrimitiveDateTime,
+}
+
+/// The version of a branding, in microseconds since the epoch of the last update
+pub(crate) fn version(updated_at: PrimitiveDateTime) -> i64 {
+    i64::try_from(updated_at.assume_utc().unix_timestamp_nanos() / 1000).unwrap_or_default()
+}
+
+fn serialize_version<S>(updated_at: &PrimitiveDateTime, ser: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    ser.serialize_i64(version(*updated_at))
+}
+
+/// Load the branding of a competition that is not in the trash
+pub(crate) fn load(conn: &mut DbConnection, competition_id: Id) -> QueryResult<Option<Branding>> {
+    competition_branding::table
+        .inner_join(competitions::table)
+        .filter(competition_branding::competition_id.eq(competition_id))
+        .filter(competitions::deleted_at.is_null())
+        .select(Branding::as_select())
+        .first(conn)
+        .optional()
+}
+
+/// Accent colors overriding the defaults of `assets/custom.css`
+fn theme_css(branding: &Branding) -> String {
+    let mut css = String::from(":root {\n");
+    if let Some(color) = &branding.accent_color {
+        css.push_str(&format!("    --accent: {color};\n"));
+    }
+    if let Some(color) = &branding.accent_background_color {
+        css.push_str(&format!("    --accent-bg: {color};\n"));
+    }
+    css.push_str("}\n");
+    css
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn get_theme(
+    state: AppState,
+    competition_id: Path<Id>,
+    requested: Query<AssetVersion>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Result<Response> {
+    let competition_id = competition_id.0;
+    let branding = state
+        .with_connection(move |conn| load(conn, competition_id))
+        .await?
+        .ok_or_else(|| {
+            Error::NotFound(format!(
+                "No branding for competition {competition_id} found"
+            ))
+        })?;
+    Ok(versioned_asset(
+        &version(branding.updated_at).to_string(),
+        requested.0,
+        if_none_match,
+        HeaderValue::from_static("text/css"),
+        theme_css(&branding),
+    ))
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn get_logo(
+    state: AppState,
+    competition_id: Path<Id>,
+    requested: Query<AssetVersion>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Result<Response> {
+    let competition_id = competition_id.0;
+    let logo = state
+        .with_connection(move |conn| {
+            competition_branding::table
+                .inner_join(competitions::table)
+                .filter(competition_branding::competition_id.eq(competition_id))
+                .filter(competitions::deleted_at.is_null())
+                .filter(competition_branding::logo.is_not_null())
+                .select((
+                    competition_branding::logo.assume_not_null(),
+                    competition_branding::logo_content_type.assume_not_null(),
+                    competition_branding::updated_at,
+                ))
+                .first::<(Vec<u8>, String, PrimitiveDateTime)>(conn)
+                .optional()
+        })
+        .await?;
+    let (logo, content_type, updated_at) = logo.ok_or_else(|| {
+        Error::NotFound(format!("No logo for competition {competition_id} found"))
+    })?;
+    let content_type =
+        HeaderValue::from_str(&content_type).map_err(|e| Error::InvalidInput(e.to_string()))?;
+    Ok(versioned_asset(
+        &version(updated_at).to_string(),
+        requested.0,
+        if_none_match,
+        content_type,
+        logo,
+    ))
+}
@@ -16,6 +16,7 @@ pub async fn render(state: AppState) -> Result<Html<String>> {
     let competitions = state
         .with_connection(move |_conn| {
             // start here implementing loading competation data from the database
+            // (competitions in the trash, i.e. with `deleted_at` set, must not be shown)
             todo!()
         })
         .await?;
@@ -0,0 +1,126 @@
+//! Online backups of the database
+//!
+//! Copying the database file while the server runs is unsafe, as recent
+//! changes may still be in the WAL file and other connections can write
+//! during the copy. Backups are therefore taken on a pooled connection with
+//! `sqlite3_serialize`, which reads all pages within a single read
+//! transaction and so yields a consistent snapshot like SQLite's backup API.
+//! Diesel does not expose the raw connection handle required by the
+//! `sqlite3_backup_*` functions, so these cannot be used on pooled connections.
+//!
+//! Scheduled backups are written to the configured backup directory as
+//! `race_time-<timestamp>.db`. Only the most recent ones are kept.
+use diesel::SqliteConnection;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use time::macros::format_description;
+use time::OffsetDateTime;
+
+/// Prefix of all backup files, other files in the backup directory are ignored
+const BACKUP_PREFIX: &str = "race_time-";
+/// Extension of all backup files
+const BACKUP_EXTENSION: &str = "db";
+
+/// Where and how often scheduled backups are written
+#[derive(Clone, Debug)]
+pub struct BackupSettings {
+    pub dir: PathBuf,
+    pub interval: Duration,
+    /// number of backups kept in `dir`, older ones are removed
+    pub keep: usize,
+}
+
+/// A consistent copy of the whole database, suitable to be written to a file
+pub fn snapshot(conn: &mut SqliteConnection) -> Vec<u8> {
+    conn.serialize_database_to_buffer().to_vec()
+}
+
+/// File name of a backup taken at the given time
+///
+/// The names sort in the order the backups were taken
+pub fn file_name(taken_at: OffsetDateTime) -> String {
+    let format = format_description!("[year][month][day]-[hour][minute][second]");
+    let timestamp = taken_at.format(&format).expect("Can format this timestamp");
+    format!("{BACKUP_PREFIX}{timestamp}.{BACKUP_EXTENSION}")
+}
+
+/// Write a snapshot to the backup directory
+///
+/// The data is written to a temporary file first, so an interrupted backup
+/// never looks like a complete one. Returns the path of the new backup
+pub fn write(dir: &Path, data: &[u8], taken_at: OffsetDateTime) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(file_name(taken_at));
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, data)?;
+    std::fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+/// All backups in the directory, oldest first
+pub fn list(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut backups = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension().is_some_and(|e| e == BACKUP_EXTENSION)
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(BACKUP_PREFIX))
+        })
+        .collect::<Vec<_>>();
+    backups.sort();
+    Ok(backups)
+}
+
+/// Remove all but the `keep` most recent backups
+///
+/// Returns the removed files
+pub fn rotate(dir: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let backups = list(dir)?;
+    let outdated = backups.len().saturating_sub(keep);
+    let removed = backups.into_iter().take(outdated).collect::<Vec<_>>();
+    for path in &removed {
+        std::fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+/// Take a backup and rotate the backup directory
+async fn run_backup(
+    pool: &deadpool_diesel::sqlite::Pool,
+    settings: &BackupSettings,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let data = pool
+        .get()
+        .await?
+        .interact(snapshot)
+        .await
+        .map_err(|e| e.to_string())?;
+    let settings = settings.clone();
+    let path = tokio::task::spawn_blocking(move || {
+        let path = write(&settings.dir, &data, OffsetDateTime::now_utc())?;
+        for removed in rotate(&settings.dir, settings.keep)? {
+            tracing::info!(path = %removed.display(), "Removed outdated backup");
+        }
+        std::io::Result::Ok(path)
+    })
+    .await??;
+    Ok(path)
+}
+
+/// Take a backup every `settings.interval`
+///
+/// This runs until the application stops, errors are only logged
+pub async fn run_backup_task(pool: deadpool_diesel::sqlite::Pool, settings: BackupSettings) {
+    let mut interval = tokio::time::interval(settings.interval);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match run_backup(&pool, &settings).await {
+            Ok(path) => tracing::info!(path = %path.display(), "Wrote database backup"),
+            Err(e) => tracing::error!(error = %e, "Failed to write database backup"),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Instrumentation of database connections
+//!
+//! Each query runs within a `db_query` tracing span carrying its SQL, the
+//! number of bind parameters and, once finished, its duration. Queries
+//! exceeding the configured threshold are logged at WARN level. Only the
+//! number of bind parameters is logged there, as their values may contain
+//! personal data of the participants.
+use diesel::connection::{DebugQuery, Instrumentation, InstrumentationEvent};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::Span;
+
+/// Logs every query and records its duration
+pub struct QueryInstrumentation {
+    /// queries taking at least this long are logged as slow, `None` disables it
+    slow_query_threshold: Option<Duration>,
+    query_duration: prometheus::Histogram,
+    running: Option<RunningQuery>,
+}
+
+/// The query currently executed on the connection
+struct RunningQuery {
+    started: Instant,
+    span: Span,
+}
+
+impl QueryInstrumentation {
+    pub fn new(
+        slow_query_threshold: Option<Duration>,
+        query_duration: prometheus::Histogram,
+    ) -> Self {
+        Self {
+            slow_query_threshold,
+            query_duration,
+            running: None,
+        }
+    }
+}
+
+impl Instrumentation for QueryInstrumentation {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { query, .. } => {
+                let span = tracing::debug_span!(
+                    "db_query",
+                    sql = Empty,
+                    binds = Empty,
+                    duration_ms = Empty
+                );
+                // formatting the query is not free, skip it if nobody listens
+                if !span.is_disabled() {
+                    let (sql, binds) = sql_and_bind_count(query);
+                    span.record("sql", sql);
+                    span.record("binds", binds);
+                }
+                span.in_scope(|| tracing::debug!(?query));
+                self.running = Some(RunningQuery {
+                    started: Instant::now(),
+                    span,
+                });
+            }
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                // queries are executed one after another on a connection,
+                // so this is always the one started last
+                let Some(RunningQuery { started, span }) = self.running.take() else {
+                    return;
+                };
+                let elapsed = started.elapsed();
+                self.query_duration.observe(elapsed.as_secs_f64());
+                let duration_ms = elapsed.as_secs_f64() * 1000.0;
+                span.record("duration_ms", duration_ms);
+                if self
+                    .slow_query_threshold
+                    .is_some_and(|threshold| elapsed >= threshold)
+                {
+                    let (sql, binds) = sql_and_bind_count(query);
+                    span.in_scope(|| {
+                        tracing::warn!(
+                            sql,
+                            binds,
+                            duration_ms,
+                            failed = error.is_some(),
+                            "Slow query"
+                        );
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The SQL of a query without its bind values and the number of bind parameters
+fn sql_and_bind_count(query: &dyn DebugQuery) -> (String, usize) {
+    let mut formatted = String::new();
+    if write!(formatted, "{query}").is_err() {
+        return (String::from("<unprintable query>"), 0);
+    }
+    // the bind values are appended after the SQL, see `diesel::debug_query`
+    if let Some(len) = formatted.rfind(" -- binds: ") {
+        formatted.truncate(len);
+    }
+    let binds = count_bind_parameters(&formatted);
+    (formatted, binds)
+}
+
+/// SQLite uses `?` for each bind parameter
+#[cfg(feature = "sqlite")]
+fn count_bind_parameters(sql: &str) -> usize {
+    sql.matches('?').count()
+}
+
+/// PostgreSQL uses numbered bind parameters, e.g. `$1`
+#[cfg(feature = "postgres")]
+fn count_bind_parameters(sql: &str) -> usize {
+    sql.split('$')
+        .skip(1)
+        .filter(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        .count()
+}
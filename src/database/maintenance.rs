@@ -0,0 +1,213 @@
+//! Database maintenance operations for the `db` command line subcommands
+//!
+//! These work on a plain connection, so operators can manage the database
+//! without starting the web server. Backups taken while the server runs are
+//! described in `super::backup`.
+//!
+//! Checking and restoring the database is only available for SQLite, for
+//! PostgreSQL the server's own tools like `pg_dump` and `pg_restore` are used.
+use super::schema::competitions;
+use super::{DbBackend, DbConnection};
+use diesel::migration::{MigrationSource, Result};
+use diesel::prelude::*;
+#[cfg(feature = "sqlite")]
+use diesel::sql_types::{BigInt, Nullable, Text};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
+#[cfg(feature = "sqlite")]
+use std::path::Path;
+
+/// All migrations of the application, embedded at compile time
+#[cfg(feature = "sqlite")]
+pub const MIGRATIONS: EmbeddedMigrations = diesel_migrations::embed_migrations!("migrations");
+/// All migrations of the application, embedded at compile time
+#[cfg(feature = "postgres")]
+pub const MIGRATIONS: EmbeddedMigrations =
+    diesel_migrations::embed_migrations!("migrations_postgres");
+
+/// Whether a migration was applied to the database
+#[derive(Debug)]
+pub struct MigrationStatus {
+    /// name of the migration directory, starting with the version
+    pub name: String,
+    pub applied: bool,
+}
+
+/// Open a connection with the same foreign key handling as the web server
+pub fn establish(database_url: &str) -> Result<DbConnection> {
+    #[cfg_attr(feature = "postgres", allow(unused_mut))]
+    let mut conn = DbConnection::establish(database_url)?;
+    // PostgreSQL always enforces foreign keys
+    #[cfg(feature = "sqlite")]
+    diesel::sql_query("PRAGMA foreign_keys = ON;").execute(&mut conn)?;
+    Ok(conn)
+}
+
+/// Apply all pending migrations
+///
+/// Returns the versions of the applied migrations
+pub fn migrate(conn: &mut DbConnection) -> Result<Vec<String>> {
+    Ok(conn
+        .run_pending_migrations(MIGRATIONS)?
+        .into_iter()
+        .map(|v| v.to_string())
+        .collect())
+}
+
+/// List all known migrations in the order they are applied
+pub fn status(conn: &mut DbConnection) -> Result<Vec<MigrationStatus>> {
+    let applied = conn.applied_migrations()?;
+    let mut migrations = MigrationSource::<DbBackend>::migrations(&MIGRATIONS)?;
+    migrations.sort_by(|a, b| a.name().version().cmp(&b.name().version()));
+    Ok(migrations
+        .iter()
+        .map(|m| MigrationStatus {
+            name: m.name().to_string(),
+            applied: applied.contains(&m.name().version()),
+        })
+        .collect())
+}
+
+/// Revert the most recently applied migration
+///
+/// Returns the version of the reverted migration
+pub fn revert(conn: &mut DbConnection) -> Result<String> {
+    Ok(conn.revert_last_migration(MIGRATIONS)?.to_string())
+}
+
+/// Insert the test data into an empty database
+///
+/// Fails if there are competitions already
+pub fn seed(conn: &mut DbConnection) -> Result<()> {
+    let competition_count = competitions::table.count().get_result::<i64>(conn)?;
+    if competition_count != 0 {
+        return Err(format!(
+            "The database already contains {competition_count} competitions, not inserting test data"
+        )
+        .into());
+    }
+    super::test_data::insert_test_data(conn)?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(QueryableByName)]
+struct IntegrityCheckRow {
+    #[diesel(sql_type = Text)]
+    integrity_check: String,
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(QueryableByName)]
+struct ForeignKeyViolation {
+    #[diesel(sql_type = Text)]
+    table: String,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    rowid: Option<i64>,
+    #[diesel(sql_type = Text)]
+    parent: String,
+}
+
+/// Check the database file for corruption and violated foreign keys
+///
+/// Returns a description of each problem, which is empty for a healthy database
+#[cfg(feature = "sqlite")]
+pub fn check(conn: &mut DbConnection) -> Result<Vec<String>> {
+    let mut problems = diesel::sql_query("PRAGMA integrity_check;")
+        .load::<IntegrityCheckRow>(conn)?
+        .into_iter()
+        .map(|row| row.integrity_check)
+        .filter(|message| message != "ok")
+        .collect::<Vec<_>>();
+    problems.extend(
+        diesel::sql_query("PRAGMA foreign_key_check;")
+            .load::<ForeignKeyViolation>(conn)?
+            .into_iter()
+            .map(|v| {
+                let row = v.rowid.map(|id| format!(" {id}")).unwrap_or_default();
+                format!(
+                    "Row{row} of `{}` references a missing entry of `{}`",
+                    v.table, v.parent
+                )
+            }),
+    );
+    Ok(problems)
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(QueryableByName)]
+struct TableCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Replace the database by a backup
+///
+/// The backup is opened read-only and checked first, the database is only
+/// replaced if the backup is intact. The web server must not be running,
+/// as open connections would still see the old database
+#[cfg(feature = "sqlite")]
+pub fn restore(backup: &Path, database_url: &str) -> Result<()> {
+    if !backup.is_file() {
+        return Err(format!("The backup `{}` does not exist", backup.display()).into());
+    }
+    let not_a_backup = |reason: &dyn std::fmt::Display| {
+        format!(
+            "`{}` is not a backup of this application: {reason}",
+            backup.display()
+        )
+    };
+    let mut source = establish(&read_only_uri(backup))?;
+    let migration_tables = diesel::sql_query(
+        "SELECT COUNT(*) AS count FROM sqlite_master WHERE type = 'table' AND name = '__diesel_schema_migrations';",
+    )
+    .get_result::<TableCount>(&mut source)
+    .map_err(|e| not_a_backup(&e))?
+    .count;
+    if migration_tables != 1 {
+        return Err(not_a_backup(&"no migrations were applied").into());
+    }
+    let problems = check(&mut source)?;
+    if !problems.is_empty() {
+        return Err(format!(
+            "The backup `{}` is damaged: {}",
+            backup.display(),
+            problems.join(", ")
+        )
+        .into());
+    }
+    // write a complete copy next to the database first, so a failed restore
+    // leaves the current database untouched
+    let restored = format!("{database_url}.restore");
+    remove_if_exists(&restored)?;
+    diesel::sql_query("VACUUM INTO ?;")
+        .bind::<Text, _>(&restored)
+        .execute(&mut source)?;
+    // the WAL file of the old database must not be applied to the restored one
+    remove_if_exists(&format!("{database_url}-wal"))?;
+    remove_if_exists(&format!("{database_url}-shm"))?;
+    std::fs::rename(&restored, database_url)?;
+    Ok(())
+}
+
+/// SQLite URI to open a file read-only
+///
+/// `?`, `#` and `%` have a special meaning in URIs, so the path is percent-encoded
+#[cfg(feature = "sqlite")]
+fn read_only_uri(path: &Path) -> String {
+    const PATH_ESCAPES: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+        .add(b' ')
+        .add(b'"')
+        .add(b'#')
+        .add(b'%')
+        .add(b'?');
+    let path = percent_encoding::percent_encode(path.as_os_str().as_encoded_bytes(), PATH_ESCAPES);
+    format!("file:{path}?mode=ro")
+}
+
+#[cfg(feature = "sqlite")]
+fn remove_if_exists(path: &str) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
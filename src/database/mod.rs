@@ -1,6 +1,58 @@
+//! Database access
+//!
+//! The database backend is selected at compile time with either the `sqlite`
+//! (default) or the `postgres` cargo feature. All other code refers to the
+//! backend only through the aliases below.
+#[cfg(feature = "sqlite")]
+pub mod backup;
+pub mod instrumentation;
+pub mod maintenance;
 pub mod schema;
 pub mod shared_models;
 pub mod test_data;
 
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("The `sqlite` and `postgres` features cannot be enabled at the same time");
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("Either the `sqlite` or the `postgres` feature must be enabled");
+
+#[cfg(feature = "sqlite")]
+mod backend {
+    pub use deadpool_diesel::sqlite::{Hook, HookError, Pool as DbPool};
+
+    /// The diesel backend of the selected database
+    pub type DbBackend = diesel::sqlite::Sqlite;
+    /// A connection to the selected database
+    pub type DbConnection = diesel::SqliteConnection;
+
+    /// SQL types that differ between the backends, used by `super::schema`
+    pub mod sql_types {
+        pub use diesel::sql_types::TimestamptzSqlite as TimestampWithTimeZone;
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod backend {
+    pub use deadpool_diesel::postgres::{Hook, HookError, Pool as DbPool};
+
+    /// The diesel backend of the selected database
+    pub type DbBackend = diesel::pg::Pg;
+    /// A connection to the selected database
+    pub type DbConnection = diesel::PgConnection;
+
+    /// SQL types that differ between the backends, used by `super::schema`
+    pub mod sql_types {
+        pub use diesel::sql_types::Timestamptz as TimestampWithTimeZone;
+    }
+}
+
+pub use backend::*;
+
 /// The id type of the application
 pub type Id = i32;
+
+/// The current UTC time as stored in `TIMESTAMP` columns
+pub(crate) fn now() -> time::PrimitiveDateTime {
+    let now = time::OffsetDateTime::now_utc();
+    time::PrimitiveDateTime::new(now.date(), now.time())
+}
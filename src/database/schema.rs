@@ -1,5 +1,31 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        token_hash -> Text,
+        scope -> Text,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        created_at -> Timestamp,
+        user_id -> Nullable<Integer>,
+        action -> Text,
+        entity -> Text,
+        entity_id -> Integer,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     categories (id) {
         id -> Integer,
@@ -11,6 +37,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    certificate_templates (competition_id) {
+        competition_id -> Integer,
+        title -> Text,
+        body -> Text,
+    }
+}
+
+diesel::table! {
+    competition_branding (competition_id) {
+        competition_id -> Integer,
+        accent_color -> Nullable<Text>,
+        accent_background_color -> Nullable<Text>,
+        footer -> Text,
+        logo -> Nullable<Binary>,
+        logo_content_type -> Nullable<Text>,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     competitions (id) {
         id -> Integer,
@@ -19,6 +65,25 @@ diesel::table! {
         date -> Date,
         location -> Text,
         announcement -> Text,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    competitions_in_series (series_id, competition_id) {
+        series_id -> Integer,
+        competition_id -> Integer,
+    }
+}
+
+diesel::table! {
+    login_throttles (id) {
+        id -> Integer,
+        kind -> Text,
+        key -> Text,
+        failed_attempts -> Integer,
+        last_failed_at -> Timestamp,
+        locked_until -> Nullable<Timestamp>,
     }
 }
 
@@ -31,6 +96,12 @@ diesel::table! {
         category_id -> Integer,
         consent_agb -> Bool,
         birth_year -> Integer,
+        person_id -> Nullable<Integer>,
+        registered_at -> Nullable<Timestamp>,
+        payment_status -> Text,
+        paid_amount -> Nullable<Integer>,
+        paid_at -> Nullable<Timestamp>,
+        deleted_at -> Nullable<Timestamp>,
     }
 }
 
@@ -41,19 +112,64 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    persons (id) {
+        id -> Integer,
+        first_name -> Text,
+        last_name -> Text,
+        birth_year -> Integer,
+        club -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     races (id) {
         id -> Integer,
         name -> Text,
         competition_id -> Integer,
+        entry_fee -> Integer,
+        late_entry_fee -> Nullable<Integer>,
+        late_entry_from -> Nullable<Date>,
     }
 }
 
 diesel::table! {
+    recovery_codes (id) {
+        id -> Integer,
+        user_id -> Integer,
+        code_hash -> Text,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    results (participant_id) {
+        participant_id -> Integer,
+        finish_time -> Integer,
+    }
+}
+
+diesel::table! {
+    series (id) {
+        id -> Integer,
+        name -> Text,
+        description -> Text,
+        points_per_rank -> Text,
+        best_n -> Integer,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::database::sql_types::TimestampWithTimeZone;
+
     session_records (id) {
         id -> Binary,
         data -> Text,
-        expiry_date -> TimestamptzSqlite,
+        expiry_date -> TimestampWithTimeZone,
+        public_id -> Text,
+        user_id -> Nullable<Integer>,
+        created_at -> Timestamp,
     }
 }
 
@@ -63,6 +179,7 @@ diesel::table! {
         short_name -> Text,
         name -> Text,
         race_id -> Integer,
+        surcharge -> Integer,
     }
 }
 
@@ -80,23 +197,45 @@ diesel::table! {
         id -> Integer,
         name -> Text,
         password -> Text,
+        totp_secret -> Nullable<Text>,
+        totp_last_used_step -> Nullable<BigInt>,
     }
 }
 
+diesel::joinable!(api_tokens -> users (user_id));
+diesel::joinable!(audit_log -> users (user_id));
 diesel::joinable!(categories -> starts (start_id));
+diesel::joinable!(certificate_templates -> competitions (competition_id));
+diesel::joinable!(competition_branding -> competitions (competition_id));
+diesel::joinable!(competitions_in_series -> competitions (competition_id));
+diesel::joinable!(competitions_in_series -> series (series_id));
 diesel::joinable!(participants -> categories (category_id));
+diesel::joinable!(participants -> persons (person_id));
 diesel::joinable!(participants_in_special_category -> participants (participant_id));
 diesel::joinable!(participants_in_special_category -> special_categories (special_category_id));
 diesel::joinable!(races -> competitions (competition_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(results -> participants (participant_id));
+diesel::joinable!(session_records -> users (user_id));
 diesel::joinable!(special_categories -> races (race_id));
 diesel::joinable!(starts -> races (race_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_tokens,
+    audit_log,
     categories,
+    certificate_templates,
+    competition_branding,
     competitions,
+    competitions_in_series,
+    login_throttles,
     participants,
     participants_in_special_category,
+    persons,
     races,
+    recovery_codes,
+    results,
+    series,
     session_records,
     special_categories,
     starts,
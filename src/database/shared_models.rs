@@ -1,6 +1,6 @@
 use super::Id;
 use crate::database::schema::{
-    competitions, participants, participants_in_special_category, races, special_categories,
+    competitions, participants, participants_in_special_category, races, series, special_categories,
 };
 use diesel::prelude::*;
 use serde::Serialize;
@@ -56,7 +56,40 @@ pub struct SpecialCategoryPerParticipant {
     participant_id: Id,
 }
 
-fn ymd_date<S>(d: &time::Date, ser: S) -> Result<S::Ok, S::Error>
+#[derive(Queryable, Selectable, Serialize, Debug, Identifiable)]
+#[diesel(table_name = series)]
+pub struct Series {
+    pub id: Id,
+    pub name: String,
+    pub description: String,
+    /// Comma separated list of points for rank 1, 2, 3, …
+    pub points_per_rank: String,
+    /// Number of results counted for the standings
+    pub best_n: i32,
+}
+
+impl Series {
+    /// Points awarded for rank 1, 2, 3, …
+    ///
+    /// The scheme is validated while storing a series, so an invalid
+    /// scheme is treated as if no points are awarded at all
+    pub fn points_scheme(&self) -> Vec<i32> {
+        parse_points_scheme(&self.points_per_rank).unwrap_or_default()
+    }
+}
+
+/// Parse a comma separated points scheme like `25, 20, 16`
+pub fn parse_points_scheme(input: &str) -> Result<Vec<i32>, std::num::ParseIntError> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Serialize a date as `YYYY-MM-DD`
+pub(crate) fn ymd_date<S>(d: &time::Date, ser: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
@@ -1,15 +1,13 @@
 use std::collections::HashMap;
 
-use argon2::password_hash::SaltString;
-use argon2::Argon2;
-use argon2::PasswordHasher;
 use time::Date;
 
 use crate::database::schema::{
     categories, competitions, participants, participants_in_special_category, races,
     special_categories, starts, users,
 };
-use crate::database::Id;
+use crate::database::{DbConnection, Id};
+use diesel::connection::DefaultLoadingMode;
 use diesel::prelude::*;
 
 #[derive(diesel::Insertable, Clone)]
@@ -64,7 +62,7 @@ impl NewCategory {
 }
 
 /// create a set of test data to see something in the application
-pub(crate) fn insert_test_data(conn: &mut SqliteConnection) -> QueryResult<()> {
+pub(crate) fn insert_test_data(conn: &mut DbConnection) -> QueryResult<()> {
     conn.transaction(|conn| {
         let competition_count = competitions::table.count().get_result::<i64>(conn)?;
         if competition_count != 0 {
@@ -118,7 +116,7 @@ pub(crate) fn insert_test_data(conn: &mut SqliteConnection) -> QueryResult<()> {
             .order_by(races::id.desc())
             .limit(inserted_races as i64)
             .select((races::name, races::id))
-            .load_iter(conn)?
+            .load_iter::<_, DefaultLoadingMode>(conn)?
             .collect::<QueryResult<HashMap<String, Id>>>()?;
 
         let inserted_starts = diesel::insert_into(starts::table)
@@ -169,7 +167,7 @@ pub(crate) fn insert_test_data(conn: &mut SqliteConnection) -> QueryResult<()> {
             .order_by(starts::id.desc())
             .limit(inserted_starts as i64)
             .select((starts::name, starts::id))
-            .load_iter(conn)?
+            .load_iter::<_, DefaultLoadingMode>(conn)?
             .collect::<QueryResult<HashMap<String, Id>>>()?;
 
         let categories_400m = NewCategory::clone_for_femal([
@@ -287,6 +285,8 @@ pub(crate) fn insert_test_data(conn: &mut SqliteConnection) -> QueryResult<()> {
                 )
             ]).execute(conn)?;
 
+        crate::admin::persons::link_unmatched_participants(conn)?;
+
         let johns_id = participants::table.filter(participants::first_name.eq("John")).select(participants::id).first::<Id>(conn)?;
 
         diesel::insert_into(participants_in_special_category::table)
@@ -296,17 +296,14 @@ pub(crate) fn insert_test_data(conn: &mut SqliteConnection) -> QueryResult<()> {
             ))
             .execute(conn)?;
 
-        let password = "admin";
-        let salt = SaltString::generate(&mut rand::rngs::OsRng);
-        let password_hash = Argon2::default()
-            .hash_password(password.as_bytes(), &salt)
+        let password_hash = crate::admin::user::password::hash_password("admin")
             .expect("We know that we can hash this password");
 
         println!("Created user `admin` with password `admin`, go to /admin/login.html to access the admin area");
         diesel::insert_into(users::table)
             .values((
                 users::name.eq("admin"),
-                users::password.eq(password_hash.to_string()),
+                users::password.eq(password_hash),
             ))
             .execute(conn)
     })?;
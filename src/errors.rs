@@ -30,6 +30,18 @@ pub enum Error {
     NotFound(String),
     #[error("Received invalid input: {0}")]
     InvalidInput(String),
+    #[error("Session Error: {0}")]
+    SessionError(#[from] axum_login::tower_sessions::session::Error),
+    #[error("Missing or invalid CSRF token")]
+    InvalidCsrfToken,
+    #[error("Missing, invalid or expired API token")]
+    InvalidApiToken,
+    #[error("The API token does not allow this request")]
+    InsufficientScope,
+    #[error("Cannot create QR code: {0}")]
+    QrCodeError(#[from] qrcode::types::QrError),
+    #[error("Cannot render metrics: {0}")]
+    MetricsError(#[from] prometheus::Error),
 }
 
 impl From<deadpool_diesel::InteractError> for Error {
@@ -38,6 +50,15 @@ impl From<deadpool_diesel::InteractError> for Error {
     }
 }
 
+impl From<axum_login::Error<crate::admin::user::auth_session::LoginBackend>> for Error {
+    fn from(value: axum_login::Error<crate::admin::user::auth_session::LoginBackend>) -> Self {
+        match value {
+            axum_login::Error::Session(e) => Self::SessionError(e),
+            axum_login::Error::Backend(e) => e,
+        }
+    }
+}
+
 impl From<argon2::password_hash::Error> for Error {
     fn from(_value: argon2::password_hash::Error) -> Self {
         Self::HashError
@@ -57,10 +78,15 @@ impl IntoResponse for Error {
                 StatusCode::NOT_FOUND
             }
             Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            Error::InvalidCsrfToken | Error::InsufficientScope => StatusCode::FORBIDDEN,
+            Error::InvalidApiToken => StatusCode::UNAUTHORIZED,
             Error::PoolInteractError(_)
             | Error::DieselError(_)
             | Error::PoolError(_)
             | Error::HashError
+            | Error::SessionError(_)
+            | Error::QrCodeError(_)
+            | Error::MetricsError(_)
             | Error::TemplateError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
         let mut resp = Json(ErrorResponse {
@@ -0,0 +1,141 @@
+//! Health and readiness endpoints for reverse proxies and service managers
+//!
+//! `/healthz` only reports that the process is alive and answers requests.
+//! `/readyz` additionally checks everything required to serve pages: a
+//! database connection, an up to date schema and loadable templates,
+//! including those of the template directory. Both answer with a JSON report,
+//! `/readyz` with status 503 if any component fails.
+use crate::app_state;
+use crate::database::maintenance::MIGRATIONS;
+use crate::templates;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+use diesel_migrations::MigrationHarness;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How long the database checks may take before the database is considered unavailable
+const DATABASE_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Error,
+}
+
+#[derive(Serialize, Debug)]
+struct ComponentStatus {
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<Result<(), String>> for ComponentStatus {
+    fn from(value: Result<(), String>) -> Self {
+        match value {
+            Ok(()) => Self {
+                status: Status::Ok,
+                error: None,
+            },
+            Err(error) => Self {
+                status: Status::Error,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct HealthReport {
+    status: Status,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    components: BTreeMap<&'static str, ComponentStatus>,
+}
+
+impl IntoResponse for HealthReport {
+    fn into_response(self) -> Response {
+        let status = match self.status {
+            Status::Ok => StatusCode::OK,
+            Status::Error => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// The process is alive
+#[axum::debug_handler]
+pub(crate) async fn healthz() -> Response {
+    HealthReport {
+        status: Status::Ok,
+        components: BTreeMap::new(),
+    }
+    .into_response()
+}
+
+/// The application is able to serve requests
+#[axum::debug_handler]
+pub(crate) async fn readyz(state: State<app_state::State>) -> Response {
+    let (database, migrations) =
+        match tokio::time::timeout(DATABASE_CHECK_TIMEOUT, check_database(&state)).await {
+            Ok(Ok(checks)) => checks,
+            Ok(Err(error)) => (Err(error.clone()), Err(error)),
+            Err(_) => {
+                let error = String::from("Timed out waiting for a database connection");
+                (Err(error.clone()), Err(error))
+            }
+        };
+    let components = BTreeMap::from([
+        ("database", database.into()),
+        ("migrations", migrations.into()),
+        ("templates", check_templates(&state).into()),
+    ]);
+    let status = if components
+        .values()
+        .all(|c: &ComponentStatus| c.status == Status::Ok)
+    {
+        Status::Ok
+    } else {
+        tracing::warn!(?components, "Readiness check failed");
+        Status::Error
+    };
+    HealthReport { status, components }.into_response()
+}
+
+/// Run `SELECT 1` and check for pending migrations on a pooled connection
+async fn check_database(
+    state: &app_state::State,
+) -> Result<(Result<(), String>, Result<(), String>), String> {
+    let conn = state.pool.get().await.map_err(|e| e.to_string())?;
+    conn.interact(|conn| {
+        let database = diesel::select(1.into_sql::<Integer>())
+            .get_result::<i32>(conn)
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+        let migrations = match conn.has_pending_migration(MIGRATIONS) {
+            Ok(false) => Ok(()),
+            Ok(true) => Err(String::from("There are pending migrations")),
+            Err(e) => Err(e.to_string()),
+        };
+        (database, migrations)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Load and compile all templates, including those of the template directory
+fn check_templates(state: &app_state::State) -> Result<(), String> {
+    let names = templates::names(state.template_dir.as_deref())
+        .map_err(|e| format!("Cannot read the template directory: {e}"))?;
+    let env = state.template_env();
+    for name in names {
+        env.get_template(&name)
+            .map_err(|e| format!("Cannot load template `{name}`: {e}"))?;
+    }
+    Ok(())
+}
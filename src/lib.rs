@@ -2,30 +2,33 @@
 // are supposed to be replaced by workshop participants
 #![allow(unreachable_code, unused_variables, dead_code)]
 use admin::user::auth_session::LoginBackend;
-use admin::user::sqlite_session_store::SqliteSessionStore;
-use axum::http::header::CONTENT_TYPE;
-use axum::http::HeaderValue;
-use axum::response::{IntoResponse, Response};
+use admin::user::session_store::{DatabaseSessionStore, AUTH_DATA_KEY};
 use axum::Router;
 use axum_login::tower_sessions::SessionManagerLayer;
 use axum_login::AuthManagerLayerBuilder;
 use diesel_migrations::MigrationHarness;
 use service_config::Config;
+use tower_http::compression::CompressionLayer;
 use tower_http::trace::TraceLayer;
 
 pub mod admin;
 pub mod app_state;
+mod assets;
+mod branding;
 mod competition_overview;
 pub mod database;
 pub mod errors;
+mod health;
+pub mod metrics;
+mod pdf;
 mod registration;
 mod registration_list;
+mod series_standings;
 pub mod service_config;
+mod templates;
 
 mod axum_ext;
 
-const MIGRATIONS: diesel_migrations::EmbeddedMigrations = diesel_migrations::embed_migrations!();
-
 pub async fn setup(config: Config) -> (Router, app_state::State) {
     let base_url = config.base_url.clone();
     let state = app_state::State::from_config(&config);
@@ -35,10 +38,13 @@ pub async fn setup(config: Config) -> (Router, app_state::State) {
         .get()
         .await
         .expect("Failed to get a connection from the pool");
-    conn.interact(|conn| conn.run_pending_migrations(MIGRATIONS).map(|_| ()))
-        .await
-        .expect("Failed to run migrations")
-        .expect("Failed to run migrations");
+    conn.interact(|conn| {
+        conn.run_pending_migrations(database::maintenance::MIGRATIONS)
+            .map(|_| ())
+    })
+    .await
+    .expect("Failed to run migrations")
+    .expect("Failed to run migrations");
 
     if config.insert_test_data {
         conn.interact(database::test_data::insert_test_data)
@@ -47,22 +53,44 @@ pub async fn setup(config: Config) -> (Router, app_state::State) {
             .expect("Failed to insert test data");
     }
     // Session layer.
-    let session_store = SqliteSessionStore::new(state.pool.clone());
+    let session_store = DatabaseSessionStore::new(state.pool.clone());
+    if config.session_cleanup_interval_secs > 0 {
+        tokio::task::spawn(
+            session_store
+                .clone()
+                .run_cleanup_task(std::time::Duration::from_secs(
+                    config.session_cleanup_interval_secs,
+                )),
+        );
+    }
     let session_layer = SessionManagerLayer::new(session_store);
+    #[cfg(feature = "sqlite")]
+    if let Some(settings) = config.backup_settings() {
+        tokio::task::spawn(database::backup::run_backup_task(
+            state.pool.clone(),
+            settings,
+        ));
+    }
 
     // Auth service.
     let backend = LoginBackend::new(state.pool.clone());
-    let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
+    let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer)
+        .with_data_key(AUTH_DATA_KEY)
+        .build();
 
     let router = Router::new()
-        .route("/assets/simple.min.css", axum::routing::get(get_simple_css))
-        .route("/assets/custom.css", axum::routing::get(get_custom_css))
+        .route("/metrics", axum::routing::get(metrics::render))
+        .route("/healthz", axum::routing::get(health::healthz))
+        .route("/readyz", axum::routing::get(health::readyz))
         .route(
             "/index.html",
             axum::routing::get(self::competition_overview::render),
         )
+        .merge(assets::routes())
+        .merge(branding::routes())
         .merge(registration::routes())
         .merge(registration_list::routes())
+        .merge(series_standings::routes())
         .nest("/admin", admin::routes());
     let router = if base_url.is_empty() {
         router
@@ -71,21 +99,35 @@ pub async fn setup(config: Config) -> (Router, app_state::State) {
     };
     let router = router
         .layer(auth_layer)
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track_requests,
+        ))
         .with_state(state.clone())
+        // gzip or brotli, depending on the `Accept-Encoding` of the request
+        .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http());
     (router, state)
 }
 
-async fn get_simple_css() -> Response {
-    let mut resp = include_str!("../assets/simple.min.css").into_response();
-    resp.headers_mut()
-        .insert(CONTENT_TYPE, HeaderValue::from_static("text/css"));
-    resp
-}
+/// Release the resources of the application after the server stopped
+///
+/// With SQLite the WAL file is written back into the database and truncated
+/// first, so no large WAL file is left behind
+pub async fn shutdown(state: app_state::State) {
+    #[cfg(feature = "sqlite")]
+    {
+        use diesel::RunQueryDsl;
 
-async fn get_custom_css() -> Response {
-    let mut resp = include_str!("../assets/custom.css").into_response();
-    resp.headers_mut()
-        .insert(CONTENT_TYPE, HeaderValue::from_static("text/css"));
-    resp
+        let checkpoint = state
+            .with_connection(|conn| {
+                diesel::sql_query("PRAGMA wal_checkpoint(TRUNCATE);").execute(conn)
+            })
+            .await;
+        match checkpoint {
+            Ok(_) => tracing::info!("Checkpointed the WAL file"),
+            Err(e) => tracing::error!(error = %e, "Failed to checkpoint the WAL file"),
+        }
+    }
+    state.pool.close();
 }
@@ -1,17 +1,90 @@
 use clap::Parser;
-use race_timing::service_config::Config;
+use race_timing::database::maintenance;
+use race_timing::service_config::{Cli, Command, Config, ConfigCommand, DbCommand};
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::prelude::*;
 
 #[tokio::main]
-async fn main() {
-    let config = Config::parse();
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(2);
+        }
+    };
 
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config).await,
+        Command::Config(ConfigCommand::Check) => print!("{}", config.to_toml()),
+        Command::Db(command) => {
+            if let Err(e) = run_db_command(&config, command) {
+                eprintln!("Error: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_db_command(
+    config: &Config,
+    command: DbCommand,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // restoring replaces the database file, so it must not be opened before
+    let connect = || maintenance::establish(&config.database_url);
+    match command {
+        DbCommand::Migrate => {
+            let applied = maintenance::migrate(&mut connect()?)?;
+            if applied.is_empty() {
+                println!("No pending migrations");
+            }
+            for version in applied {
+                println!("Applied migration {version}");
+            }
+        }
+        DbCommand::Status => {
+            for migration in maintenance::status(&mut connect()?)? {
+                let marker = if migration.applied { "x" } else { " " };
+                println!("[{marker}] {}", migration.name);
+            }
+        }
+        DbCommand::Revert => {
+            let version = maintenance::revert(&mut connect()?)?;
+            println!("Reverted migration {version}");
+        }
+        DbCommand::Seed => maintenance::seed(&mut connect()?)?,
+        #[cfg(feature = "sqlite")]
+        DbCommand::Check => {
+            let problems = maintenance::check(&mut connect()?)?;
+            if !problems.is_empty() {
+                for problem in &problems {
+                    println!("{problem}");
+                }
+                return Err(format!("Found {} problems", problems.len()).into());
+            }
+            println!("No problems found");
+        }
+        #[cfg(feature = "sqlite")]
+        DbCommand::Restore { backup } => {
+            maintenance::restore(&backup, &config.database_url)?;
+            println!("Restored the database from `{}`", backup.display());
+        }
+    }
+    Ok(())
+}
+
+async fn serve(config: Config) {
     let subscriber = tracing_subscriber::Registry::default()
         .with(tracing_subscriber::fmt::layer())
         .with(tracing_subscriber::EnvFilter::from_default_env());
     tracing::subscriber::set_global_default(subscriber).expect("Failed to setup tracing");
 
-    let (router, _) = race_timing::setup(config.clone()).await;
+    let (router, state) = race_timing::setup(config.clone()).await;
 
     println!(
         "Starting server at http://{}:{}{}/index.html",
@@ -21,5 +94,54 @@ async fn main() {
         .await
         .expect("Failed to start server");
 
-    axum::serve(listener, router).await.expect("App crashed");
+    let shutdown_requested = Arc::new(tokio::sync::Notify::new());
+    let server = axum::serve(
+        listener,
+        // the client address is used to throttle failed login attempts
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown_requested = shutdown_requested.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown_requested.notify_one();
+        }
+    });
+    // open requests may take a while to finish, but not forever
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    let drain_deadline = async {
+        shutdown_requested.notified().await;
+        tokio::time::sleep(shutdown_timeout).await;
+    };
+    tokio::select! {
+        result = server => result.expect("App crashed"),
+        () = drain_deadline => {
+            tracing::warn!(?shutdown_timeout, "Open requests did not finish in time");
+        }
+    }
+    race_timing::shutdown(state).await;
+    tracing::info!("Shutdown complete");
+}
+
+/// Wait for SIGINT (Ctrl+C) or SIGTERM
+async fn shutdown_signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        () = interrupt => {},
+        () = terminate => {},
+    }
+    tracing::info!("Shutting down, waiting for open requests to finish");
 }
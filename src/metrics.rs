@@ -0,0 +1,202 @@
+//! Prometheus metrics exposed at `/metrics`
+//!
+//! All metrics live in a registry owned by the application state. HTTP
+//! requests and database queries are recorded as they happen, while the
+//! connection pool statistics and the registration counts are read on each
+//! scrape, so they are always up to date.
+use crate::app_state;
+use crate::database::schema::{categories, competitions, participants, races, starts};
+use crate::database::Id;
+use crate::errors::Result;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use diesel::prelude::*;
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::time::Instant;
+
+/// Prefix of all metric names
+const NAMESPACE: &str = "race_timing";
+/// Route label of requests that did not match any route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// All metrics of the application
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    /// recorded by the connection instrumentation, see `app_state`
+    pub(crate) query_duration: Histogram,
+    pool_max_size: IntGauge,
+    pool_size: IntGauge,
+    pool_available: IntGauge,
+    pool_waiting: IntGauge,
+    registrations: IntGaugeVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_owned()), None)
+            .expect("The namespace is a valid metric name");
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of handled HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .expect("Valid metric definition");
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time until the response of an HTTP request was ready",
+            ),
+            &["method", "route"],
+        )
+        .expect("Valid metric definition");
+        let query_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "db_query_duration_seconds",
+                "Execution time of database queries",
+            )
+            .buckets(vec![
+                0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5,
+                1.0,
+            ]),
+        )
+        .expect("Valid metric definition");
+        let gauge =
+            |name: &str, help: &str| IntGauge::new(name, help).expect("Valid metric definition");
+        let pool_max_size = gauge(
+            "db_pool_max_size",
+            "Maximum number of connections in the pool",
+        );
+        let pool_size = gauge("db_pool_size", "Number of open connections in the pool");
+        let pool_available = gauge(
+            "db_pool_available",
+            "Number of idle connections in the pool",
+        );
+        let pool_waiting = gauge(
+            "db_pool_waiting",
+            "Number of requests waiting for a connection",
+        );
+        let registrations = IntGaugeVec::new(
+            Opts::new(
+                "registrations",
+                "Number of registered participants per competition, without the trash",
+            ),
+            &["competition_id", "competition"],
+        )
+        .expect("Valid metric definition");
+
+        let metrics = Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            query_duration,
+            pool_max_size,
+            pool_size,
+            pool_available,
+            pool_waiting,
+            registrations,
+        };
+        metrics.register_all();
+        metrics
+    }
+
+    fn register_all(&self) {
+        let collectors: [Box<dyn prometheus::core::Collector>; 8] = [
+            Box::new(self.http_requests.clone()),
+            Box::new(self.http_request_duration.clone()),
+            Box::new(self.query_duration.clone()),
+            Box::new(self.pool_max_size.clone()),
+            Box::new(self.pool_size.clone()),
+            Box::new(self.pool_available.clone()),
+            Box::new(self.pool_waiting.clone()),
+            Box::new(self.registrations.clone()),
+        ];
+        for collector in collectors {
+            self.registry
+                .register(collector)
+                .expect("Each metric is only registered once");
+        }
+    }
+}
+
+/// Middleware recording count and duration of all requests
+///
+/// Requests are labeled with the route pattern instead of the actual path,
+/// e.g. `/admin/competitions/:competition_id/races.html`, to keep the
+/// number of label values bounded
+pub(crate) async fn track_requests(
+    state: State<app_state::State>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+        .to_owned();
+    let method = request.method().clone();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let metrics = &state.metrics;
+    metrics
+        .http_request_duration
+        .with_label_values(&[method.as_str(), &route])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .with_label_values(&[method.as_str(), &route, response.status().as_str()])
+        .inc();
+    response
+}
+
+/// Render all metrics in the Prometheus text format
+#[axum::debug_handler]
+pub(crate) async fn render(state: State<app_state::State>) -> Result<Response> {
+    let metrics = &state.metrics;
+    let pool_status = state.pool.status();
+    metrics.pool_max_size.set(pool_status.max_size as i64);
+    metrics.pool_size.set(pool_status.size as i64);
+    metrics.pool_available.set(pool_status.available as i64);
+    metrics.pool_waiting.set(pool_status.waiting as i64);
+
+    let registrations = state
+        .with_connection(|conn| {
+            participants::table
+                .inner_join(categories::table.inner_join(
+                    starts::table.inner_join(races::table.inner_join(competitions::table)),
+                ))
+                .filter(participants::deleted_at.is_null())
+                .filter(competitions::deleted_at.is_null())
+                .group_by((competitions::id, competitions::name))
+                .select((
+                    competitions::id,
+                    competitions::name,
+                    diesel::dsl::count(participants::id),
+                ))
+                .load::<(Id, String, i64)>(conn)
+        })
+        .await?;
+    // competitions without registrations or in the trash are not reported
+    metrics.registrations.reset();
+    for (competition_id, name, count) in registrations {
+        metrics
+            .registrations
+            .with_label_values(&[&competition_id.to_string(), &name])
+            .set(count);
+    }
+
+    let body = TextEncoder::new().encode_to_string(&metrics.registry.gather())?;
+    Ok(([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response())
+}
@@ -0,0 +1,279 @@
+//! Minimal PDF generation helpers used for printable documents
+//!
+//! We only use the Helvetica base fonts here. These are shipped with every
+//! PDF reader, so we do not need to embed any font data. Text is written using
+//! the WinAnsi encoding, which covers the latin characters that commonly
+//! appear in names and clubs (including umlauts)
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use std::borrow::Cow;
+
+/// Width of an A4 page in points
+pub(crate) const A4_WIDTH: f32 = 595.0;
+/// Height of an A4 page in points
+pub(crate) const A4_HEIGHT: f32 = 842.0;
+/// Margin used on all sides of a page
+pub(crate) const MARGIN: f32 = 50.0;
+
+const REGULAR_FONT: Name<'static> = Name(b"F1");
+const BOLD_FONT: Name<'static> = Name(b"F2");
+
+/// Font variants that can be used to render text
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource_name(self) -> Name<'static> {
+        match self {
+            Font::Regular => REGULAR_FONT,
+            Font::Bold => BOLD_FONT,
+        }
+    }
+}
+
+/// A simple document builder consisting of a list of A4 pages
+///
+/// All coordinates are measured in points from the bottom left corner
+/// of the current page
+#[derive(Default)]
+pub(crate) struct PdfDocument {
+    pages: Vec<Content>,
+}
+
+impl PdfDocument {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a new page, all following drawing operations go to this page
+    pub(crate) fn new_page(&mut self) {
+        self.pages.push(Content::new());
+    }
+
+    fn current_page(&mut self) -> &mut Content {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        self.pages.last_mut().expect("We pushed a page above")
+    }
+
+    /// Write a single line of text starting at the given position
+    pub(crate) fn text(&mut self, x: f32, y: f32, font: Font, size: f32, text: &str) {
+        let encoded = encode_win_ansi(text);
+        self.current_page()
+            .begin_text()
+            .set_font(font.resource_name(), size)
+            .next_line(x, y)
+            .show(Str(&encoded))
+            .end_text();
+    }
+
+    /// Write a single line of text that does not exceed the given width
+    ///
+    /// Longer text is shortened with an ellipsis. As the width of the text is
+    /// only estimated, anything beyond the width is clipped in addition
+    pub(crate) fn text_with_max_width(
+        &mut self,
+        x: f32,
+        y: f32,
+        max_width: f32,
+        font: Font,
+        size: f32,
+        text: &str,
+    ) {
+        let encoded = encode_win_ansi(&truncate_to_width(text, size, max_width));
+        self.current_page()
+            .save_state()
+            // leave room for descenders and accents above and below the baseline
+            .rect(x, y - size, max_width, 3.0 * size)
+            .clip_nonzero()
+            .end_path()
+            .begin_text()
+            .set_font(font.resource_name(), size)
+            .next_line(x, y)
+            .show(Str(&encoded))
+            .end_text()
+            .restore_state();
+    }
+
+    /// Write a single line of text horizontally centered on the page
+    pub(crate) fn centered_text(&mut self, y: f32, font: Font, size: f32, text: &str) {
+        let x = (A4_WIDTH - approximate_text_width(text, size)) / 2.0;
+        self.text(x.max(MARGIN), y, font, size, text);
+    }
+
+    /// Draw a straight line between two points
+    pub(crate) fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.current_page()
+            .set_line_width(0.5)
+            .move_to(x1, y1)
+            .line_to(x2, y2)
+            .stroke();
+    }
+
+    /// Write the final PDF file
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let regular_font_id = Ref::new(3);
+        let bold_font_id = Ref::new(4);
+        // each page requires two objects: The page itself and the content stream
+        let page_ids = (0..self.pages.len())
+            .map(|idx| Ref::new(5 + 2 * idx as i32))
+            .collect::<Vec<_>>();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(page_tree_id);
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().copied())
+            .count(page_ids.len() as i32);
+        pdf.type1_font(regular_font_id)
+            .base_font(Name(b"Helvetica"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.type1_font(bold_font_id)
+            .base_font(Name(b"Helvetica-Bold"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+        for (page_id, content) in page_ids.into_iter().zip(self.pages) {
+            let content_id = page_id.next();
+            let mut page = pdf.page(page_id);
+            page.media_box(Rect::new(0.0, 0.0, A4_WIDTH, A4_HEIGHT))
+                .parent(page_tree_id)
+                .contents(content_id);
+            let mut resources = page.resources();
+            resources
+                .fonts()
+                .pair(REGULAR_FONT, regular_font_id)
+                .pair(BOLD_FONT, bold_font_id);
+            resources.finish();
+            page.finish();
+            pdf.stream(content_id, &content.finish());
+        }
+        pdf.finish()
+    }
+}
+
+/// A table spanning potentially multiple pages
+///
+/// The title lines and the header row are repeated on each page
+pub(crate) struct Table<'a> {
+    /// Lines printed above the table, the first one is printed as headline
+    pub(crate) title: Vec<String>,
+    /// Column headers together with the relative width of each column
+    pub(crate) columns: Vec<(String, f32)>,
+    /// Actual table content, each row is expected to have one entry per column
+    pub(crate) rows: &'a [Vec<String>],
+}
+
+impl Table<'_> {
+    const FONT_SIZE: f32 = 10.0;
+    const ROW_HEIGHT: f32 = 16.0;
+    /// Space kept free between the text of neighbouring columns
+    const CELL_PADDING: f32 = 4.0;
+
+    /// Render the table into a new PDF document
+    pub(crate) fn render(&self) -> Vec<u8> {
+        let mut doc = PdfDocument::new();
+        let total_width = self.columns.iter().map(|(_, w)| w).sum::<f32>();
+        let scale = (A4_WIDTH - 2.0 * MARGIN) / total_width;
+        // offset and available text width of each column
+        let columns = self
+            .columns
+            .iter()
+            .scan(MARGIN, |x, (_, w)| {
+                let current = *x;
+                *x += w * scale;
+                Some((current, w * scale - Self::CELL_PADDING))
+            })
+            .collect::<Vec<_>>();
+
+        let mut rows = self.rows.iter().peekable();
+        // render at least one page, even if there are no rows
+        let mut first_page = true;
+        while first_page || rows.peek().is_some() {
+            first_page = false;
+            doc.new_page();
+            let mut y = A4_HEIGHT - MARGIN;
+            for (idx, line) in self.title.iter().enumerate() {
+                if idx == 0 {
+                    doc.text(MARGIN, y, Font::Bold, 16.0, line);
+                    y -= 22.0;
+                } else {
+                    doc.text(MARGIN, y, Font::Regular, 11.0, line);
+                    y -= 15.0;
+                }
+            }
+            y -= 10.0;
+            for ((header, _), (x, width)) in self.columns.iter().zip(&columns) {
+                doc.text_with_max_width(*x, y, *width, Font::Bold, Self::FONT_SIZE, header);
+            }
+            doc.line(MARGIN, y - 4.0, A4_WIDTH - MARGIN, y - 4.0);
+            y -= Self::ROW_HEIGHT;
+
+            while y > MARGIN {
+                let Some(row) = rows.next() else {
+                    break;
+                };
+                for (cell, (x, width)) in row.iter().zip(&columns) {
+                    doc.text_with_max_width(*x, y, *width, Font::Regular, Self::FONT_SIZE, cell);
+                }
+                y -= Self::ROW_HEIGHT;
+            }
+        }
+        doc.finish()
+    }
+}
+
+/// Rough estimation of the rendered width of a text in Helvetica
+///
+/// Helvetica's glyphs are on average about half as wide as the font size
+pub(crate) fn approximate_text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * 0.5
+}
+
+/// Shorten a text with an ellipsis, so that its estimated width fits into `max_width`
+fn truncate_to_width(text: &str, size: f32, max_width: f32) -> Cow<'_, str> {
+    if approximate_text_width(text, size) <= max_width {
+        return Cow::Borrowed(text);
+    }
+    let available = max_width - approximate_text_width("…", size);
+    let mut truncated = String::new();
+    let mut width = 0.0;
+    for c in text.chars() {
+        width += approximate_text_width(c.encode_utf8(&mut [0; 4]), size);
+        if width > available {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.truncate(truncated.trim_end().len());
+    truncated.push('…');
+    Cow::Owned(truncated)
+}
+
+/// Translate a string into the WinAnsi encoding used by the base fonts
+///
+/// Characters that cannot be represented are replaced by `?`
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{A0}'..='\u{FF}' => c as u8,
+            '€' => 0x80,
+            '‚' => 0x82,
+            '„' => 0x84,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect()
+}
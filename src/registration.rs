@@ -1,11 +1,12 @@
 //! Routes for handling the registration of a new participant
+use crate::admin::payments::{self, PaymentInstructions};
 use crate::app_state::{self, AppState};
-use crate::database::schema::{categories, participants, races};
+use crate::database::schema::{categories, competitions, participants, races};
 use crate::database::shared_models::{Competition, Race, SpecialCategories};
-use crate::database::Id;
+use crate::database::{DbConnection, Id};
 use crate::errors::{Error, Result};
 use axum::extract::Path;
-use axum::response::{Html, Redirect};
+use axum::response::Html;
 use axum::Router;
 use diesel::associations::HasTable;
 use diesel::prelude::*;
@@ -170,7 +171,9 @@ where
 
 impl RegistrationForm {
     /// Are the provided registration form data valid
-    fn is_valid(&self) -> Result<()> {
+    ///
+    /// Has to be checked before calling `into_database`
+    pub fn is_valid(&self) -> Result<()> {
         if !self.new_participant.consent {
             tracing::debug!(?self);
             Err(Error::InvalidInput(String::from(
@@ -185,14 +188,36 @@ impl RegistrationForm {
     /// Insert the registration form data into the database
     ///
     /// If a `participant_id` is provided we need to handle an update
-    /// otherwise it's an insert of existing data
-    pub async fn into_database(
+    /// otherwise it's an insert of existing data. Callers run this inside a
+    /// transaction, so the admin pages can record the change in the audit log
+    /// together with it.
+    ///
+    /// New participants are linked to a returning runner with the same name
+    /// and birth year, or to a new person. Existing participants keep their
+    /// person, as it might have been linked manually on the admin pages.
+    ///
+    /// Returns the id of the inserted or updated participant
+    pub fn into_database(
+        self,
+        conn: &mut DbConnection,
+        competition_id: Id,
+        participant_id: Option<Id>,
+    ) -> QueryResult<Id> {
+        let is_new = participant_id.is_none();
+        let participant_id = self.store(conn, competition_id, participant_id)?;
+        if is_new {
+            crate::admin::persons::link_to_person(conn, participant_id)?;
+        }
+        Ok(participant_id)
+    }
+
+    /// Insert or update the participant and its special categories
+    fn store(
         self,
-        state: &AppState,
+        conn: &mut DbConnection,
         competition_id: Id,
         participant_id: Option<Id>,
-    ) -> Result<()> {
-        self.is_valid()?;
+    ) -> QueryResult<Id> {
         let age = time::OffsetDateTime::now_utc().year() - self.new_participant.age;
         let special_categories_id = self.special_categories.keys().copied().collect::<Vec<_>>();
 
@@ -204,6 +229,7 @@ impl RegistrationForm {
         //    + Resolve Race id + birth year to relevat category
         //    + Resolve special categories by id (verify that they exist)
         // 2. Insert participant
+        //    (set `registered_at` to the current time, it determines the entry fee)
         // 3. Insert special category mapping
 
         todo!("Insert the new participant into the database")
@@ -212,7 +238,7 @@ impl RegistrationForm {
 
 /// Load data relevant for the registration form for a certain competition
 fn load_competition_data(
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
     path: Id,
 ) -> QueryResult<Option<(Competition, Vec<RaceWithSpecialCategory>)>> {
     // to render the registration page we need to have various information
@@ -251,32 +277,73 @@ pub async fn render_registration_page_with_optional_data(
     let min_age = races.iter().map(|r| r.race.min_age).max();
     let max_age = races.iter().map(|r| r.race.max_age).min();
     let params = HashMap::from([("competition", &competition.name as &str)]);
-    state.render_template(
-        "registration.html",
-        RegistrationPageData {
-            race_data: races,
-            min_age,
-            max_age,
-            participant,
-            head_title: state.translation(&format!("short_{title}")),
-            title: state.translation_with_params(title, params),
-            event: competition,
-            target_uri,
-        },
-    )
+    let competition_id = competition.id;
+    let data = RegistrationPageData {
+        race_data: races,
+        min_age,
+        max_age,
+        participant,
+        head_title: state.translation(&format!("short_{title}")),
+        title: state.translation_with_params(title, params),
+        event: competition,
+        target_uri,
+    };
+    state
+        .render_competition_template("registration.html", competition_id, data)
+        .await
+}
+
+/// Data used to confirm a registration
+///
+/// see `templates/registration_confirmation.html` for the template
+#[derive(Serialize)]
+struct RegistrationConfirmationData {
+    competition_id: Id,
+    competition_name: String,
+    first_name: String,
+    last_name: String,
+    /// reference code and amount for the bank transfer, if there is anything to pay
+    payment: Option<PaymentInstructions>,
 }
 
 /// Handle adding a new participant
+///
+/// The confirmation page tells the participant how to pay the entry fee
 #[axum::debug_handler(state = app_state::State)]
 async fn add_participant(
     state: AppState,
     event_id: Path<Id>,
     form_data: axum::extract::Form<RegistrationForm>,
-) -> Result<Redirect> {
-    form_data.0.into_database(&state, event_id.0, None).await?;
-    let base_url = state.base_url();
-    Ok(Redirect::to(&format!(
-        "{base_url}/{}/registration_list.html",
-        event_id.0
-    )))
+) -> Result<Html<String>> {
+    let form = form_data.0;
+    form.is_valid()?;
+    let competition_id = event_id.0;
+    let first_name = form.new_participant.firstname.clone();
+    let last_name = form.new_participant.lastname.clone();
+    let (competition_name, payment) = state
+        .with_connection(move |conn| {
+            conn.transaction(|conn| {
+                let participant_id = form.into_database(conn, competition_id, None)?;
+                let competition_name = competitions::table
+                    .find(competition_id)
+                    .select(competitions::name)
+                    .first::<String>(conn)?;
+                let payment = payments::payment_instructions(conn, competition_id, participant_id)?;
+                QueryResult::Ok((competition_name, payment))
+            })
+        })
+        .await?;
+    state
+        .render_competition_template(
+            "registration_confirmation.html",
+            competition_id,
+            RegistrationConfirmationData {
+                competition_id,
+                competition_name,
+                first_name,
+                last_name,
+                payment,
+            },
+        )
+        .await
 }
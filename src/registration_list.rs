@@ -4,27 +4,28 @@ use crate::database::schema::{categories, participants, races, starts};
 use crate::database::shared_models::{
     Competition, Race, SpecialCategories, SpecialCategoryPerParticipant,
 };
-use crate::database::Id;
+use crate::database::{DbBackend, Id};
 use crate::errors::{Error, Result};
 use axum::extract::Path;
 use axum::response::Html;
 use axum::Router;
 use diesel::prelude::*;
-use diesel::sqlite::Sqlite;
 use serde::Serialize;
 use time::PrimitiveDateTime;
 
 pub fn routes() -> Router<app_state::State> {
-    Router::new().route(
-        "/:event_id/registration_list.html",
-        axum::routing::get(render_registration_list),
-    )
+    Router::new()
+        .route(
+            "/:event_id/registration_list.html",
+            axum::routing::get(render_registration_list),
+        )
+        .route_layer(axum::middleware::from_fn(crate::assets::etag_page))
 }
 
 /// Data for a specific participants
 #[derive(Queryable, Selectable, Debug, serde::Serialize, Identifiable)]
 #[diesel(table_name = participants)]
-#[diesel(check_for_backend(Sqlite))]
+#[diesel(check_for_backend(DbBackend))]
 pub struct ParticipantEntry {
     /// id of the participant
     #[serde(skip)]
@@ -98,7 +99,7 @@ async fn render_registration_list(state: AppState, event_id: Path<Id>) -> Result
         Vec<Race>,
         Vec<Vec<SpecialCategories>>,
         Vec<Vec<SpecialCategoryPerParticipant>>,
-    ) = todo!("Load all relevant participant data");
+    ) = todo!("Load all relevant participant data (skip participants in the trash)");
     let competition_info = competition_info
         .ok_or_else(|| Error::NotFound(format!("No competition for id {} found", event_id)))?;
 
@@ -141,11 +142,14 @@ async fn render_registration_list(state: AppState, event_id: Path<Id>) -> Result
         })
         .collect::<Vec<_>>();
 
-    state.render_template(
-        "registration_list.html",
-        RegistrationListData {
-            race_map,
-            competition_info,
-        },
-    )
+    state
+        .render_competition_template(
+            "registration_list.html",
+            competition_info.id,
+            RegistrationListData {
+                race_map,
+                competition_info,
+            },
+        )
+        .await
 }
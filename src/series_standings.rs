@@ -0,0 +1,236 @@
+//! Render the standings of a series of competitions
+//!
+//! Participants are matched across competitions by their name and birth year.
+//! For each competition the participants earn points depending on their rank
+//! in their category. Only the best N results count for the final standings.
+use crate::app_state::{self, AppState};
+use crate::database::schema::{
+    categories, competitions, competitions_in_series, participants, races, results, series, starts,
+};
+use crate::database::shared_models::Series;
+use crate::database::{DbBackend, Id};
+use crate::errors::{Error, Result};
+use axum::extract::Path;
+use axum::response::Html;
+use axum::Router;
+use diesel::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+pub fn routes() -> Router<app_state::State> {
+    Router::new()
+        .route(
+            "/series/:series_id/standings.html",
+            axum::routing::get(render_series_standings),
+        )
+        .route_layer(axum::middleware::from_fn(crate::assets::etag_page))
+}
+
+/// A single result of a participant in one of the competitions of the series
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = participants)]
+#[diesel(check_for_backend(DbBackend))]
+struct SeriesResult {
+    first_name: String,
+    last_name: String,
+    club: Option<String>,
+    birth_year: i32,
+    #[diesel(select_expression = races::competition_id)]
+    competition_id: Id,
+    #[diesel(select_expression = categories::id)]
+    category_id: Id,
+    #[diesel(select_expression = categories::label)]
+    category: String,
+    #[diesel(select_expression = results::finish_time)]
+    finish_time: i32,
+}
+
+#[derive(Queryable, Serialize, Debug)]
+struct SeriesCompetition {
+    id: Id,
+    name: String,
+}
+
+/// Key used to identify a participant across competitions
+#[derive(Hash, PartialEq, Eq, Debug)]
+struct ParticipantIdentity {
+    last_name: String,
+    first_name: String,
+    birth_year: i32,
+}
+
+impl ParticipantIdentity {
+    fn new(result: &SeriesResult) -> Self {
+        Self {
+            last_name: result.last_name.trim().to_lowercase(),
+            first_name: result.first_name.trim().to_lowercase(),
+            birth_year: result.birth_year,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct StandingsEntry {
+    rank: usize,
+    first_name: String,
+    last_name: String,
+    club: Option<String>,
+    birth_year: i32,
+    /// points per competition, in the order of `SeriesStandingsData::competitions`
+    points: Vec<Option<i32>>,
+    total: i32,
+}
+
+#[derive(Serialize, Debug)]
+struct CategoryStandings {
+    category: String,
+    entries: Vec<StandingsEntry>,
+}
+
+/// Data used to render the series standings
+///
+/// See `templates/series_standings.html` for the relevant template
+#[derive(Serialize)]
+struct SeriesStandingsData {
+    series: Series,
+    competitions: Vec<SeriesCompetition>,
+    categories: Vec<CategoryStandings>,
+}
+
+#[axum::debug_handler(state = app_state::State)]
+async fn render_series_standings(state: AppState, series_id: Path<Id>) -> Result<Html<String>> {
+    let series_id = series_id.0;
+    let (series, competitions, results) = state
+        .with_connection(move |conn| {
+            let series = series::table
+                .find(series_id)
+                .select(Series::as_select())
+                .first(conn)
+                .optional()?;
+            let competitions = competitions::table
+                .inner_join(competitions_in_series::table)
+                .filter(competitions_in_series::series_id.eq(series_id))
+                .filter(competitions::deleted_at.is_null())
+                .order_by((competitions::date, competitions::id))
+                .select((competitions::id, competitions::name))
+                .load::<SeriesCompetition>(conn)?;
+            let results = participants::table
+                .inner_join(results::table)
+                .inner_join(categories::table.inner_join(starts::table.inner_join(races::table)))
+                .filter(
+                    races::competition_id.eq_any(
+                        competitions_in_series::table
+                            .filter(competitions_in_series::series_id.eq(series_id))
+                            .select(competitions_in_series::competition_id),
+                    ),
+                )
+                .filter(participants::deleted_at.is_null())
+                .order_by((categories::id, results::finish_time))
+                .select(SeriesResult::as_select())
+                .load(conn)?;
+            QueryResult::Ok((series, competitions, results))
+        })
+        .await?;
+    let series =
+        series.ok_or_else(|| Error::NotFound(format!("Series with id {series_id} not found")))?;
+    let categories = compute_standings(&series, &competitions, results);
+
+    state.render_template(
+        "series_standings.html",
+        SeriesStandingsData {
+            series,
+            competitions,
+            categories,
+        },
+    )
+}
+
+/// Compute the standings per category label
+///
+/// `results` is expected to be ordered by category and finish time
+fn compute_standings(
+    series: &Series,
+    competitions: &[SeriesCompetition],
+    results: Vec<SeriesResult>,
+) -> Vec<CategoryStandings> {
+    let points_scheme = series.points_scheme();
+    let best_n = usize::try_from(series.best_n).unwrap_or_default();
+    let competition_index = competitions
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| (c.id, idx))
+        .collect::<HashMap<_, _>>();
+
+    let mut per_category = BTreeMap::<String, HashMap<ParticipantIdentity, StandingsEntry>>::new();
+    let mut current_category = None;
+    let mut position = 0;
+    let mut rank = 0;
+    let mut previous_time = None;
+    for result in results {
+        if current_category != Some(result.category_id) {
+            current_category = Some(result.category_id);
+            position = 0;
+            previous_time = None;
+        }
+        position += 1;
+        // participants with the same finish time share their rank
+        if previous_time != Some(result.finish_time) {
+            rank = position;
+            previous_time = Some(result.finish_time);
+        }
+        let Some(competition_idx) = competition_index.get(&result.competition_id) else {
+            continue;
+        };
+        let points = points_scheme.get(rank - 1).copied().unwrap_or(0);
+        let entry = per_category
+            .entry(result.category.clone())
+            .or_default()
+            .entry(ParticipantIdentity::new(&result))
+            .or_insert_with(|| StandingsEntry {
+                rank: 0,
+                first_name: result.first_name.clone(),
+                last_name: result.last_name.clone(),
+                club: None,
+                birth_year: result.birth_year,
+                points: vec![None; competitions.len()],
+                total: 0,
+            });
+        entry.points[*competition_idx] = Some(points);
+        // keep the first known club
+        if entry.club.is_none() {
+            entry.club = result.club;
+        }
+    }
+
+    per_category
+        .into_iter()
+        .map(|(category, entries)| {
+            let mut entries = entries
+                .into_values()
+                .map(|mut entry| {
+                    let mut points = entry.points.iter().flatten().copied().collect::<Vec<_>>();
+                    points.sort_unstable_by(|a, b| b.cmp(a));
+                    // series stored before points were limited may contain huge points
+                    entry.total = points.into_iter().take(best_n).fold(0, i32::saturating_add);
+                    entry
+                })
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| {
+                b.total
+                    .cmp(&a.total)
+                    .then_with(|| a.last_name.cmp(&b.last_name))
+                    .then_with(|| a.first_name.cmp(&b.first_name))
+            });
+            let mut previous_total = None;
+            for idx in 0..entries.len() {
+                entries[idx].rank = if previous_total == Some(entries[idx].total) {
+                    entries[idx - 1].rank
+                } else {
+                    idx + 1
+                };
+                previous_total = Some(entries[idx].total);
+            }
+            CategoryStandings { category, entries }
+        })
+        .collect()
+}
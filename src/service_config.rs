@@ -1,35 +1,398 @@
 //! Command line arguments / Application configuration definition
 //!
-//! This is either set at startup via the command line or explicitly while running the tests
+//! The configuration is loaded in layers, each one overriding the previous ones:
 //!
-//! For a real world application you would likely want to have the ability to also load
-//! (parts of this) from environment variables or a configuration file
+//! 1. The defaults defined by `Config::default`
+//! 2. A TOML file, either given by `--config` or `race_timing.toml` in the
+//!    working directory if that exists
+//! 3. Environment variables prefixed with `RACE_TIMING_`, e.g. `RACE_TIMING_PORT`
+//! 4. Command line flags, e.g. `--port`
+//!
+//! The tests construct a `Config` explicitly instead
+#[cfg(feature = "sqlite")]
+use crate::database::backup::BackupSettings;
+use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sqlite")]
+use std::time::Duration;
+
+/// Configuration file that is loaded if no other file is given
+const DEFAULT_CONFIG_FILE: &str = "race_timing.toml";
+
+/// Command line interface of the application
+#[derive(clap::Parser, Debug)]
+#[command(about)]
+pub struct Cli {
+    /// Path to a TOML configuration file
+    #[arg(long, global = true, env = "RACE_TIMING_CONFIG")]
+    pub config: Option<PathBuf>,
+    #[command(flatten)]
+    pub overrides: ConfigLayer,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
 
-#[derive(clap::Parser, Clone, Debug)]
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Start the web server, this is the default
+    Serve,
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manage the database without starting the web server
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Validate the configuration and print the effective values
+    Check,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum DbCommand {
+    /// Apply all pending migrations
+    Migrate,
+    /// List all migrations and whether they are applied
+    Status,
+    /// Revert the most recently applied migration
+    Revert,
+    /// Insert the test data into an empty database
+    Seed,
+    /// Check the database for corruption and violated foreign keys
+    #[cfg(feature = "sqlite")]
+    Check,
+    /// Replace the database by a backup, the server must not be running
+    #[cfg(feature = "sqlite")]
+    Restore {
+        /// Path of the backup file
+        backup: PathBuf,
+    },
+}
+
+/// The effective application configuration
+#[derive(Serialize, Clone, Debug)]
 pub struct Config {
     /// Port the application is running on
-    #[clap(default_value = "8000")]
     pub port: u16,
     /// Address the application is listing on
-    #[clap(default_value = "0.0.0.0")]
     pub address: IpAddr,
     /// Path where the database should be stored
-    #[clap(default_value = "race_time.db")]
     pub database_url: String,
     /// Whether or not test data should be inserted into the database
-    #[clap(long = "insert-test-data")]
     pub insert_test_data: bool,
     /// Base url the application is hosted at
-    #[clap(long = "base_url", default_value = "")]
     pub base_url: String,
-    /// Path to the template directory
-    #[clap(default_value = "templates")]
-    pub template_dir: PathBuf,
+    /// Directory with templates overriding the embedded ones
+    pub template_dir: Option<PathBuf>,
+    /// Whether templates are reloaded on every render, for developing templates
+    pub template_reload: bool,
+    /// Number of days deleted competitions and participants are kept in the trash
+    pub trash_retention_days: u32,
+    /// Number of failed logins after which a user name or IP address is locked out
+    pub login_lockout_attempts: u32,
+    /// Number of minutes a user name or IP address stays locked out
+    pub login_lockout_minutes: u32,
+    /// Interval in seconds between removing expired sessions from the database, 0 disables it
+    pub session_cleanup_interval_secs: u64,
+    /// Directory scheduled backups are written to, no backups are written if unset
+    pub backup_dir: Option<PathBuf>,
+    /// Interval in seconds between scheduled backups, 0 disables them
+    pub backup_interval_secs: u64,
+    /// Number of scheduled backups kept, older ones are removed
+    pub backup_keep: u32,
+    /// Queries taking at least this many milliseconds are logged as slow, 0 disables it
+    pub slow_query_threshold_ms: u64,
+    /// Number of seconds open requests may take to finish on shutdown
+    pub shutdown_timeout_secs: u64,
+    /// Internal point in time used instead of the system clock to verify
+    /// one-time passwords, so tests can use known codes
+    ///
+    /// This cannot be set from the outside
+    #[serde(skip)]
+    pub fixed_time: Option<time::OffsetDateTime>,
     /// Internal flag whether or on this config is a test run config
     ///
-    /// This cannot be set from the command line
-    #[clap(skip)]
+    /// This cannot be set from the outside
+    #[serde(skip)]
     pub is_test: bool,
 }
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 8000,
+            address: IpAddr::from([0, 0, 0, 0]),
+            database_url: String::from("race_time.db"),
+            insert_test_data: false,
+            base_url: String::new(),
+            template_dir: None,
+            template_reload: false,
+            trash_retention_days: 30,
+            login_lockout_attempts: 10,
+            login_lockout_minutes: 15,
+            session_cleanup_interval_secs: 3600,
+            backup_dir: None,
+            backup_interval_secs: 3600,
+            backup_keep: 24,
+            slow_query_threshold_ms: 500,
+            shutdown_timeout_secs: 30,
+            fixed_time: None,
+            is_test: false,
+        }
+    }
+}
+
+/// A single configuration layer, where each value is optional
+///
+/// This is used both for the configuration file and for the command line
+/// flags and environment variables. See `Config` for the meaning of each value
+#[derive(clap::Args, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    /// Port the application is running on [default: 8000]
+    #[arg(long, global = true, env = "RACE_TIMING_PORT")]
+    pub port: Option<u16>,
+    /// Address the application is listing on [default: 0.0.0.0]
+    #[arg(long, global = true, env = "RACE_TIMING_ADDRESS")]
+    pub address: Option<IpAddr>,
+    /// Path where the database should be stored [default: race_time.db]
+    #[arg(long, global = true, env = "RACE_TIMING_DATABASE_URL")]
+    pub database_url: Option<String>,
+    /// Whether or not test data should be inserted into the database
+    #[arg(
+        long,
+        global = true,
+        env = "RACE_TIMING_INSERT_TEST_DATA",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub insert_test_data: Option<bool>,
+    /// Base url the application is hosted at, e.g. `/timing` [default: ""]
+    #[arg(long, alias = "base_url", global = true, env = "RACE_TIMING_BASE_URL")]
+    pub base_url: Option<String>,
+    /// Directory with templates overriding the embedded ones
+    #[arg(long, global = true, env = "RACE_TIMING_TEMPLATE_DIR")]
+    pub template_dir: Option<PathBuf>,
+    /// Whether templates are reloaded on every render, for developing templates
+    #[arg(
+        long,
+        global = true,
+        env = "RACE_TIMING_TEMPLATE_RELOAD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true"
+    )]
+    pub template_reload: Option<bool>,
+    /// Number of days deleted competitions and participants are kept in the trash [default: 30]
+    #[arg(long, global = true, env = "RACE_TIMING_TRASH_RETENTION_DAYS")]
+    pub trash_retention_days: Option<u32>,
+    /// Number of failed logins after which a user name or IP address is locked out [default: 10]
+    #[arg(long, global = true, env = "RACE_TIMING_LOGIN_LOCKOUT_ATTEMPTS")]
+    pub login_lockout_attempts: Option<u32>,
+    /// Number of minutes a user name or IP address stays locked out [default: 15]
+    #[arg(long, global = true, env = "RACE_TIMING_LOGIN_LOCKOUT_MINUTES")]
+    pub login_lockout_minutes: Option<u32>,
+    /// Interval in seconds between removing expired sessions, 0 disables it [default: 3600]
+    #[arg(
+        long = "session-cleanup-interval",
+        global = true,
+        env = "RACE_TIMING_SESSION_CLEANUP_INTERVAL_SECS"
+    )]
+    pub session_cleanup_interval_secs: Option<u64>,
+    /// Directory scheduled backups are written to, no backups are written if unset
+    #[arg(long, global = true, env = "RACE_TIMING_BACKUP_DIR")]
+    pub backup_dir: Option<PathBuf>,
+    /// Interval in seconds between scheduled backups, 0 disables them [default: 3600]
+    #[arg(
+        long = "backup-interval",
+        global = true,
+        env = "RACE_TIMING_BACKUP_INTERVAL_SECS"
+    )]
+    pub backup_interval_secs: Option<u64>,
+    /// Number of scheduled backups kept, older ones are removed [default: 24]
+    #[arg(long, global = true, env = "RACE_TIMING_BACKUP_KEEP")]
+    pub backup_keep: Option<u32>,
+    /// Queries taking at least this many milliseconds are logged as slow, 0 disables it [default: 500]
+    #[arg(
+        long = "slow-query-threshold",
+        global = true,
+        env = "RACE_TIMING_SLOW_QUERY_THRESHOLD_MS"
+    )]
+    pub slow_query_threshold_ms: Option<u64>,
+    /// Number of seconds open requests may take to finish on shutdown [default: 30]
+    #[arg(
+        long = "shutdown-timeout",
+        global = true,
+        env = "RACE_TIMING_SHUTDOWN_TIMEOUT_SECS"
+    )]
+    pub shutdown_timeout_secs: Option<u64>,
+}
+
+impl ConfigLayer {
+    /// Read a layer from a TOML file
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+        toml::from_str(&content).map_err(|source| ConfigError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Cannot read the configuration file `{}`: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid configuration file `{}`: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("Invalid value for `{field}`: {message}")]
+    Invalid {
+        field: &'static str,
+        message: String,
+    },
+}
+
+impl Config {
+    /// Load the configuration from all layers as given on the command line
+    pub fn load(cli: &Cli) -> Result<Self, ConfigError> {
+        let file = match &cli.config {
+            Some(path) => Some(ConfigLayer::from_file(path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Some(ConfigLayer::from_file(Path::new(DEFAULT_CONFIG_FILE))?)
+            }
+            None => None,
+        };
+        let config = Self::from_layers(file.into_iter().chain([cli.overrides.clone()]));
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Apply the given layers on top of the defaults, later layers win
+    pub fn from_layers(layers: impl IntoIterator<Item = ConfigLayer>) -> Self {
+        layers.into_iter().fold(Self::default(), |config, layer| {
+            let ConfigLayer {
+                port,
+                address,
+                database_url,
+                insert_test_data,
+                base_url,
+                template_dir,
+                template_reload,
+                trash_retention_days,
+                login_lockout_attempts,
+                login_lockout_minutes,
+                session_cleanup_interval_secs,
+                backup_dir,
+                backup_interval_secs,
+                backup_keep,
+                slow_query_threshold_ms,
+                shutdown_timeout_secs,
+            } = layer;
+            Self {
+                port: port.unwrap_or(config.port),
+                address: address.unwrap_or(config.address),
+                database_url: database_url.unwrap_or(config.database_url),
+                insert_test_data: insert_test_data.unwrap_or(config.insert_test_data),
+                base_url: base_url.unwrap_or(config.base_url),
+                template_dir: template_dir.or(config.template_dir),
+                template_reload: template_reload.unwrap_or(config.template_reload),
+                trash_retention_days: trash_retention_days.unwrap_or(config.trash_retention_days),
+                login_lockout_attempts: login_lockout_attempts
+                    .unwrap_or(config.login_lockout_attempts),
+                login_lockout_minutes: login_lockout_minutes
+                    .unwrap_or(config.login_lockout_minutes),
+                session_cleanup_interval_secs: session_cleanup_interval_secs
+                    .unwrap_or(config.session_cleanup_interval_secs),
+                backup_dir: backup_dir.or(config.backup_dir),
+                backup_interval_secs: backup_interval_secs.unwrap_or(config.backup_interval_secs),
+                backup_keep: backup_keep.unwrap_or(config.backup_keep),
+                slow_query_threshold_ms: slow_query_threshold_ms
+                    .unwrap_or(config.slow_query_threshold_ms),
+                shutdown_timeout_secs: shutdown_timeout_secs
+                    .unwrap_or(config.shutdown_timeout_secs),
+                ..config
+            }
+        })
+    }
+
+    /// Check the values that cannot be checked by their type alone
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, message: &str| {
+            Err(ConfigError::Invalid {
+                field,
+                message: message.to_owned(),
+            })
+        };
+        if self.database_url.trim().is_empty() {
+            return invalid("database_url", "must not be empty");
+        }
+        if !self.base_url.is_empty()
+            && (!self.base_url.starts_with('/') || self.base_url.ends_with('/'))
+        {
+            return invalid(
+                "base_url",
+                "must be empty or start with a `/` and not end with one",
+            );
+        }
+        match &self.template_dir {
+            Some(dir) if !dir.is_dir() => {
+                return invalid(
+                    "template_dir",
+                    &format!("`{}` is not a directory", dir.display()),
+                );
+            }
+            None if self.template_reload => {
+                return invalid(
+                    "template_reload",
+                    "requires a `template_dir` to reload the templates from",
+                );
+            }
+            _ => {}
+        }
+        if self.login_lockout_attempts == 0 {
+            return invalid("login_lockout_attempts", "must be at least 1");
+        }
+        if self.login_lockout_minutes == 0 {
+            return invalid("login_lockout_minutes", "must be at least 1");
+        }
+        if cfg!(feature = "postgres") && self.backup_dir.is_some() {
+            return invalid(
+                "backup_dir",
+                "backups are only supported with SQLite, use `pg_dump` for PostgreSQL",
+            );
+        }
+        if self.backup_keep == 0 {
+            return invalid("backup_keep", "must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// Settings for scheduled backups, if they are enabled
+    #[cfg(feature = "sqlite")]
+    pub fn backup_settings(&self) -> Option<BackupSettings> {
+        let dir = self.backup_dir.clone()?;
+        (self.backup_interval_secs > 0).then(|| BackupSettings {
+            dir,
+            interval: Duration::from_secs(self.backup_interval_secs),
+            keep: self.backup_keep as usize,
+        })
+    }
+
+    /// The effective configuration in the format of the configuration file
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("The configuration can be serialized")
+    }
+}
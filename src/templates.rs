@@ -0,0 +1,49 @@
+//! Templates of the HTML pages
+//!
+//! All templates of `templates/` are embedded into the binary at compile
+//! time, so the server does not depend on its working directory. The optional
+//! template directory of the configuration is an override layer: a template
+//! found there is used instead of the embedded one with the same name, which
+//! allows to customize single pages without rebuilding the application.
+use minijinja::Environment;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+include!(concat!(env!("OUT_DIR"), "/embedded_templates.rs"));
+
+/// Source of the embedded template with the given name
+fn embedded(name: &str) -> Option<&'static str> {
+    EMBEDDED_TEMPLATES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, source)| *source)
+}
+
+/// Setup the loader of `env`, templates in `override_dir` take precedence
+pub(crate) fn set_loader(env: &mut Environment<'static>, override_dir: Option<PathBuf>) {
+    let override_loader = override_dir.map(minijinja::path_loader);
+    env.set_loader(move |name| {
+        if let Some(load) = &override_loader {
+            if let Some(source) = load(name)? {
+                return Ok(Some(source));
+            }
+        }
+        Ok(embedded(name).map(str::to_owned))
+    });
+}
+
+/// Names of all templates, the embedded ones and those in `override_dir`
+pub(crate) fn names(override_dir: Option<&Path>) -> std::io::Result<BTreeSet<String>> {
+    let mut names = EMBEDDED_TEMPLATES
+        .iter()
+        .map(|(name, _)| (*name).to_owned())
+        .collect::<BTreeSet<_>>();
+    if let Some(dir) = override_dir {
+        for entry in std::fs::read_dir(dir)? {
+            if let Some(name) = entry?.file_name().to_str().filter(|n| n.ends_with(".html")) {
+                names.insert(name.to_owned());
+            }
+        }
+    }
+    Ok(names)
+}
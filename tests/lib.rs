@@ -10,19 +10,93 @@ use tower::ServiceExt;
 // the `test_data` argument indicates whether we want to include test data
 // on startup or not
 fn test_config(test_data: bool) -> Config {
-    let template_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("templates");
-
     Config {
         port: 8000,
         address: "127.0.0.1".parse().unwrap(),
-        database_url: ":memory:".into(),
+        database_url: test_database_url(),
         insert_test_data: test_data,
         base_url: "".into(),
-        template_dir,
+        template_dir: None,
+        template_reload: false,
+        trash_retention_days: 30,
+        login_lockout_attempts: 10,
+        login_lockout_minutes: 15,
+        session_cleanup_interval_secs: 3600,
+        backup_dir: None,
+        backup_interval_secs: 3600,
+        backup_keep: 24,
+        slow_query_threshold_ms: 500,
+        shutdown_timeout_secs: 30,
+        fixed_time: None,
         is_test: true,
     }
 }
 
+// each test gets its own empty database
+#[cfg(feature = "sqlite")]
+fn test_database_url() -> String {
+    ":memory:".into()
+}
+
+// each test gets its own empty database
+//
+// The databases are created on the server given by
+// `RACE_TIMING_TEST_POSTGRES_URL`, by default a local server without password.
+// They are removed once the thread running the test ends, even if the test
+// failed. Databases of other processes are left alone, as concurrent test
+// runs may still use them
+#[cfg(feature = "postgres")]
+fn test_database_url() -> String {
+    use diesel::prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Drops the database when the test thread ends
+    struct TestDatabase {
+        server: String,
+        name: String,
+    }
+
+    impl Drop for TestDatabase {
+        fn drop(&mut self) {
+            let dropped = PgConnection::establish(&format!("{}/postgres", self.server))
+                .map_err(|e| e.to_string())
+                .and_then(|mut conn| {
+                    diesel::sql_query(format!(
+                        "DROP DATABASE IF EXISTS {} WITH (FORCE)",
+                        self.name
+                    ))
+                    .execute(&mut conn)
+                    .map_err(|e| e.to_string())
+                });
+            if let Err(e) = dropped {
+                eprintln!("Failed to drop test database {}: {e}", self.name);
+            }
+        }
+    }
+
+    thread_local! {
+        static CREATED_DATABASES: std::cell::RefCell<Vec<TestDatabase>> =
+            const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    static DATABASE_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let server = std::env::var("RACE_TIMING_TEST_POSTGRES_URL")
+        .unwrap_or_else(|_| String::from("postgres://postgres@localhost"));
+    let name = format!(
+        "race_timing_test_{}_{}",
+        std::process::id(),
+        DATABASE_COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let mut conn = PgConnection::establish(&format!("{server}/postgres"))
+        .expect("A PostgreSQL server is required to run the tests with the `postgres` feature");
+    diesel::sql_query(format!("CREATE DATABASE {name}"))
+        .execute(&mut conn)
+        .unwrap();
+    let url = format!("{server}/{name}");
+    CREATED_DATABASES.with(|created| created.borrow_mut().push(TestDatabase { server, name }));
+    url
+}
+
 #[tokio::test]
 async fn translations_work() {
     let (router, _state) = race_timing::setup(test_config(false)).await;
@@ -102,3 +176,2364 @@ async fn translations_work() {
     let string = String::from_utf8(data.to_vec()).unwrap();
     assert!(string.contains("Wettkämpfe"), "{string}");
 }
+
+// login as the `admin` user created by the test data
+//
+// returns the session cookie that needs to be sent with
+// any following request to the admin pages
+async fn login(router: &axum::Router) -> String {
+    // the login form requires a session with a CSRF token
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/login.html")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let cookie = session_cookie(&resp);
+    let token = csrf_token(router, &cookie).await;
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::post("/admin/login")
+                .header("Cookie", &cookie)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(format!(
+                    "csrf_token={token}&name=admin&password=admin"
+                )))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    session_cookie(&resp)
+}
+
+fn session_cookie(resp: &axum::response::Response) -> String {
+    let cookie = resp.headers().get("Set-Cookie").unwrap().to_str().unwrap();
+    cookie.split(';').next().unwrap().to_owned()
+}
+
+// extract the CSRF token of the given session from the login form
+async fn csrf_token(router: &axum::Router, cookie: &str) -> String {
+    let (status, string) = get_page(router, cookie, "/admin/login.html").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, rest) = string
+        .split_once("name=\"csrf_token\" value=\"")
+        .expect("The login form contains a CSRF token");
+    rest.split('"').next().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn start_list_pdf() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::participants;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    // long values are cut off instead of overflowing into the next column
+    state
+        .with_connection(|conn| {
+            diesel::update(participants::table.filter(participants::first_name.eq("John")))
+                .set(participants::club.eq(
+                    "Allgemeiner Sportverein Donaustadt Laufgemeinschaft Wien Sektion Crosslauf",
+                ))
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/starts/6/start_list.pdf")
+                .header("Cookie", &cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["Content-Type"], "application/pdf");
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert!(data.starts_with(b"%PDF-"));
+    // the document contains the participant registered for this start
+    let content = String::from_utf8_lossy(&data);
+    assert!(content.contains("Doe, John"), "{content}");
+    // the shortened club is written hex encoded due to the ellipsis
+    let club = "Allgemeiner Sportverein "
+        .bytes()
+        .map(|b| format!("{b:02X}"))
+        .collect::<String>();
+    assert!(content.contains(&format!("<{club}")), "{content}");
+    assert!(content.contains("85> Tj"), "{content}");
+    assert!(!content.contains("Crosslauf"), "{content}");
+
+    // unknown starts result in a 404
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/starts/4242/start_list.pdf")
+                .header("Cookie", &cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn results_and_certificates() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::participants;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (participant_id, category_id) = state
+        .with_connection(|conn| {
+            participants::table
+                .filter(participants::first_name.eq("John"))
+                .select((participants::id, participants::category_id))
+                .first::<(i32, i32)>(conn)
+        })
+        .await
+        .unwrap();
+
+    // store a finish time
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/categories/{category_id}/results"),
+        format!("{participant_id}=45%3A12"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get(format!("/admin/categories/{category_id}/results.html"))
+                .header("Cookie", &cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let string = String::from_utf8(data.to_vec()).unwrap();
+    assert!(string.contains("0:45:12"), "{string}");
+
+    // invalid times are rejected
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/categories/{category_id}/results"),
+        format!("{participant_id}=45%3A75"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // the certificate contains the placeholder values from the result,
+    // placeholders within those values are not replaced
+    state
+        .with_connection(move |conn| {
+            diesel::update(participants::table.find(participant_id))
+                .set(participants::club.eq("Club %rank% %time% %unknown%"))
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get(format!("/admin/categories/{category_id}/certificates.pdf"))
+                .header("Cookie", &cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert!(data.starts_with(b"%PDF-"));
+    let content = String::from_utf8_lossy(&data);
+    assert!(content.contains("John Doe"), "{content}");
+    assert!(content.contains("0:45:12"), "{content}");
+    assert!(content.contains("rank 1 "), "{content}");
+    assert!(
+        content.contains("Club %rank% %time% %unknown%"),
+        "{content}"
+    );
+}
+
+// send a url encoded form as logged in user
+//
+// this includes the CSRF token of the session, like the forms
+// rendered by the admin pages
+async fn post_form(
+    router: &axum::Router,
+    cookie: &str,
+    uri: &str,
+    body: impl Into<String>,
+) -> axum::response::Response {
+    let token = csrf_token(router, cookie).await;
+    router
+        .clone()
+        .oneshot(
+            Request::post(uri)
+                .header("Cookie", cookie)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(format!("csrf_token={token}&{}", body.into())))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn series_standings() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::participants;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (participant_id, category_id) = state
+        .with_connection(|conn| {
+            participants::table
+                .filter(participants::first_name.eq("John"))
+                .select((participants::id, participants::category_id))
+                .first::<(i32, i32)>(conn)
+        })
+        .await
+        .unwrap();
+
+    // invalid points schemes are rejected
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/create",
+        "name=Cup&description=&points_per_rank=10%2Cfoo&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/create",
+        "name=Cup&description=&points_per_rank=2000000000%2C8&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/create",
+        "name=Cup&description=&points_per_rank=10%2C8&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/categories/{category_id}/results"),
+        format!("{participant_id}=45%3A12"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    // the standings page is public
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/series/1/standings.html")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let string = String::from_utf8(data.to_vec()).unwrap();
+    assert!(
+        string.contains("Country Cross Race Vienna 2024"),
+        "{string}"
+    );
+    assert!(string.contains("<td>John</td>"), "{string}");
+    assert!(string.contains("<td>10</td>"), "{string}");
+    assert!(!string.contains("<td>Jane</td>"), "{string}");
+
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/series/42/standings.html")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // results and series are tracked in the audit log
+    let (_, string) = get_page(
+        &router,
+        &cookie,
+        &format!("/admin/audit.html?entity=result&entity_id={participant_id}&action=&user_id="),
+    )
+    .await;
+    assert!(string.contains("<td>create</td>"), "{string}");
+    assert!(string.contains("finish_time&quot;:2712"), "{string}");
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/1",
+        "name=Cup+2024&description=&points_per_rank=10%2C8&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = post_form(&router, &cookie, "/admin/series/1/delete", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(
+        &router,
+        &cookie,
+        "/admin/audit.html?entity=series&entity_id=1&action=&user_id=",
+    )
+    .await;
+    assert_eq!(string.matches("<td>series</td>").count(), 3, "{string}");
+    assert!(string.contains("<td>delete</td>"), "{string}");
+    assert!(string.contains("Cup 2024"), "{string}");
+    assert!(string.contains("competition_ids&quot;:[1]"), "{string}");
+}
+
+// send a GET request with the given session cookie
+//
+// returns the status code and the body of the response
+async fn get_page(router: &axum::Router, cookie: &str, uri: &str) -> (StatusCode, String) {
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get(uri)
+                .header("Cookie", cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = resp.status();
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(data.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn persons_link_participants() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::participants;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+
+    // a returning runner with slightly different spelling
+    let (johns_person, category_id) = state
+        .with_connection(|conn| {
+            participants::table
+                .filter(participants::first_name.eq("John"))
+                .select((participants::person_id, participants::category_id))
+                .first::<(Option<i32>, i32)>(conn)
+        })
+        .await
+        .unwrap();
+    let johns_person = johns_person.expect("test data is linked to persons");
+    let second_entry = state
+        .with_connection(move |conn| {
+            diesel::insert_into(participants::table)
+                .values((
+                    participants::last_name.eq("doe "),
+                    participants::first_name.eq("JOHN"),
+                    participants::category_id.eq(category_id),
+                    participants::consent_agb.eq(true),
+                    participants::birth_year.eq(1995),
+                ))
+                .returning(participants::id)
+                .get_result::<i32>(conn)
+        })
+        .await
+        .unwrap();
+
+    let resp = post_form(&router, &cookie, "/admin/persons/match", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    async fn person_of(state: &race_timing::app_state::State, participant_id: i32) -> Option<i32> {
+        state
+            .with_connection(move |conn| {
+                participants::table
+                    .find(participant_id)
+                    .select(participants::person_id)
+                    .first::<Option<i32>>(conn)
+            })
+            .await
+            .unwrap()
+    }
+    assert_eq!(person_of(&state, second_entry).await, Some(johns_person));
+
+    let (status, string) = get_page(
+        &router,
+        &cookie,
+        &format!("/admin/persons/{johns_person}/history.html"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        string.contains("Country Cross Race Vienna 2024"),
+        "{string}"
+    );
+    assert!(string.contains("<td>JOHN</td>"), "{string}");
+
+    // unlinking moves the entry to a new person
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/persons/{johns_person}/unlink/{second_entry}"),
+        "",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let new_person = person_of(&state, second_entry).await.unwrap();
+    assert_ne!(new_person, johns_person);
+
+    // linking it again removes the now unused person
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/persons/{johns_person}/link"),
+        format!("participant_id={second_entry}"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(person_of(&state, second_entry).await, Some(johns_person));
+    let (status, _) = get_page(
+        &router,
+        &cookie,
+        &format!("/admin/persons/{new_person}/history.html"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn entry_fees_and_payments() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::{participants, races, special_categories};
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (race_ids, special_category_ids, johns_id) = state
+        .with_connection(|conn| {
+            let race_ids = races::table
+                .filter(races::competition_id.eq(1))
+                .select(races::id)
+                .load::<i32>(conn)?;
+            let special_category_ids = special_categories::table
+                .filter(special_categories::race_id.eq_any(&race_ids))
+                .select(special_categories::id)
+                .load::<i32>(conn)?;
+            let johns_id = participants::table
+                .filter(participants::first_name.eq("John"))
+                .select(participants::id)
+                .first::<i32>(conn)?;
+            diesel::QueryResult::Ok((race_ids, special_category_ids, johns_id))
+        })
+        .await
+        .unwrap();
+
+    let mut form = Vec::new();
+    for id in &race_ids {
+        form.push(format!(
+            "entry_fee_{id}=25&late_entry_fee_{id}=&late_entry_from_{id}="
+        ));
+    }
+    for id in &special_category_ids {
+        form.push(format!("surcharge_{id}=2%2C50"));
+    }
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/fees",
+        form.join("&"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    // a late fee without date is rejected
+    let id = race_ids[0];
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/fees",
+        format!("{}&late_entry_fee_{id}=30", form.join("&")),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    // amounts are limited, so sums of them cannot overflow
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/fees",
+        format!("{}&entry_fee_{id}=10000.01", form.join("&")),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let reference_code = format!("RT{johns_id:06}{:02}", 98 - (johns_id * 100) % 97);
+    let (status, string) = get_page(&router, &cookie, "/admin/competitions/1/unpaid.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(string.contains(&reference_code), "{string}");
+    // John registered for a special category with surcharge
+    assert!(string.contains("<td>27.50</td>"), "{string}");
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/import_payments",
+        format!(
+            "statement=Date%3BText%3BAmount%0A2024-05-01%3B{reference_code}%20John%20Doe%3B27.50%0A2024-05-02%3BRT00000100%3B10"
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let string = String::from_utf8(data.to_vec()).unwrap();
+    assert!(string.contains(&reference_code), "{string}");
+    assert!(string.contains("RT00000100"), "{string}");
+
+    let (_, string) = get_page(&router, &cookie, "/admin/competitions/1/unpaid.html").await;
+    assert!(!string.contains(&reference_code), "{string}");
+    assert!(string.contains("27.50"), "{string}");
+
+    let paid = state
+        .with_connection(move |conn| {
+            participants::table
+                .find(johns_id)
+                .select(participants::paid_amount)
+                .first::<Option<i32>>(conn)
+        })
+        .await
+        .unwrap();
+    assert_eq!(paid, Some(2750));
+
+    // ids with more than six digits do not fit into a reference code
+    state
+        .with_connection(move |conn| {
+            let category_id = participants::table
+                .find(johns_id)
+                .select(participants::category_id)
+                .first::<i32>(conn)?;
+            diesel::insert_into(participants::table)
+                .values((
+                    participants::id.eq(1_000_000),
+                    participants::first_name.eq("Max"),
+                    participants::last_name.eq("Widerange"),
+                    participants::category_id.eq(category_id),
+                    participants::consent_agb.eq(true),
+                    participants::birth_year.eq(1990),
+                ))
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+    let (_, string) = get_page(&router, &cookie, "/admin/competitions/1/unpaid.html").await;
+    assert!(string.contains("Widerange"), "{string}");
+    assert!(!string.contains("RT1000000"), "{string}");
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/import_payments",
+        "statement=RT100000017",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (_, string) = get_page(&router, &cookie, "/admin/competitions/1/unpaid.html").await;
+    assert!(string.contains("Widerange"), "{string}");
+
+    // the entry fee can be waived instead
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/waive_fees",
+        "participant_1000000=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, "/admin/competitions/1/unpaid.html").await;
+    assert!(!string.contains("Widerange"), "{string}");
+    let (status, paid_amount) = state
+        .with_connection(move |conn| {
+            participants::table
+                .find(1_000_000)
+                .select((participants::payment_status, participants::paid_amount))
+                .first::<(String, Option<i32>)>(conn)
+        })
+        .await
+        .unwrap();
+    assert_eq!(status, "waived");
+    assert_eq!(paid_amount, None);
+}
+
+#[tokio::test]
+async fn audit_log() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::{participants, races, special_categories};
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (race_ids, special_category_ids, johns_id) = state
+        .with_connection(|conn| {
+            let race_ids = races::table
+                .filter(races::competition_id.eq(1))
+                .select(races::id)
+                .load::<i32>(conn)?;
+            let special_category_ids = special_categories::table
+                .filter(special_categories::race_id.eq_any(&race_ids))
+                .select(special_categories::id)
+                .load::<i32>(conn)?;
+            let johns_id = participants::table
+                .filter(participants::first_name.eq("John"))
+                .select(participants::id)
+                .first::<i32>(conn)?;
+            diesel::QueryResult::Ok((race_ids, special_category_ids, johns_id))
+        })
+        .await
+        .unwrap();
+    let form = race_ids
+        .iter()
+        .map(|id| format!("entry_fee_{id}=12.5&late_entry_fee_{id}=&late_entry_from_{id}="))
+        .chain(
+            special_category_ids
+                .iter()
+                .map(|id| format!("surcharge_{id}=0")),
+        )
+        .collect::<Vec<_>>()
+        .join("&");
+    let resp = post_form(&router, &cookie, "/admin/competitions/1/fees", form.clone()).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    // storing the same values again does not create new entries
+    let resp = post_form(&router, &cookie, "/admin/competitions/1/fees", form).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/competitions/1/mark_paid",
+        format!("participant_{johns_id}=on"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    let (status, string) = get_page(&router, &cookie, "/admin/audit.html").await;
+    assert_eq!(status, StatusCode::OK, "{string}");
+    assert_eq!(
+        string.matches("<td>race</td>").count(),
+        race_ids.len(),
+        "{string}"
+    );
+    assert!(string.contains("<td>participant</td>"), "{string}");
+    assert!(string.contains("<td>admin</td>"), "{string}");
+    assert!(string.contains("entry_fee&quot;:1250"), "{string}");
+
+    let (status, string) = get_page(
+        &router,
+        &cookie,
+        &format!("/admin/audit.html?entity=participant&entity_id={johns_id}&action=&user_id="),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!string.contains("<td>race</td>"), "{string}");
+    assert!(
+        string.contains("payment_status&quot;:&quot;paid"),
+        "{string}"
+    );
+}
+
+#[tokio::test]
+async fn trash_restore_and_purge() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::participants;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (johns_id, category_id) = state
+        .with_connection(|conn| {
+            participants::table
+                .filter(participants::first_name.eq("John"))
+                .select((participants::id, participants::category_id))
+                .first::<(i32, i32)>(conn)
+        })
+        .await
+        .unwrap();
+    let results_page = format!("/admin/categories/{category_id}/results.html");
+    let (_, string) = get_page(&router, &cookie, &results_page).await;
+    assert!(string.contains("John"), "{string}");
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/participants/{johns_id}/delete"),
+        "redirect_to=trash.html",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, &results_page).await;
+    assert!(!string.contains("John"), "{string}");
+    let (status, string) = get_page(&router, &cookie, "/admin/trash.html").await;
+    assert_eq!(status, StatusCode::OK, "{string}");
+    assert!(string.contains("John"), "{string}");
+    // deleting twice is not possible
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/participants/{johns_id}/delete"),
+        "redirect_to=trash.html",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/trash/participants/{johns_id}/restore"),
+        "",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, &results_page).await;
+    assert!(string.contains("John"), "{string}");
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/create",
+        "name=Cup&description=&points_per_rank=10%2C8&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, "/admin/series/index.html").await;
+    assert!(string.contains("<td>1</td>"), "{string}");
+
+    let resp = post_form(&router, &cookie, "/admin/competitions/1/delete", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    // competitions in the trash are hidden from the other admin pages
+    let (_, string) = get_page(&router, &cookie, "/admin/series/index.html").await;
+    assert!(string.contains("<td>0</td>"), "{string}");
+    let (status, _) = get_page(&router, &cookie, "/admin/competitions/1/unpaid.html").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = get_page(&router, &cookie, "/admin/competitions/1/fees.html").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/starts/6/start_list.pdf")
+                .header("Cookie", &cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    // entries within the retention period are kept
+    let resp = post_form(&router, &cookie, "/admin/trash/purge", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, "/admin/trash.html").await;
+    assert!(
+        string.contains("/admin/trash/competitions/1/restore"),
+        "{string}"
+    );
+    let (_, string) = get_page(&router, &cookie, "/admin/audit.html?entity=competition").await;
+    assert!(string.contains("<td>delete</td>"), "{string}");
+}
+
+#[tokio::test]
+async fn trash_purge_after_retention() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::{audit_log, competitions, participants, races};
+
+    let mut config = test_config(true);
+    config.trash_retention_days = 0;
+    let (router, state) = race_timing::setup(config).await;
+    let cookie = login(&router).await;
+    let (race_count, participant_count) = state
+        .with_connection(|conn| {
+            let races = races::table.count().get_result::<i64>(conn)?;
+            let participants = participants::table.count().get_result::<i64>(conn)?;
+            diesel::QueryResult::Ok((races, participants))
+        })
+        .await
+        .unwrap();
+    assert!(race_count > 0 && participant_count > 0);
+    let resp = post_form(&router, &cookie, "/admin/competitions/1/delete", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, "/admin/trash.html").await;
+    assert!(string.contains("✓"), "{string}");
+
+    let resp = post_form(&router, &cookie, "/admin/trash/purge", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (competitions, participants) = state
+        .with_connection(|conn| {
+            let competitions = competitions::table.count().get_result::<i64>(conn)?;
+            let participants = participants::table.count().get_result::<i64>(conn)?;
+            diesel::QueryResult::Ok((competitions, participants))
+        })
+        .await
+        .unwrap();
+    assert_eq!(competitions, 0);
+    assert_eq!(participants, 0);
+
+    // rows removed by cascading deletes are recorded as well
+    let deletions = |entity: &'static str| {
+        state.with_connection(move |conn| {
+            audit_log::table
+                .filter(audit_log::entity.eq(entity))
+                .filter(audit_log::action.eq("delete"))
+                .filter(audit_log::after.is_null())
+                .count()
+                .get_result::<i64>(conn)
+        })
+    };
+    assert_eq!(deletions("competition").await.unwrap(), 1);
+    assert_eq!(deletions("race").await.unwrap(), race_count);
+    assert_eq!(deletions("participant").await.unwrap(), participant_count);
+    assert!(deletions("start").await.unwrap() > 0);
+    assert!(deletions("category").await.unwrap() > 0);
+}
+
+#[tokio::test]
+async fn deletion_requires_confirmation_and_csrf_token() {
+    let (router, _state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+
+    // the GET request only renders a confirmation page
+    let (status, string) = get_page(&router, &cookie, "/admin/competitions/1/delete.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(string.contains("Competition #1"), "{string}");
+    assert!(string.contains("method=\"post\""), "{string}");
+    // cancelling returns to the page the deletion was started from
+    let (status, string) = get_page(
+        &router,
+        &cookie,
+        "/admin/races/1/delete.html?redirect_to=competitions/1/races.html",
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        string.contains("href=\"/admin/competitions&#x2f;1&#x2f;races.html\""),
+        "{string}"
+    );
+    let (_, string) = get_page(&router, &cookie, "/admin/trash.html").await;
+    assert!(
+        !string.contains("/admin/trash/competitions/1/restore"),
+        "{string}"
+    );
+
+    // posting without or with a wrong token is rejected
+    for body in ["", "csrf_token=invalid"] {
+        let resp = router
+            .clone()
+            .oneshot(
+                Request::post("/admin/competitions/1/delete")
+                    .header("Cookie", &cookie)
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+    // the token of a different session is rejected as well
+    let other_cookie = login(&router).await;
+    let other_token = csrf_token(&router, &other_cookie).await;
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::post("/admin/competitions/1/delete")
+                .header("Cookie", &cookie)
+                .header("X-CSRF-Token", &other_token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // the token can also be sent as header
+    let token = csrf_token(&router, &cookie).await;
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::post("/admin/competitions/1/delete")
+                .header("Cookie", &cookie)
+                .header("X-CSRF-Token", &token)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (_, string) = get_page(&router, &cookie, "/admin/trash.html").await;
+    assert!(
+        string.contains("/admin/trash/competitions/1/restore"),
+        "{string}"
+    );
+}
+
+// try to login as `admin` with the given password from the given address
+async fn try_login(router: &axum::Router, password: &str, ip: [u8; 4]) -> axum::response::Response {
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/login.html")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let cookie = session_cookie(&resp);
+    let token = csrf_token(router, &cookie).await;
+    router
+        .clone()
+        .oneshot(
+            Request::post("/admin/login")
+                .header("Cookie", &cookie)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .extension(axum::extract::ConnectInfo(std::net::SocketAddr::from((
+                    ip, 4242,
+                ))))
+                .body(Body::from(format!(
+                    "csrf_token={token}&name=admin&password={password}"
+                )))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn login_throttling() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::login_throttles;
+
+    let mut config = test_config(true);
+    config.login_lockout_attempts = 4;
+    let (router, state) = race_timing::setup(config).await;
+    let cookie = login(&router).await;
+
+    // expired counters are removed with the next failed attempt
+    state
+        .with_connection(|conn| {
+            diesel::insert_into(login_throttles::table)
+                .values((
+                    login_throttles::kind.eq("username"),
+                    login_throttles::key.eq("random-user-name"),
+                    login_throttles::failed_attempts.eq(1),
+                    login_throttles::last_failed_at.eq(time::macros::datetime!(2020-01-01 12:00)),
+                ))
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let resp = try_login(&router, "wrong", [10, 0, 0, 1]).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+    // further attempts need to wait, even with the correct password
+    let resp = try_login(&router, "admin", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("Retry-After"));
+    let (status, string) = get_page(&router, &cookie, "/admin/login_throttles.html").await;
+    assert_eq!(status, StatusCode::OK, "{string}");
+    assert!(string.contains("<td>admin</td>"), "{string}");
+    assert!(string.contains("<td>10.0.0.1</td>"), "{string}");
+    assert!(string.contains("<td>3</td>"), "{string}");
+    assert!(!string.contains("random-user-name"), "{string}");
+
+    // reaching the maximal number of attempts locks the login
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let resp = try_login(&router, "wrong", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let resp = try_login(&router, "admin", [10, 0, 0, 2]).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let (_, string) = get_page(&router, &cookie, "/admin/login_throttles.html").await;
+    assert!(string.contains("✓"), "{string}");
+
+    // admins can clear the lockout
+    let ids = state
+        .with_connection(|conn| {
+            login_throttles::table
+                .select(login_throttles::id)
+                .load::<i32>(conn)
+        })
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 2);
+    for id in ids {
+        let resp = post_form(
+            &router,
+            &cookie,
+            &format!("/admin/login_throttles/{id}/clear"),
+            "",
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    }
+    let resp = try_login(&router, "admin", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+}
+
+#[tokio::test]
+async fn session_listing_revocation_and_logout() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::session_records;
+
+    async fn public_ids(state: &race_timing::app_state::State) -> Vec<String> {
+        state
+            .with_connection(|conn| {
+                session_records::table
+                    .filter(session_records::user_id.is_not_null())
+                    .select(session_records::public_id)
+                    .load::<String>(conn)
+            })
+            .await
+            .unwrap()
+    }
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let other_cookie = login(&router).await;
+
+    let (status, string) = get_page(&router, &cookie, "/admin/sessions.html").await;
+    assert_eq!(status, StatusCode::OK, "{string}");
+    assert_eq!(string.matches("/revoke\"").count(), 2, "{string}");
+    assert_eq!(string.matches("✓").count(), 1, "{string}");
+
+    // revoking all other sessions logs out the other session
+    let resp = post_form(&router, &cookie, "/admin/sessions/revoke_others", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (status, _) = get_page(&router, &other_cookie, "/admin/sessions.html").await;
+    assert_ne!(status, StatusCode::OK);
+    let (status, string) = get_page(&router, &cookie, "/admin/sessions.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(string.matches("/revoke\"").count(), 1, "{string}");
+
+    // revoking a single session
+    let existing = public_ids(&state).await;
+    let other_cookie = login(&router).await;
+    let new_session = public_ids(&state)
+        .await
+        .into_iter()
+        .find(|id| !existing.contains(id))
+        .unwrap();
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/sessions/{new_session}/revoke"),
+        "",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (status, _) = get_page(&router, &other_cookie, "/admin/sessions.html").await;
+    assert_ne!(status, StatusCode::OK);
+
+    // logout ends the current session
+    let resp = post_form(&router, &cookie, "/admin/logout", "").await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (status, _) = get_page(&router, &cookie, "/admin/sessions.html").await;
+    assert_ne!(status, StatusCode::OK);
+    assert!(public_ids(&state).await.is_empty());
+}
+
+#[tokio::test]
+async fn expired_sessions_are_rejected_and_purged() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::session_records;
+
+    let mut config = test_config(true);
+    config.session_cleanup_interval_secs = 1;
+    let (router, state) = race_timing::setup(config).await;
+    let cookie = login(&router).await;
+    let (status, _) = get_page(&router, &cookie, "/admin/sessions.html").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // let the session of the logged in user expire
+    state
+        .with_connection(|conn| {
+            diesel::update(session_records::table.filter(session_records::user_id.is_not_null()))
+                .set(
+                    session_records::expiry_date
+                        .eq(time::OffsetDateTime::now_utc() - time::Duration::minutes(1)),
+                )
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+    let (status, _) = get_page(&router, &cookie, "/admin/sessions.html").await;
+    assert_ne!(status, StatusCode::OK);
+
+    // the cleanup task removes the expired session
+    tokio::time::sleep(std::time::Duration::from_millis(2500)).await;
+    let expired = state
+        .with_connection(|conn| {
+            session_records::table
+                .filter(session_records::user_id.is_not_null())
+                .count()
+                .get_result::<i64>(conn)
+        })
+        .await
+        .unwrap();
+    assert_eq!(expired, 0);
+}
+
+#[tokio::test]
+async fn outdated_password_hashes_are_upgraded() {
+    use argon2::password_hash::SaltString;
+    use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+    use diesel::prelude::*;
+    use race_timing::database::schema::users;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let salt = SaltString::generate(&mut rand::rngs::OsRng);
+    let outdated = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(b"admin", &salt)
+    .unwrap()
+    .to_string();
+    let stored = outdated.clone();
+    state
+        .with_connection(move |conn| {
+            diesel::update(users::table.filter(users::name.eq("admin")))
+                .set(users::password.eq(stored))
+                .execute(conn)
+        })
+        .await
+        .unwrap();
+
+    let resp = try_login(&router, "admin", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let hash = state
+        .with_connection(|conn| {
+            users::table
+                .filter(users::name.eq("admin"))
+                .select(users::password)
+                .first::<String>(conn)
+        })
+        .await
+        .unwrap();
+    assert_ne!(hash, outdated);
+    assert!(hash.contains("m=19456,t=2,p=1"), "{hash}");
+    // the new hash still accepts the same password
+    let resp = try_login(&router, "admin", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+}
+
+#[tokio::test]
+async fn change_password() {
+    let (router, _state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let other_cookie = login(&router).await;
+
+    let change = |current: &'static str, new: &'static str, confirmation: &'static str| {
+        let router = router.clone();
+        let cookie = cookie.clone();
+        async move {
+            let resp = post_form(
+                &router,
+                &cookie,
+                "/admin/password",
+                format!(
+                    "current_password={current}&new_password={new}\
+                     &new_password_confirmation={confirmation}"
+                ),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let data = resp.into_body().collect().await.unwrap().to_bytes();
+            String::from_utf8(data.to_vec()).unwrap()
+        }
+    };
+
+    let string = change("wrong", "correct-horse-battery", "correct-horse-battery").await;
+    assert!(string.contains("The current password is wrong"), "{string}");
+    let string = change("admin", "short", "short").await;
+    assert!(string.contains("at least 12 characters"), "{string}");
+    let string = change("admin", "my-admin-password", "my-admin-password").await;
+    assert!(
+        string.contains("must not contain the user name"),
+        "{string}"
+    );
+    let string = change("admin", "correct-horse-battery", "correct-horse-staple").await;
+    assert!(string.contains("do not match"), "{string}");
+
+    let string = change("admin", "correct-horse-battery", "correct-horse-battery").await;
+    assert!(string.contains("Your password was changed"), "{string}");
+    // the current session stays logged in, other sessions are logged out
+    let (status, _) = get_page(&router, &cookie, "/admin/sessions.html").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_page(&router, &other_cookie, "/admin/sessions.html").await;
+    assert_ne!(status, StatusCode::OK);
+
+    let resp = try_login(&router, "admin", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = try_login(&router, "correct-horse-battery", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    // wrong current passwords count towards the login throttling
+    for _ in 0..3 {
+        let string = change("wrong", "another-long-secret", "another-long-secret").await;
+        assert!(string.contains("The current password is wrong"), "{string}");
+    }
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/password",
+        "current_password=correct-horse-battery&new_password=another-long-secret\
+         &new_password_confirmation=another-long-secret",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let resp = try_login(&router, "correct-horse-battery", [10, 0, 0, 2]).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[test]
+fn totp_codes_match_rfc_6238() {
+    use race_timing::admin::user::totp::code_at;
+
+    // test vectors for SHA1 from RFC 6238, truncated to six digits
+    let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    assert_eq!(code_at(secret, 59).as_deref(), Some("287082"));
+    assert_eq!(code_at(secret, 1111111109).as_deref(), Some("081804"));
+    assert_eq!(code_at(secret, 1234567890).as_deref(), Some("005924"));
+    assert_eq!(code_at(secret, 2000000000).as_deref(), Some("279037"));
+    assert_eq!(code_at("not base32!", 59), None);
+}
+
+// login as `admin` and answer the second step with the given code
+async fn login_with_second_factor(router: &axum::Router, code: &str) -> axum::response::Response {
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/login.html")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let cookie = session_cookie(&resp);
+    let token = csrf_token(router, &cookie).await;
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::post("/admin/login")
+                .header("Cookie", &cookie)
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(format!(
+                    "csrf_token={token}&name=admin&password=admin"
+                )))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers()["Location"], "/admin/login/totp.html");
+    let (status, string) = get_page(router, &cookie, "/admin/login/totp.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(string.contains("authenticator app"), "{string}");
+    post_form(router, &cookie, "/admin/login/totp", format!("code={code}")).await
+}
+
+#[tokio::test]
+async fn two_factor_authentication() {
+    use race_timing::admin::user::totp::code_at;
+
+    let now = 1_700_000_000;
+    let mut config = test_config(true);
+    config.fixed_time = Some(time::OffsetDateTime::from_unix_timestamp(now as i64).unwrap());
+    let (router, _state) = race_timing::setup(config).await;
+    let cookie = login(&router).await;
+
+    let resp = post_form(&router, &cookie, "/admin/two_factor/setup", "").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let string = String::from_utf8(data.to_vec()).unwrap();
+    assert!(string.contains("<svg"), "{string}");
+    let (_, rest) = string.split_once("<code>").unwrap();
+    let secret = rest.split('<').next().unwrap().to_owned();
+
+    // a code from a different point in time is rejected
+    let wrong = code_at(&secret, now - 3600).unwrap();
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/two_factor/enable",
+        format!("code={wrong}"),
+    )
+    .await;
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let string = String::from_utf8(data.to_vec()).unwrap();
+    assert!(
+        string.contains("The authentication code is invalid"),
+        "{string}"
+    );
+
+    let code = code_at(&secret, now).unwrap();
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/two_factor/enable",
+        format!("code={code}"),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let string = String::from_utf8(data.to_vec()).unwrap();
+    let recovery_codes = string
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s.split('<').next().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(recovery_codes.len(), 10, "{string}");
+    let (_, string) = get_page(&router, &cookie, "/admin/two_factor.html").await;
+    assert!(string.contains("Unused recovery codes: 10"), "{string}");
+
+    // the password alone is not enough anymore
+    let (status, _) = get_page(&router, "", "/admin/login/totp.html").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let resp = login_with_second_factor(&router, "123 456").await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    // the code used for the setup cannot be replayed
+    let resp = login_with_second_factor(&router, &code).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    // codes of the next time step are accepted to allow for clock drift
+    let next_code = code_at(&secret, now + 30).unwrap();
+    let resp = login_with_second_factor(&router, &next_code).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers()["Location"], "/admin/competitions/index.html");
+    let new_cookie = session_cookie(&resp);
+    let (status, _) = get_page(&router, &new_cookie, "/admin/two_factor.html").await;
+    assert_eq!(status, StatusCode::OK);
+    let resp = login_with_second_factor(&router, &next_code).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // each recovery code works exactly once
+    let resp = login_with_second_factor(&router, &recovery_codes[0].to_uppercase()).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = login_with_second_factor(&router, &recovery_codes[0]).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let (_, string) = get_page(&router, &cookie, "/admin/two_factor.html").await;
+    assert!(string.contains("Unused recovery codes: 9"), "{string}");
+
+    // disabling requires a valid code as well
+    let resp = post_form(&router, &cookie, "/admin/two_factor/disable", "code=000000").await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/two_factor/disable",
+        format!("code={}", recovery_codes[1]),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = try_login(&router, "admin", [10, 0, 0, 1]).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(resp.headers()["Location"], "/admin/competitions/index.html");
+}
+
+// send a request authenticated with the given API token instead of a session
+async fn api_request(
+    router: &axum::Router,
+    method: &str,
+    uri: &str,
+    token: &str,
+) -> axum::response::Response {
+    router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn api_tokens() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::api_tokens;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+
+    let create = |body: &'static str| {
+        let router = router.clone();
+        let cookie = cookie.clone();
+        async move {
+            let resp = post_form(&router, &cookie, "/admin/api_tokens", body).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let data = resp.into_body().collect().await.unwrap().to_bytes();
+            let string = String::from_utf8(data.to_vec()).unwrap();
+            let (_, rest) = string.split_once("<code id=\"new_token\">").unwrap();
+            rest.split('<').next().unwrap().to_owned()
+        }
+    };
+    let write_token = create("name=timing+bridge&scope=write&expires_on=").await;
+    let read_token = create("name=display&scope=read&expires_on=").await;
+    let expired_token = create("name=old&scope=write&expires_on=2020-01-01").await;
+    assert!(write_token.starts_with("rt_"), "{write_token}");
+
+    // only hashes are stored
+    let hashes = state
+        .with_connection(|conn| {
+            api_tokens::table
+                .select(api_tokens::token_hash)
+                .load::<String>(conn)
+        })
+        .await
+        .unwrap();
+    assert_eq!(hashes.len(), 3);
+    assert!(!hashes.contains(&write_token));
+
+    // a valid token works without a session or CSRF token
+    let resp = api_request(&router, "GET", "/admin/trash.html", &write_token).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("Set-Cookie").is_none());
+    let resp = api_request(&router, "POST", "/admin/trash/purge", &write_token).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    // read only tokens cannot change anything
+    let resp = api_request(&router, "GET", "/admin/trash.html", &read_token).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = api_request(&router, "POST", "/admin/trash/purge", &read_token).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // unknown and expired tokens are rejected
+    let resp = api_request(&router, "GET", "/admin/trash.html", "rt_unknown").await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = api_request(&router, "GET", "/admin/trash.html", &expired_token).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let (_, string) = get_page(&router, &cookie, "/admin/api_tokens.html").await;
+    assert!(string.contains("timing bridge"), "{string}");
+    assert!(string.contains("expired"), "{string}");
+    assert!(!string.contains(&write_token));
+
+    // revoked tokens are rejected
+    let token_id = state
+        .with_connection(|conn| {
+            api_tokens::table
+                .filter(api_tokens::name.eq("timing bridge"))
+                .select(api_tokens::id)
+                .first::<i32>(conn)
+        })
+        .await
+        .unwrap();
+    let resp = post_form(
+        &router,
+        &cookie,
+        &format!("/admin/api_tokens/{token_id}/revoke"),
+        "",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = api_request(&router, "GET", "/admin/trash.html", &write_token).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn configuration_layers() {
+    use clap::Parser;
+    use race_timing::service_config::{Cli, Config, ConfigError};
+
+    let template_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("templates");
+    let dir = std::env::temp_dir().join(format!("race_timing_config_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("config.toml");
+    std::fs::write(
+        &file,
+        format!(
+            "port = 9000\nbase_url = \"/timing\"\nlogin_lockout_attempts = 5\ntemplate_dir = {:?}\n",
+            template_dir
+        ),
+    )
+    .unwrap();
+
+    // file < environment < command line
+    std::env::set_var("RACE_TIMING_LOGIN_LOCKOUT_ATTEMPTS", "7");
+    let cli = Cli::try_parse_from([
+        "race_timing",
+        "--config",
+        file.to_str().unwrap(),
+        "config",
+        "check",
+        "--base-url",
+        "/results",
+    ])
+    .unwrap();
+    std::env::remove_var("RACE_TIMING_LOGIN_LOCKOUT_ATTEMPTS");
+    let config = Config::load(&cli).unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.login_lockout_attempts, 7);
+    assert_eq!(config.base_url, "/results");
+    // untouched values keep their defaults
+    assert_eq!(config.login_lockout_minutes, 15);
+    assert_eq!(config.slow_query_threshold_ms, 500);
+    assert!(config.to_toml().contains("port = 9000"));
+
+    // unknown keys are reported with their name
+    std::fs::write(&file, "prot = 9000\n").unwrap();
+    let cli = Cli::try_parse_from(["race_timing", "--config", file.to_str().unwrap()]).unwrap();
+    let err = Config::load(&cli).unwrap_err();
+    assert!(matches!(err, ConfigError::Parse { .. }), "{err}");
+    assert!(err.to_string().contains("unknown field `prot`"), "{err}");
+
+    // values are validated after merging all layers
+    std::fs::write(&file, "base_url = \"timing/\"\n").unwrap();
+    let cli = Cli::try_parse_from(["race_timing", "--config", file.to_str().unwrap()]).unwrap();
+    let err = Config::load(&cli).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ConfigError::Invalid {
+                field: "base_url",
+                ..
+            }
+        ),
+        "{err}"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn database_maintenance() {
+    use race_timing::database::maintenance;
+
+    let mut conn = maintenance::establish(&test_database_url()).unwrap();
+    let status = maintenance::status(&mut conn).unwrap();
+    assert!(!status.is_empty());
+    assert!(status.iter().all(|m| !m.applied));
+
+    let applied = maintenance::migrate(&mut conn).unwrap();
+    assert_eq!(applied.len(), status.len());
+    assert!(maintenance::migrate(&mut conn).unwrap().is_empty());
+    assert!(maintenance::status(&mut conn)
+        .unwrap()
+        .iter()
+        .all(|m| m.applied));
+
+    // only the most recent migration is reverted
+    let reverted = maintenance::revert(&mut conn).unwrap();
+    let status = maintenance::status(&mut conn).unwrap();
+    let (last, rest) = status.split_last().unwrap();
+    assert!(!last.applied);
+    assert!(
+        last.name.replace('-', "").starts_with(&reverted),
+        "{reverted}"
+    );
+    assert!(rest.iter().all(|m| m.applied));
+    assert_eq!(maintenance::migrate(&mut conn).unwrap(), vec![reverted]);
+
+    maintenance::seed(&mut conn).unwrap();
+    assert!(maintenance::seed(&mut conn).is_err());
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn database_check() {
+    use diesel::prelude::*;
+    use race_timing::database::maintenance;
+
+    let mut conn = maintenance::establish(":memory:").unwrap();
+    maintenance::migrate(&mut conn).unwrap();
+    maintenance::seed(&mut conn).unwrap();
+    assert!(maintenance::check(&mut conn).unwrap().is_empty());
+
+    // entries with missing parents are reported
+    diesel::sql_query("PRAGMA foreign_keys = OFF;")
+        .execute(&mut conn)
+        .unwrap();
+    diesel::sql_query("INSERT INTO recovery_codes(user_id, code_hash) VALUES (4242, 'x');")
+        .execute(&mut conn)
+        .unwrap();
+    let problems = maintenance::check(&mut conn).unwrap();
+    assert_eq!(problems.len(), 1, "{problems:?}");
+    assert!(problems[0].contains("`recovery_codes`"), "{problems:?}");
+}
+
+#[tokio::test]
+#[cfg(feature = "sqlite")]
+async fn online_backups_and_restore() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::competitions;
+    use race_timing::database::{backup, maintenance};
+    use time::macros::datetime;
+
+    let (router, state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let competition_count = state
+        .with_connection(|conn| competitions::table.count().get_result::<i64>(conn))
+        .await
+        .unwrap();
+
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/admin/backup/download")
+                .header("Cookie", &cookie)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let disposition = resp.headers()["Content-Disposition"].to_str().unwrap();
+    assert!(
+        disposition.starts_with("attachment; filename=\"race_time-"),
+        "{disposition}"
+    );
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert!(data.starts_with(b"SQLite format 3\0"));
+
+    // backups contain session ids and secrets, so API tokens cannot download them
+    for scope in ["read", "write"] {
+        let resp = post_form(
+            &router,
+            &cookie,
+            "/admin/api_tokens",
+            format!("name=backup&scope={scope}&expires_on="),
+        )
+        .await;
+        let body = resp.into_body().collect().await.unwrap().to_bytes();
+        let string = String::from_utf8(body.to_vec()).unwrap();
+        let (_, rest) = string.split_once("<code id=\"new_token\">").unwrap();
+        let token = rest.split('<').next().unwrap();
+        let resp = api_request(&router, "GET", "/admin/backup/download", token).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN, "{scope}");
+    }
+
+    // only the most recent backups are kept
+    let dir = std::env::temp_dir().join(format!("race_timing_backup_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for taken_at in [
+        datetime!(2026-10-18 08:00 UTC),
+        datetime!(2026-10-18 10:00 UTC),
+        datetime!(2026-10-18 09:00 UTC),
+    ] {
+        backup::write(&dir, &data, taken_at).unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "keep me").unwrap();
+    let removed = backup::rotate(&dir, 2).unwrap();
+    assert_eq!(removed, vec![dir.join("race_time-20261018-080000.db")]);
+    let kept = backup::list(&dir).unwrap();
+    assert_eq!(
+        kept,
+        vec![
+            dir.join("race_time-20261018-090000.db"),
+            dir.join("race_time-20261018-100000.db"),
+        ]
+    );
+    assert!(dir.join("notes.txt").exists());
+
+    // a restored database contains the data at the time of the backup
+    let database = dir.join("restored.db");
+    let database_url = database.to_str().unwrap();
+    maintenance::restore(&kept[1], database_url).unwrap();
+    let mut conn = maintenance::establish(database_url).unwrap();
+    let restored_count = competitions::table
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(restored_count, competition_count);
+    assert!(maintenance::check(&mut conn).unwrap().is_empty());
+    drop(conn);
+
+    // other files are rejected and leave the database untouched
+    let err = maintenance::restore(&dir.join("notes.txt"), database_url).unwrap_err();
+    assert!(err.to_string().contains("notes.txt"), "{err}");
+    let err = maintenance::restore(&dir.join("missing.db"), database_url).unwrap_err();
+    assert!(err.to_string().contains("does not exist"), "{err}");
+    let mut conn = maintenance::establish(database_url).unwrap();
+    assert!(maintenance::check(&mut conn).unwrap().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+#[cfg(feature = "sqlite")]
+async fn restore_backup_of_file_database() {
+    use diesel::prelude::*;
+    use race_timing::database::schema::competitions;
+    use race_timing::database::{backup, maintenance};
+
+    // characters with a special meaning in SQLite URIs
+    let dir = std::env::temp_dir().join(format!("race_timing_restore_{}_?#%", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let database = dir.join("race_time.db");
+    let database_url = database.to_str().unwrap();
+
+    let mut config = test_config(true);
+    config.database_url = database_url.into();
+    let (router, state) = race_timing::setup(config).await;
+    let (journal_mode, data) = state
+        .with_connection(|conn| {
+            #[derive(QueryableByName)]
+            struct JournalMode {
+                #[diesel(sql_type = diesel::sql_types::Text)]
+                journal_mode: String,
+            }
+            let mode = diesel::sql_query("PRAGMA journal_mode;").get_result::<JournalMode>(conn)?;
+            QueryResult::Ok((mode.journal_mode, backup::snapshot(conn)))
+        })
+        .await
+        .unwrap();
+    assert_eq!(journal_mode, "wal");
+    let competition_count = state
+        .with_connection(|conn| competitions::table.count().get_result::<i64>(conn))
+        .await
+        .unwrap();
+    let backup = backup::write(&dir, &data, time::OffsetDateTime::now_utc()).unwrap();
+    state
+        .with_connection(|conn| diesel::delete(competitions::table).execute(conn))
+        .await
+        .unwrap();
+    // the server must not be running during a restore
+    drop((router, state));
+
+    maintenance::restore(&backup, database_url).unwrap();
+    let mut conn = maintenance::establish(database_url).unwrap();
+    let restored_count = competitions::table
+        .count()
+        .get_result::<i64>(&mut conn)
+        .unwrap();
+    assert_eq!(restored_count, competition_count);
+    assert!(maintenance::check(&mut conn).unwrap().is_empty());
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn prometheus_metrics() {
+    let (router, _state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (status, _) = get_page(&router, &cookie, "/admin/persons/index.html").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_page(&router, &cookie, "/does_not_exist.html").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let resp = router
+        .clone()
+        .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let metrics = String::from_utf8(data.to_vec()).unwrap();
+
+    // requests are labeled with their route instead of the full path
+    assert!(
+        metrics.contains(
+            "race_timing_http_requests_total{method=\"GET\",route=\"/admin/persons/index.html\",status=\"200\"} 1"
+        ),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains(
+            "race_timing_http_requests_total{method=\"GET\",route=\"unmatched\",status=\"404\"} 1"
+        ),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains(
+            "race_timing_http_request_duration_seconds_count{method=\"POST\",route=\"/admin/login\"} 1"
+        ),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains("race_timing_db_pool_max_size 1"),
+        "{metrics}"
+    );
+    assert!(
+        metrics.contains("race_timing_db_pool_waiting 0"),
+        "{metrics}"
+    );
+    let query_count = metrics
+        .lines()
+        .find_map(|l| l.strip_prefix("race_timing_db_query_duration_seconds_count "))
+        .unwrap();
+    assert!(query_count.parse::<u64>().unwrap() > 0, "{metrics}");
+    assert!(
+        metrics.contains(
+            "race_timing_registrations{competition=\"Country Cross Race Vienna 2024\",competition_id=\"1\"}"
+        ),
+        "{metrics}"
+    );
+}
+
+/// Log output collected by a `tracing` subscriber
+#[derive(Clone, Default)]
+struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn slow_queries_are_logged() {
+    use diesel::prelude::*;
+    use race_timing::database::instrumentation::QueryInstrumentation;
+    use race_timing::database::maintenance;
+    use std::time::Duration;
+
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let histogram =
+        prometheus::Histogram::with_opts(prometheus::HistogramOpts::new("test", "test")).unwrap();
+
+    let mut conn = maintenance::establish(&test_database_url()).unwrap();
+    maintenance::migrate(&mut conn).unwrap();
+    tracing::subscriber::with_default(subscriber, || {
+        // nothing is slow with a generous threshold
+        conn.set_instrumentation(QueryInstrumentation::new(
+            Some(Duration::from_secs(60)),
+            histogram.clone(),
+        ));
+        diesel::sql_query("SELECT 1").execute(&mut conn).unwrap();
+        assert!(logs.0.lock().unwrap().is_empty());
+
+        // every query is slow with a zero threshold
+        conn.set_instrumentation(QueryInstrumentation::new(
+            Some(Duration::ZERO),
+            histogram.clone(),
+        ));
+        race_timing::database::schema::participants::table
+            .filter(race_timing::database::schema::participants::last_name.eq("Secret"))
+            .count()
+            .get_result::<i64>(&mut conn)
+            .unwrap();
+    });
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("WARN"), "{logs}");
+    assert!(logs.contains("Slow query"), "{logs}");
+    assert!(logs.contains("binds=1"), "{logs}");
+    assert!(logs.contains("duration_ms="), "{logs}");
+    assert!(logs.contains("FROM"), "{logs}");
+    // bind values may contain personal data
+    assert!(!logs.contains("Secret"), "{logs}");
+    assert_eq!(histogram.get_sample_count(), 2);
+}
+
+async fn get_json(router: &axum::Router, uri: &str) -> (StatusCode, serde_json::Value) {
+    let resp = router
+        .clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = resp.status();
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&data).unwrap())
+}
+
+#[tokio::test]
+async fn health_and_readiness() {
+    use diesel_migrations::MigrationHarness;
+
+    let (router, state) = race_timing::setup(test_config(false)).await;
+    let (status, health) = get_json(&router, "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(health, serde_json::json!({"status": "ok"}));
+
+    let (status, ready) = get_json(&router, "/readyz").await;
+    assert_eq!(status, StatusCode::OK, "{ready}");
+    assert_eq!(
+        ready,
+        serde_json::json!({
+            "status": "ok",
+            "components": {
+                "database": {"status": "ok"},
+                "migrations": {"status": "ok"},
+                "templates": {"status": "ok"},
+            }
+        })
+    );
+
+    // an outdated schema is reported, the database itself is still fine
+    state
+        .pool
+        .get()
+        .await
+        .unwrap()
+        .interact(|conn| {
+            conn.revert_last_migration(race_timing::database::maintenance::MIGRATIONS)
+                .unwrap();
+        })
+        .await
+        .unwrap();
+    let (status, ready) = get_json(&router, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready["status"], "error");
+    assert_eq!(ready["components"]["database"]["status"], "ok");
+    assert_eq!(ready["components"]["migrations"]["status"], "error");
+    assert!(ready["components"]["migrations"]["error"].is_string());
+
+    // broken templates are reported by name
+    let dir = std::env::temp_dir().join(format!("race_timing_templates_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("broken.html"), "{% if %}").unwrap();
+    let mut config = test_config(false);
+    config.template_dir = Some(dir.clone());
+    let (router, _state) = race_timing::setup(config).await;
+    let (status, ready) = get_json(&router, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(ready["components"]["migrations"]["status"], "ok");
+    assert_eq!(ready["components"]["templates"]["status"], "error");
+    assert!(ready["components"]["templates"]["error"]
+        .as_str()
+        .unwrap()
+        .contains("broken.html"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn shutdown_checkpoints_the_wal_file() {
+    use race_timing::database::maintenance;
+
+    let dir = std::env::temp_dir().join(format!("race_timing_shutdown_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let database = dir.join("race_time.db");
+    let wal = dir.join("race_time.db-wal");
+    let mut config = test_config(true);
+    config.database_url = database.to_str().unwrap().to_owned();
+
+    let (_router, state) = race_timing::setup(config).await;
+    // keep a connection open, otherwise SQLite removes the WAL file on its own
+    let pool = state.pool.clone();
+    let mut conn = maintenance::establish(&database.to_string_lossy()).unwrap();
+    assert!(maintenance::status(&mut conn)
+        .unwrap()
+        .iter()
+        .all(|m| m.applied));
+    assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+
+    race_timing::shutdown(state).await;
+    assert_eq!(std::fs::metadata(&wal).unwrap().len(), 0);
+    assert!(pool.is_closed());
+    drop(conn);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn embedded_templates_and_overrides() {
+    let dir = std::env::temp_dir().join(format!("race_timing_overrides_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let login_page = dir.join("login.html");
+
+    // the embedded templates do not depend on the working directory
+    let (router, _state) = race_timing::setup(test_config(false)).await;
+    let (status, page) = get_page(&router, "", "/admin/login.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("<form"), "{page}");
+
+    // templates in the template directory replace the embedded ones,
+    // everything else is still embedded
+    std::fs::write(
+        &login_page,
+        "{% extends \"base.html\" %}{% block body %}Custom login{% endblock %}",
+    )
+    .unwrap();
+    let mut config = test_config(false);
+    config.template_dir = Some(dir.clone());
+    let (router, _state) = race_timing::setup(config.clone()).await;
+    let (status, page) = get_page(&router, "", "/admin/login.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("Custom login"), "{page}");
+    assert!(page.contains("<html"), "{page}");
+    // loaded templates are kept
+    std::fs::write(&login_page, "Changed login").unwrap();
+    let (_, page) = get_page(&router, "", "/admin/login.html").await;
+    assert!(page.contains("Custom login"), "{page}");
+
+    // unless they are reloaded on every render
+    config.template_reload = true;
+    let (router, _state) = race_timing::setup(config).await;
+    let (_, page) = get_page(&router, "", "/admin/login.html").await;
+    assert_eq!(page, "Changed login");
+    std::fs::write(&login_page, "Changed again").unwrap();
+    let (_, page) = get_page(&router, "", "/admin/login.html").await;
+    assert_eq!(page, "Changed again");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Post a `multipart/form-data` body, file fields are given with a file name
+async fn post_multipart(
+    router: &axum::Router,
+    cookie: &str,
+    uri: &str,
+    fields: &[(&str, Option<&str>, &[u8])],
+) -> axum::response::Response {
+    const BOUNDARY: &str = "race-timing-test-boundary";
+    let mut body = Vec::new();
+    for (name, file_name, value) in fields {
+        body.extend_from_slice(format!("--{BOUNDARY}\r\n").as_bytes());
+        match file_name {
+            Some(file_name) => body.extend_from_slice(
+                format!(
+                    "Content-Disposition: form-data; name=\"{name}\"; filename=\"{file_name}\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n"
+                )
+                .as_bytes(),
+            ),
+            None => body.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n").as_bytes(),
+            ),
+        }
+        body.extend_from_slice(value);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+    router
+        .clone()
+        .oneshot(
+            Request::post(uri)
+                .header("Cookie", cookie)
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={BOUNDARY}"),
+                )
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn competition_branding() {
+    let (router, _state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let (status, page) = get_page(&router, &cookie, "/admin/competitions/1/branding.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!page.contains("/assets/competition/1/"), "{page}");
+    let (status, _) = get_page(&router, "", "/assets/competition/1/theme.css").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let logo = b"\x89PNG\r\n\x1a\nnot really an image";
+    let token = csrf_token(&router, &cookie).await;
+    let fields: [(&str, Option<&str>, &[u8]); 5] = [
+        ("csrf_token", None, token.as_bytes()),
+        ("accent_color", None, b"#1A2B3C"),
+        ("accent_background_color", None, b""),
+        ("footer", None, b"Organized by the <b>Running Club</b>"),
+        ("logo", Some("logo.png"), logo),
+    ];
+    // uploads need a CSRF token as well
+    let resp = post_multipart(
+        &router,
+        &cookie,
+        "/admin/competitions/1/branding",
+        &fields[1..],
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = post_multipart(&router, &cookie, "/admin/competitions/1/branding", &fields).await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+
+    // the edit page previews the branding
+    let (status, page) = get_page(&router, &cookie, "/admin/competitions/1/branding.html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        page.contains("Organized by the &lt;b&gt;Running Club&lt;&#x2f;b&gt;</footer>"),
+        "{page}"
+    );
+    let (_, rest) = page
+        .split_once("/assets/competition/1/theme.css?v=")
+        .unwrap();
+    let version = rest.split('"').next().unwrap();
+    assert!(
+        page.contains(&format!("/assets/competition/1/logo?v={version}")),
+        "{page}"
+    );
+
+    // the current version is cached forever, everything else is revalidated
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get(format!("/assets/competition/1/theme.css?v={version}"))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["Content-Type"], "text/css");
+    assert!(resp.headers()["Cache-Control"]
+        .to_str()
+        .unwrap()
+        .contains("immutable"));
+    let etag = resp.headers()["ETag"].clone();
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    let css = String::from_utf8(data.to_vec()).unwrap();
+    assert!(css.contains("--accent: #1a2b3c;"), "{css}");
+    assert!(!css.contains("--accent-bg"), "{css}");
+
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/assets/competition/1/logo")
+                .header("If-None-Match", etag.clone())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers()["Cache-Control"], "no-cache");
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/assets/competition/1/logo")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["Content-Type"], "image/png");
+    assert_eq!(resp.headers()["ETag"], etag);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&data[..], logo);
+
+    // invalid colors and images that are no supported image are rejected
+    let token = csrf_token(&router, &cookie).await;
+    let resp = post_multipart(
+        &router,
+        &cookie,
+        "/admin/competitions/1/branding",
+        &[
+            ("csrf_token", None, token.as_bytes()),
+            ("accent_color", None, b"red;}body{display:none"),
+        ],
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = post_multipart(
+        &router,
+        &cookie,
+        "/admin/competitions/1/branding",
+        &[
+            ("csrf_token", None, token.as_bytes()),
+            (
+                "logo",
+                Some("logo.svg"),
+                b"<svg><script>alert(1)</script></svg>",
+            ),
+        ],
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // the logo is kept unless it is removed
+    let resp = post_multipart(
+        &router,
+        &cookie,
+        "/admin/competitions/1/branding",
+        &[
+            ("csrf_token", None, token.as_bytes()),
+            ("footer", None, b""),
+            ("logo", Some(""), b""),
+        ],
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = router
+        .clone()
+        .oneshot(
+            Request::get("/assets/competition/1/logo")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let (_, page) = get_page(&router, &cookie, "/admin/competitions/1/branding.html").await;
+    assert!(!page.contains("<footer"), "{page}");
+    let resp = post_multipart(
+        &router,
+        &cookie,
+        "/admin/competitions/1/branding",
+        &[
+            ("csrf_token", None, token.as_bytes()),
+            ("remove_logo", None, b"on"),
+        ],
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let (status, _) = get_page(&router, "", "/assets/competition/1/logo").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // all changes are recorded in the audit log
+    let (_, string) = get_page(
+        &router,
+        &cookie,
+        "/admin/audit.html?entity=branding&entity_id=1&action=&user_id=",
+    )
+    .await;
+    assert!(string.contains("<td>create</td>"), "{string}");
+    assert!(string.contains("Running Club"), "{string}");
+    // removing the logo only changes `has_logo`
+    assert!(string.contains("{&quot;has_logo&quot;:false}"), "{string}");
+
+    let resp = post_multipart(
+        &router,
+        &cookie,
+        "/admin/competitions/999/branding",
+        &[("csrf_token", None, token.as_bytes())],
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn http_caching_and_compression() {
+    let (router, _state) = race_timing::setup(test_config(true)).await;
+    let cookie = login(&router).await;
+    let get = |uri: &str, headers: &[(&str, &str)]| {
+        let mut request = Request::get(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        router.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    // stylesheets are linked with the hash of their content
+    let (status, page) = get_page(&router, &cookie, "/admin/competitions/1/branding.html").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, rest) = page.split_once("/assets/custom.css?v=").unwrap();
+    let version = rest.split('"').next().unwrap();
+    assert_eq!(version.len(), 16, "{page}");
+    assert!(page.contains("/assets/simple.min.css?v="), "{page}");
+
+    let resp = get(&format!("/assets/custom.css?v={version}"), &[])
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["Content-Type"], "text/css");
+    assert_eq!(
+        resp.headers()["Cache-Control"],
+        "public, max-age=31536000, immutable"
+    );
+    let etag = resp.headers()["ETag"].to_str().unwrap().to_owned();
+    assert_eq!(etag, format!("W/\"{version}\""));
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(&data[..], include_bytes!("../assets/custom.css"));
+
+    // outdated or missing versions have to be revalidated
+    for uri in [
+        "/assets/custom.css",
+        "/assets/custom.css?v=0123456789abcdef",
+    ] {
+        let resp = get(uri, &[]).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Cache-Control"], "no-cache");
+    }
+    let resp = get("/assets/custom.css", &[("If-None-Match", &etag)])
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert!(data.is_empty());
+
+    // responses are compressed if the client supports it
+    let uncompressed = include_bytes!("../assets/simple.min.css").len();
+    for encoding in ["gzip", "br"] {
+        let resp = get("/assets/simple.min.css", &[("Accept-Encoding", encoding)])
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["Content-Encoding"], encoding);
+        assert_eq!(resp.headers()["Vary"], "accept-encoding");
+        let data = resp.into_body().collect().await.unwrap().to_bytes();
+        assert!(data.len() < uncompressed / 2, "{encoding}: {}", data.len());
+    }
+    let resp = get("/assets/simple.min.css", &[]).await.unwrap();
+    assert!(!resp.headers().contains_key("Content-Encoding"));
+
+    // public pages are revalidated with an ETag of their content
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/create",
+        "name=Cup&description=&points_per_rank=10%2C8&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = get("/series/1/standings.html", &[]).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["Cache-Control"], "no-cache");
+    let etag = resp.headers()["ETag"].to_str().unwrap().to_owned();
+    assert!(etag.starts_with("W/\""), "{etag}");
+    let resp = get(
+        "/series/1/standings.html",
+        &[("If-None-Match", &etag), ("Accept-Encoding", "gzip")],
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers()["ETag"], etag.as_str());
+    let data = resp.into_body().collect().await.unwrap().to_bytes();
+    assert!(data.is_empty());
+
+    let resp = post_form(
+        &router,
+        &cookie,
+        "/admin/series/1",
+        "name=Cup+2024&description=&points_per_rank=10%2C8&best_n=2&competition_1=on",
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+    let resp = get("/series/1/standings.html", &[("If-None-Match", &etag)])
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_ne!(resp.headers()["ETag"], etag.as_str());
+
+    let resp = get("/series/42/standings.html", &[("If-None-Match", "*")])
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert!(!resp.headers().contains_key("ETag"));
+}